@@ -0,0 +1,115 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse::ParseStream, Data, DeriveInput, Error, Expr, Fields, Ident, LitStr, Result, Token};
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "generic token types are not supported"));
+    }
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new(Span::call_site(), "Lexer can only be derived for enums"));
+    };
+
+    let mut skips = Vec::new();
+    let mut longest_match = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("lex")) {
+        attr.parse_args_with(|input: ParseStream| {
+            while !input.is_empty() {
+                let key: Ident = input.parse()?;
+                match key.to_string().as_str() {
+                    "skip" => {
+                        input.parse::<Token![=]>()?;
+                        skips.push(input.parse::<LitStr>()?);
+                    },
+                    "longest_match" => longest_match = true,
+                    _ => return Err(Error::new_spanned(key, "expected `skip` or `longest_match`")),
+                }
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut rules = Vec::new();
+    for variant in &data.variants {
+        let ident = &variant.ident;
+        let has_field = match &variant.fields {
+            Fields::Unit => false,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => true,
+            _ => return Err(Error::new_spanned(variant, "only unit variants and variants with a single field are supported")),
+        };
+
+        // The doc comments of the variant document its rules
+        let docs: Vec<_> = variant.attrs.iter().filter(|a| a.path().is_ident("doc")).collect();
+        for attr in variant.attrs.iter().filter(|a| a.path().is_ident("rule")) {
+            let (pattern, parse_with) = attr.parse_args_with(|input: ParseStream| {
+                // `lit = "->"` matches the string itself, like `lit` in `lex_rule!`
+                let pattern = if input.peek(Ident) {
+                    let key: Ident = input.parse()?;
+                    if key != "lit" {
+                        return Err(Error::new_spanned(key, "expected a string pattern or `lit = \"...\"`"))
+                    }
+                    input.parse::<Token![=]>()?;
+                    let lit: LitStr = input.parse()?;
+                    quote!(lit #lit)
+                } else {
+                    let regex: LitStr = input.parse()?;
+                    quote!(#regex)
+                };
+
+                let mut parse_with = None;
+                while !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                    if input.is_empty() {
+                        break
+                    }
+                    let key: Ident = input.parse()?;
+                    if key != "parse_with" {
+                        return Err(Error::new_spanned(key, "expected `parse_with`"))
+                    }
+                    input.parse::<Token![=]>()?;
+                    parse_with = Some(input.parse::<Expr>()?);
+                }
+                Ok((pattern, parse_with))
+            })?;
+
+            let construct = if has_field { quote!(#name::#ident(field)) } else { quote!({ let () = field; #name::#ident }) };
+            let action = match parse_with {
+                Some(parse_with) => quote!({
+                    #[allow(unused_imports)]
+                    use lexr::compat::{FromOption as _, FromResult as _, FromBool as _, FromValue as _};
+                    let outcome = lexr::compat::Outcome::of(slice, loc, |lex| (#parse_with)(lex.slice()));
+                    match (&outcome).field() {
+                        Some(field) => #construct,
+                        None => reject!(),
+                    }
+                }),
+                // A field without a parser is parsed with `FromStr`
+                None if has_field => quote!(match slice.parse() {
+                    Ok(field) => #name::#ident(field),
+                    Err(_) => reject!(),
+                }),
+                None => quote!(#name::#ident),
+            };
+            rules.push(quote!(#(#docs)* #pattern => |slice, _, loc| #action,));
+        }
+    }
+
+    let skip_rules = skips.iter().map(|s| quote!(#s => |_| continue,));
+    Ok(quote! {
+        impl #name {
+            /// Creates a lexer for the source, generated from the `#[rule]` attributes of the variants.
+            pub fn lexer<'src>(source: &'src str) -> lexr::Lexer<Self, impl lexr::RuleLexer<'src, Item = (Self, lexr::SrcLoc)>> {
+                lexr::lex_rule!{lex -> #name {
+                    config { longest_match: #longest_match },
+                    #(#skip_rules)*
+                    #(#rules)*
+                }}
+                lex(source)
+            }
+        }
+    })
+}
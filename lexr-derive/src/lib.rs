@@ -0,0 +1,56 @@
+//! Derive macros for [lexr](https://docs.rs/lexr).
+//!
+//! The macros are re-exported by lexr, and should be used from there.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod lex;
+mod logos;
+mod token_shape;
+mod token_class;
+
+/// Generates a lexer from `#[rule(...)]` attributes on the variants of a token enum.
+///
+/// See `lexr::lex_rule!` for the patterns, and the lexr crate root for the attributes.
+#[proc_macro_derive(Lexer, attributes(lex, rule))]
+pub fn derive_lexer(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    lex::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generates a lexr lexer from logos-style token definitions.
+///
+/// See `lexr::compat` for the supported attributes.
+#[proc_macro_derive(Logos, attributes(logos, token, regex))]
+pub fn derive_logos(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    logos::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Describes the sizes of the variants of a token enum, for `lexr::shape::analyze`.
+///
+/// With `#[token_shape(max_size = 16)]`, compilation fails if the enum is larger than 16 bytes.
+#[proc_macro_derive(TokenShape, attributes(token_shape))]
+pub fn derive_token_shape(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    token_shape::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Classifies the variants of a token enum for editor features, for `lexr::token_class`.
+///
+/// Variants are marked with `#[class(open = "paren")]`, `#[class(close = "paren")]`, `#[class(terminator)]`,
+/// `#[class(comment)]`, `#[class(string)]` or `#[class(import)]`, and are `Class::Other` otherwise.
+#[proc_macro_derive(TokenClass, attributes(class))]
+pub fn derive_token_class(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    token_class::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,162 @@
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::quote;
+use syn::{
+    parse::ParseStream, punctuated::Punctuated, Data, DeriveInput, Error, Expr, ExprLit, Fields,
+    Ident, Lit, LitStr, Result, Token,
+};
+
+/// A `#[token]` or `#[regex]` on a variant.
+struct Rule {
+    pattern: LitStr,
+    literal: bool,
+    ignore_case: bool,
+    action: TokenStream,
+}
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "generic token types are not supported"));
+    }
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new(Span::call_site(), "Logos can only be derived for enums"));
+    };
+
+    let mut skips = Vec::new();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("logos")) {
+        attr.parse_args_with(|input: ParseStream| parse_logos_attr(input, &mut skips))?;
+    }
+
+    let mut rules = Vec::new();
+    for variant in &data.variants {
+        let ident = &variant.ident;
+        let has_field = match &variant.fields {
+            Fields::Unit => false,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => true,
+            _ => return Err(Error::new_spanned(variant, "only unit variants and variants with a single field are supported")),
+        };
+
+        for attr in &variant.attrs {
+            let literal = if attr.path().is_ident("token") {
+                true
+            } else if attr.path().is_ident("regex") {
+                false
+            } else {
+                continue
+            };
+
+            let args = attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)?;
+            let mut args = args.into_iter();
+            let pattern = match args.next() {
+                Some(Expr::Lit(ExprLit { lit: Lit::Str(s), .. })) => s,
+                _ => return Err(Error::new_spanned(attr, "expected a string literal pattern")),
+            };
+
+            let mut callback = None;
+            let mut ignore_case = false;
+            for arg in args {
+                match arg {
+                    // Options like `priority = 2`
+                    Expr::Assign(_) => {}
+                    Expr::Call(ref call) if matches!(&*call.func, Expr::Path(p) if p.path.is_ident("ignore")) => {
+                        match call.args.iter().collect::<Vec<_>>()[..] {
+                            [Expr::Path(p)] if p.path.is_ident("case") || p.path.is_ident("ascii_case") => ignore_case = true,
+                            _ => return Err(Error::new_spanned(call, "expected `ignore(case)` or `ignore(ascii_case)`")),
+                        }
+                    }
+                    arg if callback.is_none() => callback = Some(arg),
+                    arg => return Err(Error::new_spanned(arg, "unexpected argument")),
+                }
+            }
+
+            let action = match callback {
+                Some(Expr::Path(p)) if p.path.segments.last().is_some_and(|s| s.ident == "skip") => quote!(continue),
+                Some(callback) => {
+                    let construct = if has_field { quote!(#name::#ident(field)) } else { quote!({ let () = field; #name::#ident }) };
+                    quote!({
+                        #[allow(unused_imports)]
+                        use lexr::compat::{FromOption as _, FromResult as _, FromBool as _, FromValue as _};
+                        let outcome = lexr::compat::Outcome::of(slice, loc, #callback);
+                        match (&outcome).field() {
+                            Some(field) => #construct,
+                            None => reject!(),
+                        }
+                    })
+                }
+                None if has_field => return Err(Error::new_spanned(attr, "a variant with a field needs a callback")),
+                None => quote!(#name::#ident),
+            };
+
+            rules.push(Rule { pattern, literal, ignore_case, action });
+        }
+    }
+
+    // Like logos, the longest match wins, and a literal wins over a regex matching as much, as literals come first
+    let (literals, regexes): (Vec<_>, Vec<_>) = rules.into_iter().partition(|r| r.literal);
+    let rules = literals.iter().chain(&regexes).map(|r| {
+        let action = &r.action;
+        match (r.literal, r.ignore_case) {
+            (true, false) => {
+                let pattern = &r.pattern;
+                quote!(lit #pattern => |slice, _, loc| #action,)
+            }
+            (true, true) => {
+                let pattern = LitStr::new(&format!("(?i:{})", escape(&r.pattern.value())), r.pattern.span());
+                quote!(#pattern => |slice, _, loc| #action,)
+            }
+            (false, ignore_case) => {
+                let flags = if ignore_case { "?i:" } else { "?:" };
+                let pattern = LitStr::new(&format!("({}{})", flags, r.pattern.value()), r.pattern.span());
+                quote!(#pattern => |slice, _, loc| #action,)
+            }
+        }
+    });
+    let skip_rules = skips.iter().map(|s| {
+        let pattern = LitStr::new(&format!("(?:{})", s.value()), s.span());
+        quote!(#pattern => |_| continue,)
+    });
+
+    Ok(quote! {
+        impl #name {
+            /// Creates a lexer for the source, generated from the logos token definitions.
+            pub fn lexer<'src>(source: &'src str) -> lexr::Lexer<Self, impl lexr::RuleLexer<'src, Item = (Self, lexr::SrcLoc)>> {
+                lexr::lex_rule!{lex -> #name {
+                    config { longest_match: true },
+                    #(#skip_rules)*
+                    #(#rules)*
+                }}
+                lex(source)
+            }
+        }
+    })
+}
+
+/// Escapes the characters of a literal that have a meaning in a regex.
+fn escape(literal: &str) -> String {
+    let mut regex = String::new();
+    for c in literal.chars() {
+        if r"\.+*?()|[]{}^$#&-~".contains(c) {
+            regex.push('\\');
+        }
+        regex.push(c);
+    }
+    regex
+}
+
+/// Parses the contents of a `#[logos(...)]`, collecting the skip patterns. Other options are ignored.
+fn parse_logos_attr(input: ParseStream, skips: &mut Vec<LitStr>) -> Result<()> {
+    while !input.is_empty() {
+        let key: Ident = input.parse()?;
+        if key == "skip" {
+            skips.push(input.parse()?);
+        } else {
+            while !input.is_empty() && !input.peek(Token![,]) {
+                input.parse::<TokenTree>()?;
+            }
+        }
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse::ParseStream, Data, DeriveInput, Error, Ident, LitStr, Result, Token};
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new(Span::call_site(), "TokenClass can only be derived for enums"));
+    };
+
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let ident = &variant.ident;
+        let mut class = None;
+        for attr in variant.attrs.iter().filter(|a| a.path().is_ident("class")) {
+            if class.is_some() {
+                return Err(Error::new_spanned(attr, "a variant can only have one class"));
+            }
+            class = Some(attr.parse_args_with(|input: ParseStream| {
+                let key: Ident = input.parse()?;
+                match key.to_string().as_str() {
+                    "open" | "close" => {
+                        input.parse::<Token![=]>()?;
+                        let pair: LitStr = input.parse()?;
+                        Ok(if key == "open" {
+                            quote!(lexr::token_class::Class::Open(#pair))
+                        } else {
+                            quote!(lexr::token_class::Class::Close(#pair))
+                        })
+                    },
+                    "terminator" => Ok(quote!(lexr::token_class::Class::Terminator)),
+                    "comment" => Ok(quote!(lexr::token_class::Class::Comment)),
+                    "string" => Ok(quote!(lexr::token_class::Class::String)),
+                    "import" => Ok(quote!(lexr::token_class::Class::Import)),
+                    _ => Err(Error::new_spanned(key, "expected `open`, `close`, `terminator`, `comment`, `string` or `import`")),
+                }
+            })?);
+        }
+        if let Some(class) = class {
+            arms.push(quote!(Self::#ident { .. } => #class,));
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics lexr::token_class::TokenClass for #name #ty_generics #where_clause {
+            #[allow(unreachable_patterns)]
+            fn class(&self) -> lexr::token_class::Class {
+                match self {
+                    #(#arms)*
+                    _ => lexr::token_class::Class::Other,
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,50 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{parse::ParseStream, Data, DeriveInput, Error, Ident, LitInt, Result, Token};
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new(Span::call_site(), "TokenShape can only be derived for enums"));
+    };
+
+    let mut max_size = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("token_shape")) {
+        attr.parse_args_with(|input: ParseStream| {
+            let key: Ident = input.parse()?;
+            if key != "max_size" {
+                return Err(Error::new_spanned(key, "expected `max_size`"));
+            }
+            input.parse::<Token![=]>()?;
+            max_size = Some(input.parse::<LitInt>()?);
+            Ok(())
+        })?;
+    }
+
+    let variants = data.variants.iter().map(|variant| {
+        let ident = variant.ident.to_string();
+        let types: Vec<_> = variant.fields.iter().map(|field| &field.ty).collect();
+        let type_names = types.iter().map(|ty| ty.to_token_stream().to_string().replace(' ', ""));
+        quote!(lexr::shape::VariantShape {
+            name: #ident,
+            size: ::core::mem::size_of::<(#(#types,)*)>(),
+            fields: ::std::vec![#(#type_names),*],
+        })
+    });
+
+    // Checked when the crate is compiled, for non-generic types
+    let check = max_size.map(|max| {
+        let message = format!("{} is larger than {} bytes; see lexr::shape::analyze for the variants that make it so", name, max.base10_digits());
+        quote!(const _: () = ::core::assert!(::core::mem::size_of::<#name>() <= #max, #message);)
+    });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics lexr::shape::TokenShape for #name #ty_generics #where_clause {
+            fn variants() -> ::std::vec::Vec<lexr::shape::VariantShape> {
+                ::std::vec![#(#variants),*]
+            }
+        }
+        #check
+    })
+}
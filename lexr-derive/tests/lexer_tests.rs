@@ -0,0 +1,41 @@
+use lexr::token_class::{Class, TokenClass};
+
+#[derive(lexr::Lexer, TokenClass, Debug, PartialEq)]
+#[lex(skip = r"[ \t\n]+", skip = "#[^\n]*", longest_match)]
+enum Token {
+    #[rule(lit = "if")]
+    If,
+    #[rule(lit = "(")]
+    #[class(open = "paren")]
+    LParen,
+    #[rule(lit = ")")]
+    #[class(close = "paren")]
+    RParen,
+    /// A name, like `x`
+    #[rule("[a-z]+", parse_with = str::to_string)]
+    Ident(String),
+    #[rule("[0-9]+")]
+    Num(u8),
+    #[rule("'[a-z]*'", parse_with = |s: &str| s.strip_prefix('\'')?.strip_suffix('\'').map(str::to_string))]
+    #[class(string)]
+    Str(String),
+    #[rule("[A-Z]", parse_with = |s: &str| s != "X")]
+    Upper,
+}
+use Token::*;
+
+#[test]
+fn derived_lexer_lexes_the_rules() {
+    let tokens = Token::lexer("if iffy # c\n(12 'ab')").into_token_vec();
+    assert_eq!(tokens, vec![If, Ident("iffy".to_string()), LParen, Num(12), Str("ab".to_string()), RParen]);
+    assert_eq!(tokens[4].class(), Class::String);
+    assert_eq!(Token::lexer("").spec().rules[5].doc.as_deref(), Some("A name, like `x`"));
+}
+
+#[test]
+fn derived_lexer_rejects_failed_parses() {
+    let mut lexer = Token::lexer("A 300");
+    assert_eq!(lexer.try_next().unwrap().unwrap().0, Upper);
+    assert!(lexer.try_next().unwrap().is_err());
+    assert!(Token::lexer("X").try_next().unwrap().is_err());
+}
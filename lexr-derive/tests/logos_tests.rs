@@ -0,0 +1,46 @@
+use lexr::compat::{Lex, Logos};
+
+#[derive(Logos, Debug, PartialEq)]
+#[logos(skip r"[ \t\n]+")]
+enum Token {
+    #[token("fn")]
+    Fn,
+    #[token("=")]
+    Assign,
+    #[token("==")]
+    Eq,
+    #[regex("[a-z]+")]
+    Ident,
+    #[regex("[0-9]+", |lex| lex.slice().parse().ok())]
+    Num(u8),
+    #[regex("'[a-z]*'", quoted)]
+    Str(String),
+    #[allow(dead_code)]
+    #[regex("//[^\n]*", logos::skip)]
+    Comment,
+}
+use Token::*;
+
+fn quoted(lex: &mut Lex) -> String {
+    let s = lex.slice();
+    s[1..s.len() - 1].to_string()
+}
+
+#[test]
+fn logos_definitions_lex() {
+    let tokens = Token::lexer("fn a == 'b' // c\n12").into_token_vec();
+    assert_eq!(tokens, vec![Fn, Ident, Eq, Str("b".to_string()), Num(12)]);
+}
+
+#[test]
+fn logos_literals_take_part_in_longest_match() {
+    let tokens = Token::lexer("fnord fn = ==").into_token_vec();
+    assert_eq!(tokens, vec![Ident, Fn, Assign, Eq]);
+}
+
+#[test]
+fn logos_rejected_callback_is_an_error() {
+    let mut lexer = Token::lexer("a 300");
+    assert_eq!(lexer.try_next().unwrap().unwrap().0, Ident);
+    assert!(lexer.try_next().unwrap().is_err());
+}
@@ -0,0 +1,50 @@
+//! Compares matching the most common patterns with the byte scanners of the `scan` module, and with the regex engine.
+//!
+//! Run with `cargo bench --bench scan`.
+
+use std::{hint::black_box, time::Instant};
+
+use lexr::{classes, regex_cache, scan::Matcher};
+
+const ROUNDS: usize = 200;
+
+/// Splits the input into runs of whitespace, identifiers and numbers, skipping other characters, and counts them.
+fn split(matchers: &[Matcher], input: &str) -> usize {
+    let (mut at, mut tokens) = (0, 0);
+    while at < input.len() {
+        let rest = &input[at..];
+        match matchers.iter().find_map(|matcher| matcher.find(rest)) {
+            Some(length) if length > 0 => {
+                at += length;
+                tokens += 1;
+            }
+            _ => at += rest.chars().next().unwrap().len_utf8(),
+        }
+    }
+    tokens
+}
+
+fn measure(name: &str, matchers: &[Matcher], input: &str) {
+    let start = Instant::now();
+    let mut tokens = 0;
+    for _ in 0..ROUNDS {
+        tokens += split(matchers, black_box(input));
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<24} {:>8.2} ns per byte ({tokens} tokens)", elapsed.as_nanos() as f64 / (ROUNDS * input.len()) as f64);
+}
+
+fn main() {
+    let patterns = [r"[ \n\r\t]+", classes::IDENT, "[0-9]+"];
+    let scanners: Vec<_> = patterns.iter().map(|p| Matcher::new(p).unwrap()).collect();
+    assert!(scanners.iter().all(Matcher::is_scanner));
+    let regexes: Vec<_> = patterns.iter().map(|p| Matcher::Regex(regex_cache::get(&format!("^(?:{})", p)).unwrap())).collect();
+
+    let code = "fn parse_expression(tokens: &mut TokenStream, precedence: u32) -> Result<Expr, Error> {\n    let lhs = parse_atom(tokens)?;\n    x = 12345 + y1;\n}\n".repeat(2000);
+    let long = format!("{}{}", " ".repeat(64), "a_very_long_identifier_name_that_goes_on_and_on_for_a_while ").repeat(2000);
+
+    measure("code, scanner", &scanners, &code);
+    measure("code, regex", &regexes, &code);
+    measure("long runs, scanner", &scanners, &long);
+    measure("long runs, regex", &regexes, &long);
+}
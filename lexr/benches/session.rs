@@ -0,0 +1,54 @@
+//! Compares lexing many small snippets with a new lexer for each, and with a `LexSession`.
+//!
+//! Run with `cargo bench --bench session`.
+
+use std::{hint::black_box, time::Instant};
+
+use lexr::{lex_rule, LexSession};
+
+#[derive(Debug)]
+#[allow(dead_code)]
+enum Token {
+    Ident,
+    Number(u64),
+    Punct(char),
+}
+
+lex_rule!{lex -> Token {
+    ws => |_| continue,
+    ident => |_| Token::Ident,
+    "[0-9]+" => |s| Token::Number(s.parse().unwrap()),
+    "[(){};,.=+*-]" => |s| Token::Punct(s.chars().next().unwrap()),
+}}
+
+const SNIPPETS: [&str; 4] = [
+    "let x = 1;",
+    "f(a, b)",
+    "x.y + 12 * z",
+    "{ return acc; }",
+];
+const ROUNDS: usize = 200_000;
+
+fn measure(name: &str, mut lex_snippet: impl FnMut(&str) -> usize) {
+    let start = Instant::now();
+    let mut tokens = 0;
+    for round in 0..ROUNDS {
+        tokens += lex_snippet(black_box(SNIPPETS[round % SNIPPETS.len()]));
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<12} {:>8.1} ns per snippet ({tokens} tokens)", elapsed.as_nanos() as f64 / ROUNDS as f64);
+}
+
+fn main() {
+    // Both compile the regexes before measuring
+    lex("").warm_up().unwrap();
+    let mut session = LexSession::new(|buf| lex(buf).boxed());
+    let mut buffer = Vec::new();
+
+    measure("new lexer", |snippet| lex(snippet).into_vec().len());
+    measure("lex_into", |snippet| {
+        lex(snippet).lex_into(&mut buffer);
+        buffer.len()
+    });
+    measure("session", |snippet| session.lex(snippet).unwrap().len());
+}
@@ -0,0 +1,159 @@
+//! Lexing and parsing many files on all cores, like a compiler driver does for the files of a project.
+//!
+//! [`process`] reads, lexes and parses the files on a pool of threads that take files from a shared queue,
+//! and collects the result of each file along with statistics for the whole batch:
+//!
+//! ```
+//! use lexr::{lex_rule, batch};
+//!
+//! lex_rule!{lex -> &'static str {
+//!     ws => |_| continue,
+//!     "[a-z]+" => |_| "word",
+//! }}
+//!
+//! let dir = std::env::temp_dir().join("lexr_batch_doc");
+//! std::fs::create_dir_all(&dir).unwrap();
+//! std::fs::write(dir.join("a.txt"), "one two").unwrap();
+//! std::fs::write(dir.join("b.txt"), "three 4").unwrap();
+//!
+//! let report = batch::process(
+//!     [dir.join("a.txt"), dir.join("b.txt")],
+//!     |source| lex(source).into_result_vec().into_iter().collect(),
+//!     |_source, tokens| tokens.len(),
+//! );
+//! assert_eq!(*report.files[0].result.as_ref().unwrap(), 2);
+//! assert_eq!(report.files[1].result.as_ref().unwrap_err().to_string(), "Unexpected character '4' at 1:7");
+//! assert_eq!((report.stats.files, report.stats.failed, report.stats.tokens), (2, 1, 2));
+//! ```
+
+use std::{fmt::Display, io, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, Mutex}, thread, time::{Duration, Instant}};
+
+use crate::{LexError, SrcLoc};
+
+#[derive(Debug)]
+/// An error that stopped a file from being parsed.
+pub enum BatchError {
+    /// The file could not be read, or was not valid UTF-8.
+    Io(io::Error),
+    /// The file could not be lexed.
+    Lex(LexError),
+}
+
+#[derive(Debug)]
+/// The outcome of a single file of a batch.
+pub struct FileResult<R> {
+    /// The path of the file, as given.
+    pub path: PathBuf,
+    /// The result of the parser, or the error that stopped the file before parsing.
+    pub result: Result<R, BatchError>,
+    /// The time it took to read, lex and parse the file.
+    pub duration: Duration,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Statistics for a whole batch.
+pub struct BatchStats {
+    /// The number of files.
+    pub files: usize,
+    /// The number of files that could not be read or lexed.
+    pub failed: usize,
+    /// The number of bytes in the files that were read.
+    pub bytes: usize,
+    /// The number of tokens in the files that were lexed.
+    pub tokens: usize,
+    /// The time it took to process the whole batch.
+    pub duration: Duration,
+}
+
+#[derive(Debug)]
+/// The results of a batch, along with statistics.
+pub struct BatchReport<R> {
+    /// The results of the files, in the order the paths were given.
+    pub files: Vec<FileResult<R>>,
+    /// The statistics of the batch.
+    pub stats: BatchStats,
+}
+
+/// Reads, lexes and parses the files on as many threads as there are cores.
+///
+/// `lexer_fn` lexes the source of a file, or returns the first error, and `parser_fn` gets the source along with its tokens.
+/// Diagnostics of the parser are part of its result, as the batch does not look at it.
+pub fn process<P, T, R>(
+    paths: impl IntoIterator<Item = P>,
+    lexer_fn: impl Fn(&str) -> Result<Vec<(T, SrcLoc)>, LexError> + Sync,
+    parser_fn: impl Fn(&str, Vec<(T, SrcLoc)>) -> R + Sync,
+) -> BatchReport<R> where P: AsRef<Path>, R: Send {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    process_with_threads(threads, paths, lexer_fn, parser_fn)
+}
+
+/// Like [`process`], but on the given number of threads.
+pub fn process_with_threads<P, T, R>(
+    threads: usize,
+    paths: impl IntoIterator<Item = P>,
+    lexer_fn: impl Fn(&str) -> Result<Vec<(T, SrcLoc)>, LexError> + Sync,
+    parser_fn: impl Fn(&str, Vec<(T, SrcLoc)>) -> R + Sync,
+) -> BatchReport<R> where P: AsRef<Path>, R: Send {
+    let start = Instant::now();
+    let paths: Vec<PathBuf> = paths.into_iter().map(|path| path.as_ref().to_path_buf()).collect();
+
+    // The threads take the next file from the queue, until it is empty
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, paths.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else { break };
+                let result = process_file(path, &lexer_fn, &parser_fn);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    let mut stats = BatchStats { files: paths.len(), ..Default::default() };
+    let files = results.into_inner().unwrap().into_iter().map(|result| {
+        let (file, bytes, tokens) = result.expect("every file is processed");
+        stats.failed += file.result.is_err() as usize;
+        stats.bytes += bytes;
+        stats.tokens += tokens;
+        file
+    }).collect();
+    stats.duration = start.elapsed();
+    BatchReport { files, stats }
+}
+
+/// Processes a single file, returning its result along with its number of bytes and tokens.
+fn process_file<T, R>(
+    path: &Path,
+    lexer_fn: &impl Fn(&str) -> Result<Vec<(T, SrcLoc)>, LexError>,
+    parser_fn: &impl Fn(&str, Vec<(T, SrcLoc)>) -> R,
+) -> (FileResult<R>, usize, usize) {
+    let start = Instant::now();
+    let (mut bytes, mut tokens) = (0, 0);
+    let result = std::fs::read_to_string(path).map_err(BatchError::Io).and_then(|source| {
+        bytes = source.len();
+        let lexed = lexer_fn(&source).map_err(BatchError::Lex)?;
+        tokens = lexed.len();
+        Ok(parser_fn(&source, lexed))
+    });
+    (FileResult { path: path.to_path_buf(), result, duration: start.elapsed() }, bytes, tokens)
+}
+
+impl Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchError::Io(error) => write!(f, "{}", error),
+            BatchError::Lex(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BatchError::Io(error) => Some(error),
+            BatchError::Lex(error) => Some(error),
+        }
+    }
+}
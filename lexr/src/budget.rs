@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Limits on the work of lexing a single input, to bound the latency of services lexing untrusted input.
+///
+/// Each limit is off unless set. The budget is given to the buffer with [`LexBuf::with_budget`](crate::LexBuf::with_budget),
+/// and is checked before each match, also in sub rules. Once a limit is exceeded, [`Lexer::try_next`](crate::Lexer::try_next)
+/// returns [`LexError::BudgetExceeded`](crate::LexError::BudgetExceeded):
+///
+/// ```
+/// use lexr::{lex_rule, Budget, LexBuf, LexError};
+///
+/// lex_rule!{lex -> char {
+///     ws => |_| continue,
+///     "." => |s| s.chars().next().unwrap(),
+/// }}
+///
+/// let mut lexer = lex(LexBuf::new("a b c d").with_budget(Budget::new().max_matches(5)));
+/// for expected in ['a', 'b', 'c'] {
+///     assert_eq!(lexer.try_next().unwrap().unwrap().0, expected);
+/// }
+/// // The whitespace after `c` is the sixth match
+/// match lexer.try_next() {
+///     Some(Err(LexError::BudgetExceeded(loc))) => assert_eq!(loc.to_string(), "1:6"),
+///     other => panic!("{:?}", other),
+/// }
+/// ```
+pub struct Budget {
+    max_matches: Option<usize>,
+    max_rejects: Option<usize>,
+    timeout: Option<Duration>,
+}
+
+impl Budget {
+    /// Creates a budget without limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the number of matches, counting those whose actions continue or reject, and the end of the input.
+    pub fn max_matches(mut self, max: usize) -> Self {
+        self.max_matches = Some(max);
+        self
+    }
+
+    /// Limits the number of times actions reject their match, which makes the lexer try the following rules on the same input.
+    pub fn max_rejects(mut self, max: usize) -> Self {
+        self.max_rejects = Some(max);
+        self
+    }
+
+    /// Limits the time spent lexing, from when the budget is given to the buffer.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// The work spent of a budget, kept by the buffer.
+pub(crate) struct BudgetState {
+    budget: Budget,
+    matches: usize,
+    rejects: usize,
+    deadline: Option<Instant>,
+}
+
+impl BudgetState {
+    pub(crate) fn new(budget: Budget) -> Self {
+        let deadline = budget.timeout.map(|timeout| Instant::now() + timeout);
+        Self { budget, matches: 0, rejects: 0, deadline }
+    }
+
+    /// Spends a match, or a reject and a match, returning whether the budget is exceeded.
+    pub(crate) fn spend(&mut self, rejected: bool) -> bool {
+        self.matches += 1;
+        self.rejects += rejected as usize;
+        if self.deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            return true
+        }
+        self.budget.max_matches.is_some_and(|max| self.matches > max) || self.budget.max_rejects.is_some_and(|max| self.rejects > max)
+    }
+}
@@ -0,0 +1,27 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+#[derive(Clone, Debug, Default)]
+/// A flag for cancelling lexing from another thread, like when a newer edit arrives in an editor.
+/// 
+/// The token is given to the buffer with [`LexBuf::with_cancel`](crate::LexBuf::with_cancel), and is checked before each match.
+/// Once cancelled, [`Lexer::try_next`](crate::Lexer::try_next) returns [`LexError::Cancelled`](crate::LexError::Cancelled), and iterating the lexer stops.
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels lexing with this token, and any clones of it.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed)
+    }
+
+    /// Whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,34 @@
+//! Regexes for common character classes, to use as patterns or parts of patterns.
+//!
+//! ```
+//! use lexr::{lex_rule, classes::{DIGIT, UNICODE_IDENT}};
+//!
+//! lex_rule!{lex -> String {
+//!     DIGIT => |s| s.to_string(),
+//!     "@" UNICODE_IDENT => |s| s.to_string(),
+//!     unicode_ident => |s| s.to_string(),
+//! }}
+//!
+//! assert_eq!(lex("٣café@x1").into_token_vec(), vec!["٣", "café", "@x1"]);
+//! ```
+//!
+//! As each string of a pattern is its own group, a quantifier must be in the same string as what it repeats.
+//! The identifier classes follow [Unicode Standard Annex #31](https://www.unicode.org/reports/tr31/), like identifiers in Rust.
+
+/// A character that can start an identifier, from the Unicode property `XID_Start`.
+pub const XID_START: &str = r"\p{XID_Start}";
+
+/// A character that can continue an identifier, from the Unicode property `XID_Continue`. This includes digits and `_`.
+pub const XID_CONTINUE: &str = r"\p{XID_Continue}";
+
+/// A decimal digit in any script, from the Unicode general category `Nd`.
+pub const DIGIT: &str = r"\p{Nd}";
+
+/// An ASCII decimal digit.
+pub const ASCII_DIGIT: &str = r"[0-9]";
+
+/// An ASCII identifier, starting with a letter or `_`. The `ident` pattern matches the same.
+pub const IDENT: &str = r"[A-Za-z_][A-Za-z0-9_]*";
+
+/// A Unicode identifier, starting with an `XID_Start` character or `_`, like in Rust. The `unicode_ident` pattern matches the same.
+pub const UNICODE_IDENT: &str = r"[\p{XID_Start}_]\p{XID_Continue}*";
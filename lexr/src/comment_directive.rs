@@ -0,0 +1,169 @@
+use crate::SrcLoc;
+
+/// Finds structured directives in trivia, like `// lexr:ignore-next-line` or `#pragma once`, for linters and test annotations.
+///
+/// A directive starts with one of the prefixes, preceded on its line only by comment markers, punctuation and whitespace.
+/// The prefix is followed by the name, up to whitespace or a comma, and then the arguments to the end of the line, or to `*/`.
+/// The arguments are separated by whitespace or commas, and are either a value or `key=value`, where values may be quoted:
+///
+/// ```
+/// use lexr::{lex_rule, DirectiveScanner};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Token { Comment, Ident }
+///
+/// lex_rule!{lex -> Token {
+///     ws => |_| continue,
+///     "//[^\n]*" => |_| Token::Comment,
+///     ident => |_| Token::Ident,
+/// }}
+///
+/// let source = "a\n// lexr:expect error, at=\"line 3\"\nb";
+/// let scanner = DirectiveScanner::new("lexr:");
+/// let trivia = lex(source).filter(|(token, _)| *token == Token::Comment).map(|(_, loc)| loc);
+/// let directives = scanner.scan(source, trivia);
+///
+/// assert_eq!(directives[0].name, "expect");
+/// assert_eq!(directives[0].loc.to_string(), "2:4-33");
+/// assert_eq!(directives[0].arg("at"), Some("line 3"));
+/// assert_eq!(directives[0].args[0].value, "error");
+/// ```
+#[derive(Clone, Debug)]
+pub struct DirectiveScanner {
+    prefixes: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A directive found by a [`DirectiveScanner`].
+pub struct CommentDirective<'s> {
+    /// The prefix the directive was found by.
+    pub prefix: &'s str,
+    /// The name after the prefix.
+    pub name: &'s str,
+    /// The arguments after the name.
+    pub args: Vec<DirectiveArg<'s>>,
+    /// The location from the prefix to the end of the last argument.
+    pub loc: SrcLoc,
+    /// The location of the name.
+    pub name_loc: SrcLoc,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// An argument of a [`CommentDirective`].
+pub struct DirectiveArg<'s> {
+    /// The key of a `key=value` argument.
+    pub key: Option<&'s str>,
+    /// The value, without quotes.
+    pub value: &'s str,
+    /// The location of the whole argument.
+    pub loc: SrcLoc,
+}
+
+impl DirectiveScanner {
+    /// Creates a scanner finding the directives with the prefix.
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self { prefixes: vec![prefix.into()] }
+    }
+
+    /// Finds directives with the prefix as well.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    /// Finds the directives in the trivia at the locations, in order. Each line of the trivia holds at most one directive.
+    pub fn scan<'s>(&self, source: &'s str, trivia: impl IntoIterator<Item = SrcLoc>) -> Vec<CommentDirective<'s>> {
+        let mut directives = Vec::new();
+        for loc in trivia {
+            let (mut line_no, mut col) = loc.start();
+            let mut idx = loc.get_abs_loc().0;
+            for line in loc.text(source).split('\n') {
+                let line_at = Line { text: line, line: line_no, col, idx };
+                if let Some(directive) = self.scan_line(&line_at) {
+                    directives.push(directive);
+                }
+                line_no += 1;
+                col = 1;
+                idx += line.len() + 1;
+            }
+        }
+        directives
+    }
+
+    fn scan_line<'s>(&self, line: &Line<'s>) -> Option<CommentDirective<'s>> {
+        let text = line.text;
+        let (start, prefix) = self.prefixes.iter().find_map(|prefix| {
+            let start = text.find(prefix.as_str())?;
+            text[..start].chars().all(|c| !c.is_alphanumeric()).then(|| (start, &text[start..start + prefix.len()]))
+        })?;
+        let end = text[start..].find("*/").map_or(text.len(), |end| start + end);
+
+        let mut i = start + prefix.len();
+        let name_end = scan_while(text, i, end, |c| !c.is_whitespace() && c != ',');
+        if name_end == i {
+            return None
+        }
+        let name = &text[i..name_end];
+        let name_loc = line.loc(i, name_end);
+        i = name_end;
+
+        let mut args = Vec::new();
+        loop {
+            i = scan_while(text, i, end, |c| c.is_whitespace() || c == ',');
+            if i == end {
+                break
+            }
+            let arg_start = i;
+            let mut key = None;
+            let word_end = scan_while(text, i, end, |c| !c.is_whitespace() && c != ',' && c != '=' && c != '"');
+            if word_end < end && text[word_end..].starts_with('=') {
+                key = Some(&text[i..word_end]);
+                i = word_end + 1;
+            }
+            let value = if i < end && text[i..].starts_with('"') {
+                let close = text[i + 1..end].find('"').map_or(end, |close| i + 1 + close);
+                let value = &text[i + 1..close];
+                i = (close + 1).min(end);
+                value
+            } else {
+                let value_end = scan_while(text, i, end, |c| !c.is_whitespace() && c != ',');
+                let value = &text[i..value_end];
+                i = value_end;
+                value
+            };
+            args.push(DirectiveArg { key, value, loc: line.loc(arg_start, i) });
+        }
+
+        let loc = line.loc(start, args.last().map_or(name_end, |arg| arg.loc.get_abs_loc().1 - line.idx));
+        Some(CommentDirective { prefix, name, args, loc, name_loc })
+    }
+}
+
+impl CommentDirective<'_> {
+    /// The value of the first argument with the key.
+    pub fn arg(&self, key: &str) -> Option<&str> {
+        self.args.iter().find(|arg| arg.key == Some(key)).map(|arg| arg.value)
+    }
+}
+
+/// A line of trivia, with the position and byte index of its first character.
+struct Line<'s> {
+    text: &'s str,
+    line: usize,
+    col: usize,
+    idx: usize,
+}
+
+impl Line<'_> {
+    /// The location of the non-empty byte range of the line.
+    fn loc(&self, start: usize, end: usize) -> SrcLoc {
+        let col = |i: usize| self.col + self.text[..i].chars().count();
+        let last = self.text[..end].char_indices().next_back().map_or(start, |(i, _)| i);
+        SrcLoc::new((self.line, col(start)), (self.line, col(last)), (self.idx + start, self.idx + end))
+    }
+}
+
+/// The index of the first character from `i` not satisfying the condition, at most `end`.
+fn scan_while(text: &str, i: usize, end: usize, condition: impl Fn(char) -> bool) -> usize {
+    text[i..end].char_indices().find(|(_, c)| !condition(*c)).map_or(end, |(j, _)| i + j)
+}
@@ -0,0 +1,149 @@
+//! Compatibility with token definitions written for other lexer libraries.
+//!
+//! The [`Logos`] derive accepts the `#[token(...)]`, `#[regex(...)]` and `#[logos(skip ...)]`
+//! attributes of [logos](https://docs.rs/logos), and generates a lexr lexer from them.
+//! This makes it possible to move a project to lexr one step at a time, while keeping the token definitions.
+//! In most cases only the import has to change:
+//! ```
+//! use lexr::compat::Logos;
+//!
+//! #[derive(Logos, Debug, PartialEq)]
+//! #[logos(skip r"[ \t\n]+")]
+//! enum Token {
+//!     #[token("fn")]
+//!     Fn,
+//!     #[token("(")]
+//!     LParen,
+//!     #[token(")")]
+//!     RParen,
+//!     #[regex("[a-z]+")]
+//!     Ident,
+//!     #[regex("[0-9]+", |lex| lex.slice().parse().ok())]
+//!     Num(u32),
+//! }
+//! use Token::*;
+//!
+//! let tokens = Token::lexer("fn fnord(12)").into_token_vec();
+//! assert_eq!(tokens, vec![Fn, Ident, LParen, Num(12), RParen]);
+//! ```
+//!
+//! `Token::lexer(source)` returns a regular [`Lexer`](struct@crate::Lexer), producing tokens and their locations.
+//!
+//! Callbacks are given a [`Lex`] in place of the logos lexer, which provides `slice()` and `span()`.
+//! They can return the value of the field, an `Option` or a `Result` of it, or a `bool` for variants without a field.
+//! Returning `None`, `Err` or `false` rejects the match, so the following rules can try instead.
+//! The `logos::skip` callback skips the match.
+//!
+//! Like logos, the generated lexer chooses the [longest match](crate#config), and a `#[token]` over a regex matching as much of the input:
+//! ```
+//! use lexr::compat::Logos;
+//!
+//! #[derive(Logos, Debug, PartialEq)]
+//! enum Token {
+//!     #[regex("[a-z]+")]
+//!     Ident,
+//!     #[regex("[a-z]+[0-9]+")]
+//!     Mixed,
+//!     #[token("select", ignore(case))]
+//!     Select,
+//! }
+//! use Token::*;
+//!
+//! assert_eq!(Token::lexer("ab12").into_token_vec(), vec![Mixed]);
+//! assert_eq!(Token::lexer("SeLeCt").into_token_vec(), vec![Select]);
+//! assert_eq!(Token::lexer("selects").into_token_vec(), vec![Ident]);
+//! ```
+//!
+//! `ignore(case)` and `ignore(ascii_case)` both make the pattern case-insensitive, with Unicode case folding.
+//! Other options, like `priority` and `#[logos(error = ...)]`, are ignored.
+
+use std::{cell::Cell, ops::Range};
+
+use crate::SrcLoc;
+
+pub use lexr_derive::Logos;
+
+/// The state given to the callbacks of a [`Logos`] token definition, in place of the logos lexer.
+pub struct Lex<'a> {
+    slice: &'a str,
+    loc: SrcLoc,
+}
+
+impl<'a> Lex<'a> {
+    #[doc(hidden)]
+    pub fn new(slice: &'a str, loc: SrcLoc) -> Self {
+        Self { slice, loc }
+    }
+
+    /// The matched string.
+    pub fn slice(&self) -> &'a str {
+        self.slice
+    }
+
+    /// The range of the match, in byte offsets of the input.
+    pub fn span(&self) -> Range<usize> {
+        let (start, end) = self.loc.get_abs_loc();
+        start..end
+    }
+
+    /// The location of the match.
+    pub fn loc(&self) -> SrcLoc {
+        self.loc
+    }
+}
+
+#[doc(hidden)]
+/// The result of a callback. The value of the field is taken out with `(&outcome).field()`,
+/// where the method is picked by the type of the result.
+pub struct Outcome<T>(Cell<Option<T>>);
+
+impl<T> Outcome<T> {
+    pub fn of<'a>(slice: &'a str, loc: SrcLoc, callback: impl FnOnce(&mut Lex<'a>) -> T) -> Self {
+        Self(Cell::new(Some(callback(&mut Lex::new(slice, loc)))))
+    }
+}
+
+#[doc(hidden)]
+pub trait FromOption<F> {
+    fn field(&self) -> Option<F>;
+}
+
+impl<F> FromOption<F> for Outcome<Option<F>> {
+    fn field(&self) -> Option<F> {
+        self.0.take().flatten()
+    }
+}
+
+#[doc(hidden)]
+pub trait FromResult<F> {
+    fn field(&self) -> Option<F>;
+}
+
+impl<F, E> FromResult<F> for Outcome<Result<F, E>> {
+    fn field(&self) -> Option<F> {
+        self.0.take().and_then(Result::ok)
+    }
+}
+
+#[doc(hidden)]
+pub trait FromBool {
+    fn field(&self) -> Option<()>;
+}
+
+impl FromBool for Outcome<bool> {
+    fn field(&self) -> Option<()> {
+        self.0.take().filter(|b| *b).map(|_| ())
+    }
+}
+
+#[doc(hidden)]
+/// Only found through auto-ref, so the other impls take precedence.
+pub trait FromValue<F> {
+    fn field(&self) -> Option<F>;
+}
+
+impl<F> FromValue<F> for &Outcome<F> {
+    fn field(&self) -> Option<F> {
+        self.0.take()
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt::Display;
+
+use crate::SrcLoc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The kinds of directive tokens of conditional regions, given by the classifier of [`Conditionals`].
+pub enum Directive {
+    /// Starts a region, like `#if` or `#ifdef`.
+    If,
+    /// Starts an alternative region, like `#elif`.
+    ElseIf,
+    /// Starts the final alternative region, like `#else`.
+    Else,
+    /// Ends the region, like `#endif`.
+    EndIf,
+}
+
+/// Evaluates preprocessor-style conditional regions, like `#if`/`#else`/`#endif`, at the token level.
+///
+/// The classifier marks the directive tokens, and the predicate evaluates the condition of the `If` and `ElseIf` directives.
+/// Applying it gives the tokens of the taken branches without the directives, and the disabled regions, for editors to gray out.
+/// The locations still point into the original text:
+///
+/// ```
+/// use lexr::{lex_rule, Conditionals, Directive};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Token { IfDef(String), Else, EndIf, Ident(String) }
+/// use Token::*;
+///
+/// lex_rule!{lex -> Token {
+///     ws => |_| continue,
+///     r"#ifdef\s+[a-z]+" => |s| IfDef(s[6..].trim().to_string()),
+///     "#else" => |_| Else,
+///     "#endif" => |_| EndIf,
+///     ident => |s| Ident(s.to_string()),
+/// }}
+///
+/// let conditionals = Conditionals::new(
+///     |token| match token {
+///         IfDef(_) => Some(Directive::If),
+///         Else => Some(Directive::Else),
+///         EndIf => Some(Directive::EndIf),
+///         _ => None,
+///     },
+///     |token| matches!(token, IfDef(name) if name == "debug"),
+/// );
+///
+/// let source = "a #ifdef release b c #else d #endif e";
+/// let spliced = conditionals.apply(lex(source)).unwrap();
+/// let idents: Vec<_> = spliced.tokens.iter().map(|(token, _)| token).collect();
+/// assert_eq!(idents, vec![&Ident("a".into()), &Ident("d".into()), &Ident("e".into())]);
+/// assert_eq!(spliced.disabled[0].text(source), "b c");
+/// ```
+pub struct Conditionals<T> {
+    classify: Classifier<T>,
+    predicate: Box<dyn Fn(&T) -> bool>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The result of applying [`Conditionals`].
+pub struct Spliced<T> {
+    /// The tokens of the taken branches, without the directives.
+    pub tokens: Vec<(T, SrcLoc)>,
+    /// The disabled regions, each from the start of its first token to the end of its last, in order.
+    ///
+    /// Nested directives in a disabled region are part of it.
+    pub disabled: Vec<SrcLoc>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// An error in the nesting of directives.
+pub enum ConditionalError {
+    /// An `ElseIf`, `Else` or `EndIf` directive without an open region, or an `ElseIf` or `Else` after the `Else` of its region.
+    Unmatched(SrcLoc),
+    /// An `If` directive without an `EndIf`.
+    Unterminated(SrcLoc),
+}
+
+type Classifier<T> = Box<dyn Fn(&T) -> Option<Directive>>;
+
+/// An open region.
+struct Region {
+    /// The location of the `If` directive.
+    loc: SrcLoc,
+    /// Whether the enclosing region is active.
+    enclosing: bool,
+    /// Whether a branch has been taken.
+    taken: bool,
+    /// Whether the current branch is taken.
+    active: bool,
+    /// Whether the `Else` has been seen.
+    is_else: bool,
+}
+
+impl<T> Conditionals<T> {
+    /// Creates conditionals with directives marked by `classify`, and `If` and `ElseIf` conditions evaluated by `predicate`.
+    pub fn new(classify: impl Fn(&T) -> Option<Directive> + 'static, predicate: impl Fn(&T) -> bool + 'static) -> Self {
+        Self { classify: Box::new(classify), predicate: Box::new(predicate) }
+    }
+
+    /// Splices the tokens, keeping those of the taken branches.
+    pub fn apply(&self, tokens: impl IntoIterator<Item = (T, SrcLoc)>) -> Result<Spliced<T>, ConditionalError> {
+        let mut spliced = Spliced { tokens: Vec::new(), disabled: Vec::new() };
+        let mut regions: Vec<Region> = Vec::new();
+        // The disabled region being extended, which ends at the next kept token or active directive
+        let mut region: Option<SrcLoc> = None;
+
+        for (token, loc) in tokens {
+            let active = regions.last().is_none_or(|region| region.active);
+            let disabled = match (self.classify)(&token) {
+                None => {
+                    if active {
+                        spliced.tokens.push((token, loc));
+                    }
+                    !active
+                },
+                Some(Directive::If) => {
+                    let taken = active && (self.predicate)(&token);
+                    regions.push(Region { loc, enclosing: active, taken, active: taken, is_else: false });
+                    !active
+                },
+                Some(directive) => {
+                    let Some(current) = regions.last_mut().filter(|region| !region.is_else || directive == Directive::EndIf) else {
+                        return Err(ConditionalError::Unmatched(loc))
+                    };
+                    let enclosing = current.enclosing;
+                    match directive {
+                        Directive::ElseIf => {
+                            current.active = current.enclosing && !current.taken && (self.predicate)(&token);
+                            current.taken |= current.active;
+                        },
+                        Directive::Else => {
+                            current.active = current.enclosing && !current.taken;
+                            current.taken = true;
+                            current.is_else = true;
+                        },
+                        _ => {
+                            regions.pop();
+                        },
+                    }
+                    !enclosing
+                },
+            };
+
+            if disabled {
+                region = Some(match region {
+                    Some(region) => SrcLoc::new(region.start(), loc.end(), (region.get_abs_loc().0, loc.get_abs_loc().1)),
+                    None => loc,
+                });
+            } else if let Some(region) = region.take() {
+                spliced.disabled.push(region);
+            }
+        }
+
+        if let Some(region) = regions.last() {
+            return Err(ConditionalError::Unterminated(region.loc))
+        }
+        spliced.disabled.extend(region);
+        Ok(spliced)
+    }
+}
+
+impl ConditionalError {
+    /// The location of the directive.
+    pub fn loc(&self) -> SrcLoc {
+        match self {
+            ConditionalError::Unmatched(loc) | ConditionalError::Unterminated(loc) => *loc,
+        }
+    }
+}
+
+impl Display for ConditionalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionalError::Unmatched(loc) => write!(f, "Unmatched conditional directive at {}", loc),
+            ConditionalError::Unterminated(loc) => write!(f, "Unterminated conditional directive at {}", loc),
+        }
+    }
+}
+
+impl std::error::Error for ConditionalError {}
@@ -0,0 +1,161 @@
+use std::fmt::Debug;
+
+use crate::{Base, LexError, SrcLoc};
+
+#[macro_export]
+/// Defines a unit test checking the tokens a lexer produces for inputs, as a regression suite for its rules.
+///
+/// The test is named after the lexer, like `lex_conformance` for `lex`. Each case is one of:
+/// - `"input" => [tokens]`, checking the tokens.
+/// - `"input" => spans [(token, "loc")]`, also checking the location of each token, written like `"1:3-5"`.
+/// - `"input" => error "loc"`, checking that lexing fails at the location, after any tokens.
+/// - `"input" => error 'c' "loc"`, also checking the unexpected character.
+/// - `"input" => [tokens] then error "loc"`, checking the tokens before lexing fails at the location.
+/// - `"input" => not [tokens]`, checking that the input is lexed without error, but not into the tokens,
+///   to lock in which of two overlapping rules wins.
+///
+/// Locations count from 1, unless the cases are preceded by a [`Base`](crate::Base), like `lexer = lex; base = Zero;`.
+///
+/// # Examples
+///
+///     use lexr::{lex_rule, lex_tests};
+///
+///     #[derive(Debug, PartialEq)]
+///     enum Token { Num(u32), Plus }
+///     use Token::*;
+///
+///     lex_rule!{lex -> Token {
+///         ws => |_| continue,
+///         "[0-9]+" => |s| Num(s.parse().unwrap()),
+///         lit "+" => Plus,
+///     }}
+///
+///     lex_tests!{lexer = lex;
+///         "1+2" => [Num(1), Plus, Num(2)];
+///         "10 + 2" => spans [(Num(10), "1:1-2"), (Plus, "1:4"), (Num(2), "1:6")];
+///         "1 - 2" => error "1:3";
+///         "1 - 2" => error '-' "1:3";
+///         "1 + -" => [Num(1), Plus] then error "1:5";
+///         "12" => not [Num(1), Num(2)];
+///     }
+///
+///     lex_rule!{lex_zero -> Token {
+///         ws => |_| continue,
+///         "[0-9]+" => |s| Num(s.parse().unwrap()),
+///     }}
+///
+///     lex_tests!{lexer = lex_zero; base = Zero;
+///         "10 2" => spans [(Num(10), "0:0-1"), (Num(2), "0:3")];
+///         "1 -" => error "0:2";
+///     }
+///
+macro_rules! lex_tests {
+    (lexer = $lexer:ident; base = $base:ident; $($cases:tt)*) => {
+        $crate::concat_idents!(test_name = $lexer, _conformance {
+            #[test]
+            fn test_name() {
+                const BASE: $crate::Base = $crate::Base::$base;
+                $crate::lex_tests!(@cases $lexer $($cases)*);
+            }
+        });
+    };
+
+    (lexer = $lexer:ident; $($cases:tt)*) => {
+        $crate::lex_tests!(lexer = $lexer; base = One; $($cases)*);
+    };
+
+    (@cases $lexer:ident) => {};
+
+    (@cases $lexer:ident $input:literal => spans [$(($token:expr, $loc:literal)),* $(,)?]; $($rest:tt)*) => {
+        $crate::conformance::check_tokens(BASE, $input, $lexer($input).into_result_vec(), ::std::vec![$(($token, ::core::option::Option::Some($loc))),*]);
+        $crate::lex_tests!(@cases $lexer $($rest)*);
+    };
+
+    (@cases $lexer:ident $input:literal => error $c:literal $loc:literal; $($rest:tt)*) => {
+        $crate::conformance::check_error(BASE, $input, $lexer($input).into_result_vec(), ::core::option::Option::None, ::core::option::Option::Some($c), $loc);
+        $crate::lex_tests!(@cases $lexer $($rest)*);
+    };
+
+    (@cases $lexer:ident $input:literal => error $loc:literal; $($rest:tt)*) => {
+        $crate::conformance::check_error(BASE, $input, $lexer($input).into_result_vec(), ::core::option::Option::None, ::core::option::Option::None, $loc);
+        $crate::lex_tests!(@cases $lexer $($rest)*);
+    };
+
+    (@cases $lexer:ident $input:literal => [$($token:expr),* $(,)?] then error $loc:literal; $($rest:tt)*) => {
+        $crate::conformance::check_error(BASE, $input, $lexer($input).into_result_vec(), ::core::option::Option::Some(::std::vec![$($token),*]), ::core::option::Option::None, $loc);
+        $crate::lex_tests!(@cases $lexer $($rest)*);
+    };
+
+    (@cases $lexer:ident $input:literal => not [$($token:expr),* $(,)?]; $($rest:tt)*) => {
+        $crate::conformance::check_not_tokens($input, $lexer($input).into_result_vec(), ::std::vec![$($token),*]);
+        $crate::lex_tests!(@cases $lexer $($rest)*);
+    };
+
+    (@cases $lexer:ident $input:literal => [$($token:expr),* $(,)?]; $($rest:tt)*) => {
+        $crate::conformance::check_tokens(BASE, $input, $lexer($input).into_result_vec(), ::std::vec![$(($token, ::core::option::Option::None)),*]);
+        $crate::lex_tests!(@cases $lexer $($rest)*);
+    };
+}
+
+#[doc(hidden)]
+/// Panics unless the input was lexed into the expected tokens, at the locations where given.
+pub fn check_tokens<T: PartialEq + Debug>(base: Base, input: &str, lexed: Vec<Result<(T, SrcLoc), LexError>>, expected: Vec<(T, Option<&str>)>) {
+    let mut tokens = Vec::new();
+    for result in lexed {
+        match result {
+            Ok(pair) => tokens.push(pair),
+            Err(error) => panic!("Lexing {:?} failed after {:?}: {}", input, tokens, error),
+        }
+    }
+
+    let matches = tokens.len() == expected.len() && tokens.iter().zip(&expected).all(|((token, loc), (expected, expected_loc))| {
+        token == expected && expected_loc.is_none_or(|expected_loc| loc.display().base(base).to_string() == expected_loc)
+    });
+    if !matches {
+        let found: Vec<_> = tokens.iter().map(|(token, loc)| format!("{:?} at {}", token, loc.display().base(base))).collect();
+        let expected: Vec<_> = expected.iter().map(|(token, loc)| match loc {
+            Some(loc) => format!("{:?} at {}", token, loc),
+            None => format!("{:?}", token),
+        }).collect();
+        panic!("Lexing {:?}:\n  expected [{}]\n     found [{}]", input, expected.join(", "), found.join(", "));
+    }
+}
+
+#[doc(hidden)]
+/// Panics unless lexing the input failed at the location, after the tokens and at the unexpected character where given.
+pub fn check_error<T: PartialEq + Debug>(base: Base, input: &str, mut lexed: Vec<Result<(T, SrcLoc), LexError>>, before: Option<Vec<T>>, c: Option<char>, loc: &str) {
+    let error = match lexed.pop() {
+        Some(Err(error)) => error,
+        _ => panic!("Lexing {:?} succeeded, but was expected to fail at {}", input, loc),
+    };
+    let found = error.loc().display().base(base).to_string();
+    if found != loc {
+        panic!("Lexing {:?} failed at {} instead of {}: {}", input, found, loc, error)
+    }
+    if let Some(c) = c {
+        if !matches!(error, LexError::UnexpectedChar(found, _) if found == c) {
+            panic!("Lexing {:?} failed at {}, but not on the unexpected character {:?}: {}", input, loc, c, error)
+        }
+    }
+    if let Some(before) = before {
+        let tokens: Vec<_> = lexed.into_iter().filter_map(Result::ok).map(|(token, _)| token).collect();
+        if tokens != before {
+            panic!("Lexing {:?} failed at {}, but after {:?} instead of {:?}", input, loc, tokens, before)
+        }
+    }
+}
+
+#[doc(hidden)]
+/// Panics unless the input was lexed without error, into other tokens than those given.
+pub fn check_not_tokens<T: PartialEq + Debug>(input: &str, lexed: Vec<Result<(T, SrcLoc), LexError>>, unexpected: Vec<T>) {
+    let mut tokens = Vec::new();
+    for result in lexed {
+        match result {
+            Ok((token, _)) => tokens.push(token),
+            Err(error) => panic!("Lexing {:?} failed after {:?}: {}", input, tokens, error),
+        }
+    }
+    if tokens == unexpected {
+        panic!("Lexing {:?} produced {:?}, which it was expected not to", input, tokens)
+    }
+}
@@ -0,0 +1,176 @@
+//! Statistics of the tokens of a corpus, to guide performance work like ordering rules, interning and designing the token type.
+//!
+//! [`CorpusStats`] counts the tokens of each kind, their lengths, and the trivia between them, like whitespace and comments:
+//!
+//! ```
+//! use lexr::{lex_rule, CorpusStats};
+//!
+//! #[derive(Debug)]
+//! enum Token { Num(u32), Ident(String) }
+//!
+//! lex_rule!{lex -> Token {
+//!     ws => |_| continue,
+//!     "[0-9]+" => |s| Token::Num(s.parse().unwrap()),
+//!     ident => |s| Token::Ident(s.to_string()),
+//! }}
+//!
+//! let mut stats = CorpusStats::new();
+//! for source in ["x 1 22", "yy 3"] {
+//!     stats.add(source, &lex(source).into_vec(), |token| match token {
+//!         Token::Num(_) => "Num",
+//!         Token::Ident(_) => "Ident",
+//!     });
+//! }
+//! assert_eq!(stats.tokens(), 5);
+//! assert_eq!(stats.kind(&"Num").unwrap().count, 3);
+//! assert_eq!(stats.average_len(), 7.0 / 5.0);
+//! assert_eq!(stats.trivia_ratio(), 3.0 / 10.0);
+//! ```
+//!
+//! Its `Display` writes a table of the kinds by frequency, followed by a histogram of the token lengths.
+
+use std::{collections::HashMap, fmt::Display, hash::Hash};
+
+use crate::SrcLoc;
+
+#[derive(Clone, Debug)]
+/// Statistics of the tokens of the sources added, by kind.
+pub struct CorpusStats<K> {
+    /// The number of sources added.
+    pub sources: usize,
+    /// The number of bytes in the sources.
+    pub bytes: usize,
+    /// The number of bytes in the tokens. The rest are trivia, which no token covers.
+    pub token_bytes: usize,
+    kinds: Vec<KindStats<K>>,
+    index: HashMap<K, usize>,
+    /// The number of tokens by the bucket of their length, see [`length_bucket`].
+    lengths: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Statistics of the tokens of a single kind.
+pub struct KindStats<K> {
+    /// The kind.
+    pub kind: K,
+    /// The number of tokens.
+    pub count: usize,
+    /// The number of bytes in the tokens.
+    pub bytes: usize,
+    /// The length in bytes of the longest token.
+    pub max_len: usize,
+}
+
+/// The bucket of a token length in the histogram: 0 for empty tokens, 1 for length 1, 2 for length 2, 3 for 3 to 4, 4 for 5 to 8, and so on.
+pub fn length_bucket(len: usize) -> usize {
+    match len {
+        0 => 0,
+        len => (usize::BITS - (len - 1).leading_zeros()) as usize + 1,
+    }
+}
+
+impl<K: Eq + Hash + Clone> CorpusStats<K> {
+    /// Creates empty statistics.
+    pub fn new() -> Self {
+        Self { sources: 0, bytes: 0, token_bytes: 0, kinds: Vec::new(), index: HashMap::new(), lengths: Vec::new() }
+    }
+
+    /// Adds the tokens of the source, grouped by the kind given by `kind`.
+    pub fn add<T>(&mut self, source: &str, tokens: &[(T, SrcLoc)], mut kind: impl FnMut(&T) -> K) {
+        self.sources += 1;
+        self.bytes += source.len();
+        for (token, loc) in tokens {
+            let (start, end) = loc.get_abs_loc();
+            let len = end - start;
+            self.token_bytes += len;
+
+            let kind = kind(token);
+            let i = *self.index.entry(kind.clone()).or_insert_with(|| {
+                self.kinds.push(KindStats { kind, count: 0, bytes: 0, max_len: 0 });
+                self.kinds.len() - 1
+            });
+            let stats = &mut self.kinds[i];
+            stats.count += 1;
+            stats.bytes += len;
+            stats.max_len = stats.max_len.max(len);
+
+            let bucket = length_bucket(len);
+            if self.lengths.len() <= bucket {
+                self.lengths.resize(bucket + 1, 0);
+            }
+            self.lengths[bucket] += 1;
+        }
+    }
+
+    /// The statistics of the kind, if any tokens had it.
+    pub fn kind(&self, kind: &K) -> Option<&KindStats<K>> {
+        self.index.get(kind).map(|&i| &self.kinds[i])
+    }
+
+    /// The statistics of all kinds, from the most to the least frequent.
+    pub fn kinds(&self) -> Vec<&KindStats<K>> {
+        let mut kinds: Vec<_> = self.kinds.iter().collect();
+        kinds.sort_by_key(|stats| std::cmp::Reverse(stats.count));
+        kinds
+    }
+
+    /// The number of tokens.
+    pub fn tokens(&self) -> usize {
+        self.kinds.iter().map(|stats| stats.count).sum()
+    }
+
+    /// The average length of the tokens in bytes, or 0 without tokens.
+    pub fn average_len(&self) -> f64 {
+        match self.tokens() {
+            0 => 0.0,
+            tokens => self.token_bytes as f64 / tokens as f64,
+        }
+    }
+
+    /// The part of the bytes that are trivia, not covered by any token, or 0 without any bytes.
+    pub fn trivia_ratio(&self) -> f64 {
+        match self.bytes {
+            0 => 0.0,
+            bytes => (bytes - self.token_bytes) as f64 / bytes as f64,
+        }
+    }
+
+    /// The number of tokens by the [bucket](length_bucket) of their length.
+    pub fn length_histogram(&self) -> &[usize] {
+        &self.lengths
+    }
+}
+
+impl<K: Eq + Hash + Clone> Default for CorpusStats<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash + Clone + Display> Display for CorpusStats<K> {
+    /// Writes a table of the kinds by frequency, and a histogram of the token lengths.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tokens = self.tokens();
+        writeln!(f, "{} tokens in {} sources of {} bytes, {:.1}% trivia", tokens, self.sources, self.bytes, self.trivia_ratio() * 100.0)?;
+
+        let names: Vec<_> = self.kinds().iter().map(|stats| stats.kind.to_string()).collect();
+        let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0).max("kind".len());
+        write!(f, "  {:width$}  {:>8}  {:>6}  {:>7}  {:>7}", "kind", "count", "share", "avg len", "max len")?;
+        for (stats, name) in self.kinds().into_iter().zip(&names) {
+            let share = stats.count as f64 / tokens as f64 * 100.0;
+            let average = stats.bytes as f64 / stats.count as f64;
+            write!(f, "\n  {:width$}  {:>8}  {:>5.1}%  {:>7.1}  {:>7}", name, stats.count, share, average, stats.max_len)?;
+        }
+
+        write!(f, "\n\n  {:>9}  {:>8}", "length", "count")?;
+        for (bucket, &count) in self.lengths.iter().enumerate().filter(|(_, &count)| count > 0) {
+            let range = match bucket {
+                0..=2 => bucket.to_string(),
+                bucket => format!("{}-{}", (1 << (bucket - 2)) + 1, 1 << (bucket - 1)),
+            };
+            let bar = "#".repeat((count * 40).div_ceil(tokens));
+            write!(f, "\n  {:>9}  {:>8}  {}", range, count, bar)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+//! An interactive debugger for the rules of a lexer, enabled by the `debugger` feature.
+//!
+//! [`repl`] reads lines from the standard input, and shows which rule matches each token, with its location and text.
+//! Rules can be turned off and on between lines, to see which rule takes over, without rebuilding the lexer:
+//!
+//! ```text
+//! > let x1 = 2
+//!   1:1-3  rule 2 "[a-z]+"  "let"  Ident
+//!   1:4    rule 1 ws  " "  continue
+//!   ...
+//! > :toggle 2
+//! rule 2 "[a-z]+" is off
+//! ```
+//!
+//! The rules are matched by their regexes in the [`LexerSpec`], as the actions can not be run without the lexer.
+//! Actions that reject the match, or switch to sub rules, are not followed, and rules with a custom `matcher` never match.
+//! Lines starting with `:` are commands: `:rules` lists the rules, `:toggle N` turns rule `N` off or on, and `:quit` ends the session.
+//!
+//! ```
+//! use lexr::{lex_rule, debugger::Debugger};
+//!
+//! lex_rule!{lex -> () {
+//!     ws => |_| continue,
+//!     "[a-z]+" => |_| (),
+//!     "[a-z]" => |_| (),
+//! }}
+//!
+//! let mut debugger = Debugger::new(lex("").spec());
+//! assert_eq!(debugger.trace("ab c").iter().map(|step| step.rule).collect::<Vec<_>>(), [Some(1), Some(0), Some(1)]);
+//! assert!(!debugger.toggle(1));
+//! assert_eq!(debugger.trace("ab").iter().map(|step| step.rule).collect::<Vec<_>>(), [Some(2), Some(2)]);
+//! ```
+
+use std::io::{self, BufRead, Write};
+
+use crate::{regex_cache, scan::Matcher, LexerSpec, RuleSpec, SrcLoc};
+
+/// The rules of a lexer, some of which may be turned off, matched against lines of input.
+pub struct Debugger<'a> {
+    spec: &'a LexerSpec,
+    enabled: Vec<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The match of a rule in a line, or where no rule matches.
+pub struct Step {
+    /// The index of the rule in the spec, or `None` if no rule matches, which ends the line.
+    pub rule: Option<usize>,
+    /// The location of the match in the line.
+    pub loc: SrcLoc,
+}
+
+impl<'a> Debugger<'a> {
+    /// Creates a debugger for the rules, all of which are on.
+    pub fn new(spec: &'a LexerSpec) -> Self {
+        Self { spec, enabled: vec![true; spec.rules.len()] }
+    }
+
+    /// Turns the rule at the index off or on, returning whether it is on now.
+    ///
+    /// Panics if there is no rule at the index.
+    pub fn toggle(&mut self, index: usize) -> bool {
+        self.enabled[index] = !self.enabled[index];
+        self.enabled[index]
+    }
+
+    /// Whether the rule at the index is on.
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.enabled[index]
+    }
+
+    /// Matches the rules against the input, from the start, like the lexer would choose them.
+    ///
+    /// Ends where no rule matches, or where a rule matches the empty input, as the lexer would not make progress there.
+    pub fn trace(&self, input: &str) -> Vec<Step> {
+        let mut steps = Vec::new();
+        let mut idx = 0;
+        while idx < input.len() {
+            let mut found = self.spec.rules.iter().enumerate()
+                .filter(|&(i, _)| self.enabled[i])
+                .filter_map(|(i, rule)| Some((i, self.find(rule, input, idx)?)));
+            // With the longest match, the earliest of the longest matches is used
+            let chosen = match self.spec.longest_match {
+                true => found.fold(None, |best: Option<(usize, usize)>, (i, length)| match best {
+                    Some((_, best_length)) if best_length >= length => best,
+                    _ => Some((i, length)),
+                }),
+                false => found.next(),
+            };
+            let Some((rule, length)) = chosen else {
+                let end = idx + input[idx..].chars().next().map_or(0, char::len_utf8);
+                steps.push(Step { rule: None, loc: SrcLoc::from_offsets(input, (idx, end)) });
+                break
+            };
+            steps.push(Step { rule: Some(rule), loc: SrcLoc::from_offsets(input, (idx, idx + length)) });
+            if length == 0 {
+                break
+            }
+            idx += length;
+        }
+        steps
+    }
+
+    /// The length of the match of the rule at the byte index in the input, if it matches there, with its anchors and lookahead.
+    fn find(&self, rule: &RuleSpec, input: &str, idx: usize) -> Option<usize> {
+        if rule.matcher.is_some() || (rule.bol && idx > 0 && !input[..idx].ends_with('\n')) {
+            return None
+        }
+        let options = self.spec.regex_options;
+        let rest = &input[idx..];
+        let length = match &rule.trailing {
+            Some(trailing) => regex_cache::get_with(&format!("^({})(?:{})", rule.regex, trailing), options).ok()?
+                .captures(rest)?.get(1)?.end(),
+            None => Matcher::with_options(&rule.regex, options).ok()?.find(rest)?,
+        };
+        let after = &rest[length..];
+        let eol = after.is_empty() || after.starts_with('\n') || after.starts_with("\r\n");
+        let lookahead = |regex: &String| Matcher::with_options(regex, options).is_ok_and(|matcher| matcher.is_match(after));
+        (!rule.eol || eol)
+            .then_some(length)
+            .filter(|_| rule.followed_by.iter().all(lookahead) && !rule.not_followed_by.iter().any(lookahead))
+    }
+
+    /// Writes the steps of tracing the input, one per line, with the location, rule, text and action.
+    pub fn describe(&self, input: &str) -> String {
+        let steps = self.trace(input);
+        let locs: Vec<_> = steps.iter().map(|step| step.loc.to_string()).collect();
+        let loc_width = locs.iter().map(String::len).max().unwrap_or(0);
+        let mut lines = Vec::new();
+        for (step, loc) in steps.iter().zip(&locs) {
+            let text = format!("{:?}", step.loc.text(input));
+            lines.push(match step.rule {
+                Some(i) => {
+                    let rule = &self.spec.rules[i];
+                    format!("  {:loc_width$}  rule {} {}  {}  {}", loc, i + 1, rule.name, text, rule.action)
+                },
+                None => format!("  {:loc_width$}  no rule matches {}", loc, text),
+            });
+        }
+        lines.join("\n")
+    }
+
+    /// Runs a command, the line after the `:`, returning the reply, or `None` to quit.
+    fn command(&mut self, command: &str) -> Option<String> {
+        let mut words = command.split_whitespace();
+        Some(match (words.next(), words.next().map(str::parse::<usize>)) {
+            (Some("quit" | "q"), None) => return None,
+            (Some("rules"), None) => self.spec.rules.iter().enumerate().map(|(i, rule)| {
+                format!("  {} {} {}  {}", if self.enabled[i] { " " } else { "-" }, i + 1, rule.name, rule.action)
+            }).collect::<Vec<_>>().join("\n"),
+            (Some("toggle"), Some(Ok(n))) if (1..=self.spec.rules.len()).contains(&n) => {
+                let on = self.toggle(n - 1);
+                format!("rule {} {} is {}", n, self.spec.rules[n - 1].name, if on { "on" } else { "off" })
+            },
+            _ => format!("Unknown command `{}`, expected `:rules`, `:toggle N` or `:quit`", command),
+        })
+    }
+
+    /// Runs the debugger on the lines of the input until it ends or `:quit`, writing the replies to the output.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let reply = match line.strip_prefix(':') {
+                Some(command) => match self.command(command) {
+                    Some(reply) => reply,
+                    None => break,
+                },
+                None => self.describe(&line),
+            };
+            write!(output, "{}\n> ", reply)?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+}
+
+/// Runs the debugger for the rules on the standard input and output, like `lexr::debugger::repl(lex("").spec())`.
+pub fn repl(spec: &LexerSpec) -> io::Result<()> {
+    Debugger::new(spec).run(io::stdin().lock(), io::stdout().lock())
+}
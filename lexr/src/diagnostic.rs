@@ -0,0 +1,372 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{security::SecurityWarning, EditBuilder, EditConflict, LexError, SrcLoc, TextEdit};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// How serious a diagnostic is, from the least to the most serious.
+pub enum Severity {
+    /// Additional information, like where an earlier definition is.
+    Note,
+    /// Something that is likely a mistake, but does not stop the input from being processed.
+    Warning,
+    /// Something that stops the input from being processed.
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A message about a location of the source, reported into a [`DiagnosticSink`].
+pub struct Diagnostic {
+    /// How serious the diagnostic is.
+    pub severity: Severity,
+    /// A short code identifying the kind of diagnostic, like `L0001`, so it can be filtered or looked up.
+    pub code: &'static str,
+    /// The message, without the location.
+    pub message: String,
+    /// The location the diagnostic is about.
+    pub loc: SrcLoc,
+    /// Suggested fixes, like quick-fixes in an editor.
+    pub suggestions: Vec<Suggestion>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A fix suggested by a [`Diagnostic`], made of edits to the source.
+pub struct Suggestion {
+    /// A description of the fix, like ``did you mean `==`?``.
+    pub message: String,
+    /// The edits making the fix, at byte ranges of the source.
+    pub edits: Vec<TextEdit>,
+}
+
+/// Receives the diagnostics of the lexer, the parser and the application, so reporting is controlled in one place.
+///
+/// It is implemented for `Vec<Diagnostic>`, collecting them, for [`PrintSink`], printing them immediately,
+/// and for closures, which can forward them anywhere, like to a language server client:
+///
+/// ```
+/// use lexr::{lex_rule, Diagnostic, DiagnosticSink, Severity};
+///
+/// lex_rule!{lex -> char {
+///     "[a-z]" => |s| s.chars().next().unwrap(),
+/// }}
+///
+/// let mut errors = 0;
+/// let mut sink = |diagnostic: Diagnostic| if diagnostic.severity == Severity::Error { errors += 1 };
+/// let tokens = lex("ab!c").into_vec_reporting(&mut sink);
+/// assert_eq!(tokens.len(), 2);
+/// assert_eq!(errors, 1);
+/// ```
+pub trait DiagnosticSink {
+    /// Reports the diagnostic.
+    fn report(&mut self, diagnostic: Diagnostic);
+
+    /// Reports an error with the code and message at the location.
+    fn error(&mut self, code: &'static str, message: impl Into<String>, loc: SrcLoc) where Self: Sized {
+        self.report(Diagnostic::new(Severity::Error, code, message, loc))
+    }
+
+    /// Reports a warning with the code and message at the location.
+    fn warning(&mut self, code: &'static str, message: impl Into<String>, loc: SrcLoc) where Self: Sized {
+        self.report(Diagnostic::new(Severity::Warning, code, message, loc))
+    }
+
+    /// Reports a note with the code and message at the location.
+    fn note(&mut self, code: &'static str, message: impl Into<String>, loc: SrcLoc) where Self: Sized {
+        self.report(Diagnostic::new(Severity::Note, code, message, loc))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+/// A sink printing each diagnostic to standard error as soon as it is reported.
+pub struct PrintSink;
+
+#[derive(Clone, Debug)]
+/// A sink that suppresses diagnostics or changes their severity by code, before passing them on to another sink.
+///
+/// ```
+/// use lexr::{lex_rule, security, Diagnostic, FilterSink, Severity, codes};
+///
+/// let mut sink = FilterSink::new(Vec::new())
+///     .suppress(codes::MIXED_SCRIPT)
+///     .remap(codes::CONFUSABLE, Severity::Error);
+///
+/// for warning in security::confusable_identifiers("admin аdmin", [lexr::SrcLoc::new((1, 1), (1, 5), (0, 5)), lexr::SrcLoc::new((1, 7), (1, 11), (6, 12))]) {
+///     lexr::DiagnosticSink::report(&mut sink, warning.into());
+/// }
+/// let diagnostics: Vec<Diagnostic> = sink.into_inner();
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!((diagnostics[0].code, diagnostics[0].severity), (codes::CONFUSABLE, Severity::Error));
+/// ```
+pub struct FilterSink<S> {
+    sink: S,
+    severities: HashMap<&'static str, Option<Severity>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The entry of a built-in diagnostic in the [`CATALOG`].
+pub struct DiagnosticInfo {
+    /// The code of the diagnostic, which does not change between versions.
+    pub code: &'static str,
+    /// The severity the diagnostic is reported with.
+    pub severity: Severity,
+    /// A short description of the diagnostic.
+    pub title: &'static str,
+    /// An explanation of when the diagnostic is reported, and how to fix it.
+    pub explanation: &'static str,
+}
+
+/// The codes of the built-in diagnostics.
+///
+/// Codes starting with `L` are reported by the lexer, and codes starting with `S` by the [`security`](crate::security) checks.
+pub mod codes {
+    /// None of the patterns matched the input.
+    pub const UNEXPECTED_CHAR: &str = "L0001";
+    /// Lexing was cancelled with a [`CancelToken`](crate::CancelToken).
+    pub const CANCELLED: &str = "L0002";
+    /// A rule keeps matching the empty input, so the lexer would loop forever.
+    pub const STUCK: &str = "L0003";
+    /// Lexing used up its [`Budget`](crate::Budget).
+    pub const BUDGET_EXCEEDED: &str = "L0004";
+    /// The input is not valid UTF-8.
+    pub const INVALID_UTF8: &str = "L0005";
+    /// Whitespace at the end of a line, from [`Lints`](crate::Lints).
+    pub const TRAILING_WHITESPACE: &str = "L0101";
+    /// Indentation mixing tabs and spaces, from [`Lints`](crate::Lints).
+    pub const MIXED_INDENTATION: &str = "L0102";
+    /// A line longer than the maximum, from [`Lints`](crate::Lints).
+    pub const LONG_LINE: &str = "L0103";
+    /// A bidirectional control character in the source.
+    pub const BIDI_CONTROL: &str = "S0001";
+    /// An identifier mixing letters of different scripts.
+    pub const MIXED_SCRIPT: &str = "S0002";
+    /// An identifier looking like another identifier.
+    pub const CONFUSABLE: &str = "S0003";
+}
+
+/// All built-in diagnostics, ordered by code, for tools listing or documenting them.
+pub const CATALOG: &[DiagnosticInfo] = &[
+    DiagnosticInfo {
+        code: codes::UNEXPECTED_CHAR,
+        severity: Severity::Error,
+        title: "Unexpected character",
+        explanation: "None of the patterns of the lexer match the input at the character. \
+            Either the input is invalid, or the lexer needs a rule for it, like a wildcard `_` rule reporting the character as an error token.",
+    },
+    DiagnosticInfo {
+        code: codes::CANCELLED,
+        severity: Severity::Error,
+        title: "Lexing was cancelled",
+        explanation: "The `CancelToken` given to the buffer was cancelled, usually because the result is no longer needed, like after a newer edit. \
+            The tokens before the location are complete.",
+    },
+    DiagnosticInfo {
+        code: codes::STUCK,
+        severity: Severity::Error,
+        title: "Lexer is stuck",
+        explanation: "A rule matched the empty input, and its action continued without a token, so the same rule would match again forever. \
+            Make the pattern match at least one character, or return a token. The error includes a repro of the lexer, the rule and the input.",
+    },
+    DiagnosticInfo {
+        code: codes::BUDGET_EXCEEDED,
+        severity: Severity::Error,
+        title: "Lexing exceeded its budget",
+        explanation: "Lexing took more matches, rejects or time than the `Budget` given to the buffer allows, which bounds the work spent on untrusted input. \
+            The tokens before the location are complete. Either the input is too large, or a rule rejects too often.",
+    },
+    DiagnosticInfo {
+        code: codes::INVALID_UTF8,
+        severity: Severity::Error,
+        title: "Invalid UTF-8",
+        explanation: "The input is checked to be UTF-8 as it is lexed, like a file lexed with a `MappedSource`, and is not valid UTF-8 at the location. \
+            The tokens before the location are complete. Convert the file to UTF-8.",
+    },
+    DiagnosticInfo {
+        code: codes::TRAILING_WHITESPACE,
+        severity: Severity::Warning,
+        title: "Trailing whitespace",
+        explanation: "The line ends with spaces or tabs, which are invisible and clutter diffs. Remove them. \
+            Reported by `Lints::trailing_whitespace`, with the severity given there.",
+    },
+    DiagnosticInfo {
+        code: codes::MIXED_INDENTATION,
+        severity: Severity::Warning,
+        title: "Mixed indentation",
+        explanation: "The indentation of the line mixes tabs and spaces, or uses other characters than the first indented line, \
+            so it looks different depending on the tab width. Reported by `Lints::mixed_indentation`, with the severity given there.",
+    },
+    DiagnosticInfo {
+        code: codes::LONG_LINE,
+        severity: Severity::Warning,
+        title: "Line too long",
+        explanation: "The line has more characters than the maximum. Break it into several lines. \
+            Reported by `Lints::max_line_length`, with the severity given there.",
+    },
+    DiagnosticInfo {
+        code: codes::BIDI_CONTROL,
+        severity: Severity::Warning,
+        title: "Bidirectional control character",
+        explanation: "The character can reorder how the surrounding text is displayed, so the code may look different from how it is lexed, \
+            like in the Trojan Source attacks. Remove it, or write it as an escape in strings.",
+    },
+    DiagnosticInfo {
+        code: codes::MIXED_SCRIPT,
+        severity: Severity::Warning,
+        title: "Identifier mixes scripts",
+        explanation: "The identifier has letters of scripts that look alike, like a Cyrillic `а` among Latin letters, \
+            which is rarely intended and can hide that two identifiers differ.",
+    },
+    DiagnosticInfo {
+        code: codes::CONFUSABLE,
+        severity: Severity::Warning,
+        title: "Confusable identifiers",
+        explanation: "The identifier looks like an earlier, different identifier, so code may refer to another definition than it seems to. \
+            Rename one of them.",
+    },
+];
+
+/// The catalog entry of the built-in diagnostic with the code.
+pub fn lookup(code: &str) -> Option<&'static DiagnosticInfo> {
+    CATALOG.iter().find(|info| info.code == code)
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic.
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>, loc: SrcLoc) -> Self {
+        Self { severity, code, message: message.into(), loc, suggestions: Vec::new() }
+    }
+
+    /// Adds a suggestion to replace the source at the location with the text.
+    ///
+    /// ```
+    /// use lexr::{Diagnostic, Severity, SrcLoc};
+    ///
+    /// let source = "if a = b {}";
+    /// let loc = SrcLoc::new((1, 6), (1, 6), (5, 6));
+    /// let diagnostic = Diagnostic::new(Severity::Error, "X0001", "Assignment in condition", loc)
+    ///     .with_suggestion("did you mean `==`?", loc, "==");
+    /// assert_eq!(diagnostic.suggestions[0].apply(source).unwrap(), "if a == b {}");
+    /// ```
+    pub fn with_suggestion(self, message: impl Into<String>, loc: SrcLoc, replacement: impl Into<String>) -> Self {
+        let (start, end) = loc.get_abs_loc();
+        let edit = TextEdit { range: start..end, text: replacement.into() };
+        self.with_edits(message, vec![edit])
+    }
+
+    /// Adds a suggestion made of several edits, which must not overlap.
+    pub fn with_edits(mut self, message: impl Into<String>, edits: Vec<TextEdit>) -> Self {
+        self.suggestions.push(Suggestion { message: message.into(), edits });
+        self
+    }
+}
+
+impl Suggestion {
+    /// Applies the edits to the source, which must be the source the diagnostic is about.
+    ///
+    /// Returns the fixed source, or the first two edits found to overlap.
+    pub fn apply(&self, source: &str) -> Result<String, EditConflict> {
+        let mut builder = EditBuilder::new();
+        for edit in &self.edits {
+            builder.edit(edit.clone());
+        }
+        builder.apply(source)
+    }
+}
+
+impl<S: DiagnosticSink> FilterSink<S> {
+    /// Wraps the sink, passing on all diagnostics unchanged.
+    pub fn new(sink: S) -> Self {
+        Self { sink, severities: HashMap::new() }
+    }
+
+    /// Drops the diagnostics with the code.
+    pub fn suppress(mut self, code: &'static str) -> Self {
+        self.severities.insert(code, None);
+        self
+    }
+
+    /// Passes on the diagnostics with the code with the severity instead.
+    pub fn remap(mut self, code: &'static str, severity: Severity) -> Self {
+        self.severities.insert(code, Some(severity));
+        self
+    }
+
+    /// The wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: DiagnosticSink> DiagnosticSink for FilterSink<S> {
+    fn report(&mut self, mut diagnostic: Diagnostic) {
+        match self.severities.get(diagnostic.code) {
+            Some(None) => {},
+            Some(Some(severity)) => {
+                diagnostic.severity = *severity;
+                self.sink.report(diagnostic)
+            },
+            None => self.sink.report(diagnostic),
+        }
+    }
+}
+
+impl DiagnosticSink for Vec<Diagnostic> {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.push(diagnostic)
+    }
+}
+
+impl DiagnosticSink for PrintSink {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        eprintln!("{}", diagnostic)
+    }
+}
+
+impl<F: FnMut(Diagnostic)> DiagnosticSink for F {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self(diagnostic)
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Self {
+        match error {
+            LexError::UnexpectedChar(c, loc) => Self::new(Severity::Error, codes::UNEXPECTED_CHAR, format!("Unexpected character '{}'", c), loc),
+            LexError::Cancelled(loc) => Self::new(Severity::Error, codes::CANCELLED, "Lexing was cancelled", loc),
+            LexError::BudgetExceeded(loc) => Self::new(Severity::Error, codes::BUDGET_EXCEEDED, "Lexing exceeded its budget", loc),
+            LexError::InvalidUtf8(loc) => Self::new(Severity::Error, codes::INVALID_UTF8, "Invalid UTF-8", loc),
+            LexError::Stuck(repro) => Self::new(Severity::Error, codes::STUCK, format!("Lexer is stuck, as {} keeps matching the empty input: {}", repro.rule.name, repro), repro.loc),
+        }
+    }
+}
+
+impl From<SecurityWarning> for Diagnostic {
+    fn from(warning: SecurityWarning) -> Self {
+        let (code, message) = match &warning {
+            SecurityWarning::BidiControl { c, .. } => (codes::BIDI_CONTROL, format!("Bidirectional control character U+{:04X}", *c as u32)),
+            SecurityWarning::MixedScript { ident, .. } => (codes::MIXED_SCRIPT, format!("Identifier `{}` mixes letters of different scripts", ident)),
+            SecurityWarning::Confusable { ident, other, other_loc, .. } => (codes::CONFUSABLE, format!("Identifier `{}` looks like `{}` at {}", ident, other, other_loc)),
+        };
+        let diagnostic = Self::new(Severity::Warning, code, message, warning.loc());
+        match &warning {
+            SecurityWarning::BidiControl { loc, .. } => diagnostic.with_suggestion("remove the character", *loc, ""),
+            SecurityWarning::MixedScript { .. } => diagnostic,
+            SecurityWarning::Confusable { loc, other, .. } => diagnostic.with_suggestion(format!("did you mean `{}`?", other), *loc, other.clone()),
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    /// Writes the diagnostic like `error[L0001]: Unexpected character '!' at 1:3`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: {} at {}", self.severity, self.code, self.message, self.loc)
+    }
+}
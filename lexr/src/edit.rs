@@ -0,0 +1,131 @@
+use std::{fmt::Display, ops::Range};
+
+use crate::SrcLoc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A replacement of a byte range of the source with new text. Insertions replace an empty range.
+pub struct TextEdit {
+    /// The byte range that is replaced.
+    pub range: Range<usize>,
+    /// The text replacing the range.
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Default)]
+/// Collects edits at the locations of tokens, and applies them to the source all at once.
+///
+/// The locations are those of the original source, so edits do not have to account for each other:
+///
+/// ```
+/// use lexr::{lex_rule, EditBuilder};
+///
+/// lex_rule!{lex -> &'static str {
+///     ws => |_| continue,
+///     "[a-z]+" => |_| "word",
+/// }}
+///
+/// let source = "old new old";
+/// let mut edits = EditBuilder::new();
+/// for (_, loc) in lex(source).filter(|(_, loc)| loc.text(source) == "old") {
+///     edits.replace(loc, "fresh");
+/// }
+/// assert_eq!(edits.apply(source).unwrap(), "fresh new fresh");
+/// ```
+pub struct EditBuilder {
+    edits: Vec<TextEdit>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Two edits changing overlapping parts of the source, so they can not both be applied.
+pub struct EditConflict {
+    /// The edit that was added first.
+    pub first: TextEdit,
+    /// The edit that was added later.
+    pub second: TextEdit,
+}
+
+impl EditBuilder {
+    /// Creates a builder without edits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the source at the location with the text.
+    pub fn replace(&mut self, loc: SrcLoc, text: impl Into<String>) -> &mut Self {
+        let (start, end) = loc.get_abs_loc();
+        self.edit(TextEdit { range: start..end, text: text.into() })
+    }
+
+    /// Removes the source at the location.
+    pub fn delete(&mut self, loc: SrcLoc) -> &mut Self {
+        self.replace(loc, "")
+    }
+
+    /// Inserts the text right before the location.
+    pub fn insert_before(&mut self, loc: SrcLoc, text: impl Into<String>) -> &mut Self {
+        let start = loc.get_abs_loc().0;
+        self.edit(TextEdit { range: start..start, text: text.into() })
+    }
+
+    /// Inserts the text right after the location.
+    pub fn insert_after(&mut self, loc: SrcLoc, text: impl Into<String>) -> &mut Self {
+        let end = loc.get_abs_loc().1;
+        self.edit(TextEdit { range: end..end, text: text.into() })
+    }
+
+    /// Adds an edit of a byte range.
+    pub fn edit(&mut self, edit: TextEdit) -> &mut Self {
+        self.edits.push(edit);
+        self
+    }
+
+    /// The edits added so far, in the order they were added.
+    pub fn edits(&self) -> &[TextEdit] {
+        &self.edits
+    }
+
+    /// The edits ordered by their position in the source, or the first two edits found to overlap.
+    ///
+    /// Insertions at the same position are kept in the order they were added, and come before a replacement starting there.
+    pub fn sorted(&self) -> Result<Vec<TextEdit>, EditConflict> {
+        let mut order: Vec<_> = (0..self.edits.len()).collect();
+        order.sort_by_key(|&i| (self.edits[i].range.start, self.edits[i].range.end));
+
+        for pair in order.windows(2) {
+            let (a, b) = (&self.edits[pair[0]], &self.edits[pair[1]]);
+            // An insertion is only in conflict when strictly inside a replacement
+            if b.range.start < a.range.end {
+                let (first, second) = if pair[0] < pair[1] { (a, b) } else { (b, a) };
+                return Err(EditConflict { first: first.clone(), second: second.clone() })
+            }
+        }
+        Ok(order.into_iter().map(|i| self.edits[i].clone()).collect())
+    }
+
+    /// Applies the edits to the source, which must be the source the locations are from.
+    ///
+    /// Returns the edited source, or the first two edits found to overlap.
+    pub fn apply(&self, source: &str) -> Result<String, EditConflict> {
+        let edits = self.sorted()?;
+        let mut result = String::with_capacity(source.len());
+        let mut at = 0;
+        for edit in edits {
+            result.push_str(&source[at..edit.range.start]);
+            result.push_str(&edit.text);
+            at = edit.range.end;
+        }
+        result.push_str(&source[at..]);
+        Ok(result)
+    }
+}
+
+impl Display for EditConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f, "Edits of bytes {}..{} and {}..{} overlap",
+            self.first.range.start, self.first.range.end, self.second.range.start, self.second.range.end,
+        )
+    }
+}
+
+impl std::error::Error for EditConflict {}
@@ -0,0 +1,13 @@
+//! Adapters for using lexr lexers with other parsing libraries.
+//! 
+//! Adapters that depend on a library are behind a feature with the name of the library.
+
+#[cfg(feature = "chumsky")]
+pub mod chumsky;
+#[cfg(feature = "nom")]
+pub mod nom;
+#[cfg(feature = "tree-sitter")]
+pub mod tree_sitter;
+#[cfg(feature = "proc-macro2")]
+pub mod proc_macro2;
+pub mod lalrpop;
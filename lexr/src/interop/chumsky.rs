@@ -0,0 +1,69 @@
+//! Parsing lexr tokens with [chumsky](https://docs.rs/chumsky).
+//! 
+//! [`input`] turns a [`Lexer`] into a chumsky input, with [`SrcLoc`] as the span type.
+//! 
+//! # Examples
+//! 
+//!     use chumsky::prelude::*;
+//!     use chumsky::input::ValueInput;
+//!     use lexr::{lex_rule, SrcLoc};
+//! 
+//!     #[derive(Clone, Debug, PartialEq)]
+//!     enum Token {
+//!         Num(i32),
+//!         Plus,
+//!     }
+//! 
+//!     lex_rule!{lex -> Token {
+//!         ws => |_| continue,
+//!         r"[0-9]+" => |s| Token::Num(s.parse().unwrap()),
+//!         r"\+" => |_| Token::Plus,
+//!     }}
+//! 
+//!     fn sum<'src, I: ValueInput<'src, Token = Token, Span = SrcLoc>>() -> impl Parser<'src, I, (i32, SrcLoc)> {
+//!         let num = select! { Token::Num(n) => n };
+//!         num.foldl(just(Token::Plus).ignore_then(num).repeated(), |a, b| a + b)
+//!             .map_with(|sum, e| (sum, e.span()))
+//!     }
+//! 
+//!     let result = sum().parse(lexr::interop::chumsky::input(lex("1 + 2 + 3"))).into_output();
+//!     assert_eq!(result, Some((6, SrcLoc::new((1, 1), (1, 9), (0, 9)))));
+
+use std::ops::Range;
+
+use ::chumsky::{input::{Input, Stream, ValueInput}, span::Span};
+
+use crate::{Lexer, RuleLexer, SrcLoc};
+
+/// Spans are created from the positions of tokens, so the offset is both the position and the absolute index.
+impl Span for SrcLoc {
+    type Context = ();
+    type Offset = ((usize, usize), usize);
+
+    fn new(_context: Self::Context, range: Range<Self::Offset>) -> Self {
+        SrcLoc::new(range.start.0, range.end.0, (range.start.1, range.end.1))
+    }
+
+    fn context(&self) -> Self::Context {}
+
+    fn start(&self) -> Self::Offset {
+        (SrcLoc::start(self), self.get_abs_loc().0)
+    }
+
+    fn end(&self) -> Self::Offset {
+        (SrcLoc::end(self), self.get_abs_loc().1)
+    }
+}
+
+/// Turns a lexer into a chumsky input of its tokens, spanned by their [`SrcLoc`]s.
+/// 
+/// The tokens must be `Clone`, as chumsky may need to backtrack over them.
+/// The end of input is spanned by a zero-width location at the end of the source.
+pub fn input<'src, T, Ite>(lexer: Lexer<T, Ite>) -> impl ValueInput<'src, Token = T, Span = SrcLoc>
+where
+    T: Clone + 'src,
+    Ite: Iterator<Item = (T, SrcLoc)> + RuleLexer<'src> + 'src,
+{
+    let eoi = lexer.end_loc();
+    Stream::from_iter(lexer).map(eoi, |(token, loc)| (token, loc))
+}
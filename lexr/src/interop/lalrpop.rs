@@ -0,0 +1,83 @@
+//! Using a lexr lexer as an external lexer for [LALRPOP](https://github.com/lalrpop/lalrpop).
+//! 
+//! LALRPOP expects an iterator of `Result<(Location, Token, Location), Error>` triples, which [`LalrpopLexer`] provides.
+//! The locations are the byte offsets of the tokens, and the error is a [`LexError`], so the grammar should declare:
+//! 
+//! ```ignore
+//! extern {
+//!     type Location = usize;
+//!     type Error = lexr::LexError;
+//! 
+//!     enum Token { ... }
+//! }
+//! ```
+//! 
+//! Lex errors keep the full [`SrcLoc`] with lines and columns.
+//! For parse errors, which only have byte offsets, [`LalrpopLexer::loc_at`] finds the location of the token at an offset.
+//! The adapter is passed to the generated parser by reference, with `parser.parse(&mut adapter)`, so it can still be asked afterwards:
+//! 
+//! ```
+//! use lexr::{lex_rule, interop::lalrpop::{LalrpopLexer, Spanned}};
+//! 
+//! lex_rule!{lex -> char {
+//!     ws => |_| continue,
+//!     "[a-z]" => |s| s.chars().next().unwrap(),
+//! }}
+//! 
+//! // Stands in for a generated parser, which fails at the offset of an unexpected `z`
+//! fn parse(tokens: impl Iterator<Item = Spanned<char>>) -> Result<(), usize> {
+//!     for (start, token, _) in tokens.map(Result::unwrap) {
+//!         if token == 'z' { return Err(start) }
+//!     }
+//!     Ok(())
+//! }
+//! 
+//! let mut adapter = LalrpopLexer::new(lex("a b\n  z"));
+//! let offset = parse(&mut adapter).unwrap_err();
+//! assert_eq!(adapter.loc_at(offset).unwrap().to_string(), "2:3");
+//! ```
+
+use crate::{LexError, Lexer, RuleLexer, SrcLoc};
+
+/// A token with its start and end byte offsets, or an error, as LALRPOP expects from an external lexer.
+pub type Spanned<T> = Result<(usize, T, usize), LexError>;
+
+/// Adapts a [`Lexer`](struct@crate::Lexer) to the external lexer interface of LALRPOP.
+/// 
+/// Unlike iterating the lexer directly, unexpected input is returned as an error instead of panicking.
+/// 
+/// For [`loc_at`](Self::loc_at), the location of every token produced is kept until the adapter is dropped,
+/// which is a [`SrcLoc`] per token. Use a new adapter for each source, rather than one for a long stream.
+pub struct LalrpopLexer<T, Ite: Iterator<Item = (T, SrcLoc)>> {
+    lexer: Lexer<T, Ite>,
+    locs: Vec<SrcLoc>,
+}
+
+impl<T, Ite: Iterator<Item = (T, SrcLoc)>> LalrpopLexer<T, Ite> {
+    /// Creates an adapter over the lexer.
+    pub fn new(lexer: Lexer<T, Ite>) -> Self {
+        Self { lexer, locs: Vec::new() }
+    }
+
+    /// The location of the token produced so far that starts at the byte offset, if any.
+    /// 
+    /// This recovers the lines and columns of the offsets in LALRPOP parse errors.
+    pub fn loc_at(&self, offset: usize) -> Option<SrcLoc> {
+        let idx = self.locs.partition_point(|loc| loc.get_abs_loc().0 < offset);
+        self.locs.get(idx).filter(|loc| loc.get_abs_loc().0 == offset).copied()
+    }
+}
+
+impl<'src, T, Ite: Iterator<Item = (T, SrcLoc)> + RuleLexer<'src>> Iterator for LalrpopLexer<T, Ite> {
+    type Item = Spanned<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (token, loc) = match self.lexer.try_next()? {
+            Ok(pair) => pair,
+            Err(error) => return Some(Err(error)),
+        };
+        self.locs.push(loc);
+        let (start, end) = loc.get_abs_loc();
+        Some(Ok((start, token, end)))
+    }
+}
@@ -0,0 +1,134 @@
+//! Parsing lexr tokens with [nom](https://docs.rs/nom).
+//! 
+//! [`Tokens`] is a nom input over a slice of lexed tokens and their locations.
+//! Its elements are the tokens, so nom combinators like `take` and `take_while` count and inspect tokens, not bytes.
+//! 
+//! # Examples
+//! 
+//!     use lexr::{lex_rule, SrcLoc, interop::nom::{Tokens, token}};
+//!     use nom::{multi::many1, IResult};
+//! 
+//!     #[derive(Debug, PartialEq)]
+//!     enum Token {
+//!         A, B,
+//!     }
+//! 
+//!     lex_rule!{lex -> Token {
+//!         "a" => |_| Token::A,
+//!         "b" => |_| Token::B,
+//!     }}
+//! 
+//!     let tokens = lex("aab").into_vec();
+//!     let result: IResult<_, _> = many1(token(Token::A))(Tokens::new(&tokens));
+//!     let (rest, a_tokens) = result.unwrap();
+//!     assert_eq!(a_tokens.len(), 2);
+//!     assert_eq!(rest.as_slice(), &[(Token::B, SrcLoc::new((1, 3), (1, 3), (2, 3)))]);
+
+use std::{iter::{Enumerate, Map}, ops::{Range, RangeFrom, RangeFull, RangeTo}, slice::Iter};
+
+use ::nom::{
+    error::{ErrorKind, ParseError},
+    Err, IResult, InputIter, InputLength, InputTake, Needed, Slice, UnspecializedInput,
+};
+
+use crate::SrcLoc;
+
+/// A nom input over lexed tokens and their locations.
+#[derive(Debug, PartialEq)]
+pub struct Tokens<'a, T> {
+    tokens: &'a [(T, SrcLoc)],
+}
+
+impl<'a, T: 'a> Tokens<'a, T> {
+    /// Creates an input from token/SrcLoc pairs, as produced by [`Lexer::into_vec`](crate::Lexer::into_vec).
+    pub fn new(tokens: &'a [(T, SrcLoc)]) -> Self {
+        Self { tokens }
+    }
+
+    /// The remaining token/SrcLoc pairs.
+    pub fn as_slice(&self) -> &'a [(T, SrcLoc)] {
+        self.tokens
+    }
+
+    /// The location of the next token, if any.
+    pub fn loc(&self) -> Option<SrcLoc> {
+        self.tokens.first().map(|(_, loc)| *loc)
+    }
+}
+
+impl<'a, T> Clone for Tokens<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Tokens<'a, T> {}
+
+impl<'a, T> InputLength for Tokens<'a, T> {
+    fn input_len(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+impl<'a, T> InputTake for Tokens<'a, T> {
+    fn take(&self, count: usize) -> Self {
+        Tokens::new(&self.tokens[..count])
+    }
+
+    fn take_split(&self, count: usize) -> (Self, Self) {
+        let (prefix, suffix) = self.tokens.split_at(count);
+        (Tokens::new(suffix), Tokens::new(prefix))
+    }
+}
+
+fn token_of<T>(pair: &(T, SrcLoc)) -> &T {
+    &pair.0
+}
+
+impl<'a, T> InputIter for Tokens<'a, T> {
+    type Item = &'a T;
+    type Iter = Enumerate<Self::IterElem>;
+    type IterElem = Map<Iter<'a, (T, SrcLoc)>, fn(&'a (T, SrcLoc)) -> &'a T>;
+
+    fn iter_indices(&self) -> Self::Iter {
+        self.iter_elements().enumerate()
+    }
+
+    fn iter_elements(&self) -> Self::IterElem {
+        self.tokens.iter().map(token_of as fn(&'a (T, SrcLoc)) -> &'a T)
+    }
+
+    fn position<P>(&self, predicate: P) -> Option<usize> where P: Fn(Self::Item) -> bool {
+        self.tokens.iter().position(|(token, _)| predicate(token))
+    }
+
+    fn slice_index(&self, count: usize) -> Result<usize, Needed> {
+        if self.tokens.len() >= count {
+            Ok(count)
+        } else {
+            Err(Needed::new(count - self.tokens.len()))
+        }
+    }
+}
+
+impl<'a, T> UnspecializedInput for Tokens<'a, T> {}
+
+macro_rules! impl_slice {
+    ($($range:ty),*) => {$(
+        impl<'a, T> Slice<$range> for Tokens<'a, T> {
+            fn slice(&self, range: $range) -> Self {
+                Tokens::new(&self.tokens[range])
+            }
+        }
+    )*};
+}
+
+impl_slice!(Range<usize>, RangeTo<usize>, RangeFrom<usize>, RangeFull);
+
+/// Matches a single token equal to `expected`, returning it along with its location.
+pub fn token<'a, T: PartialEq + 'a, E: ParseError<Tokens<'a, T>>>(expected: T) -> impl Fn(Tokens<'a, T>) -> IResult<Tokens<'a, T>, &'a (T, SrcLoc), E> {
+    move |input: Tokens<'a, T>| match input.tokens.first() {
+        Some(pair) if pair.0 == expected => Ok((Tokens::new(&input.tokens[1..]), pair)),
+        _ => Err(Err::Error(E::from_error_kind(input, ErrorKind::Tag))),
+    }
+}
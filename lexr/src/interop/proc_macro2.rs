@@ -0,0 +1,121 @@
+//! Lexing string literals inside procedural macros, with [proc-macro2](https://docs.rs/proc-macro2).
+//!
+//! A language embedded in a macro is often given as a string literal, like `my_dsl!("a + b")`.
+//! [`LiteralSource`] provides the value of the literal for lexing, and maps the location of each token back to a span in the literal,
+//! so errors point at the offending part of the string:
+//!
+//! ```ignore
+//! let source = LiteralSource::new(literal).expect("expected a string literal");
+//! let tokens = source.to_token_stream(lex(source.value()), |token, span| match token {
+//!     Token::Ident(name) => Ident::new(&name, span).into(),
+//!     Token::Plus => Punct::new('+', Spacing::Alone).into(),
+//! });
+//! ```
+//!
+//! Spans within a literal are only available where proc-macro2 supports `Literal::subspan`.
+//! Elsewhere the span of the whole literal is used.
+
+use ::proc_macro2::{Literal, Span, TokenStream, TokenTree};
+
+use crate::SrcLoc;
+
+/// The value of a string literal, and the positions of its characters in the source of the literal.
+pub struct LiteralSource {
+    literal: Literal,
+    value: String,
+    // The byte offset in the literal of each byte of the value, followed by the offset of the closing quote
+    offsets: Vec<usize>,
+}
+
+impl LiteralSource {
+    /// Reads a string literal, raw or not. Returns `None` for other literals.
+    pub fn new(literal: Literal) -> Option<Self> {
+        let (value, offsets) = unescape(&literal.to_string())?;
+        Some(Self { literal, value, offsets })
+    }
+
+    /// The value of the literal, with escapes resolved.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The literal.
+    pub fn literal(&self) -> &Literal {
+        &self.literal
+    }
+
+    /// The span of the location in the value, or of the whole literal if spans within it are not available.
+    pub fn span(&self, loc: SrcLoc) -> Span {
+        let (start, end) = loc.get_abs_loc();
+        let (Some(&start), Some(&end)) = (self.offsets.get(start), self.offsets.get(end)) else {
+            return self.literal.span()
+        };
+        self.literal.subspan(start..end).unwrap_or_else(|| self.literal.span())
+    }
+
+    /// Converts lexed tokens into a token stream, giving each token the span of its location.
+    pub fn to_token_stream<T>(
+        &self,
+        tokens: impl IntoIterator<Item = (T, SrcLoc)>,
+        mut convert: impl FnMut(T, Span) -> TokenTree,
+    ) -> TokenStream {
+        tokens.into_iter().map(|(token, loc)| convert(token, self.span(loc))).collect()
+    }
+}
+
+/// Resolves the escapes of a string literal, keeping the offset in the literal of each byte of the value.
+fn unescape(literal: &str) -> Option<(String, Vec<usize>)> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let start = hashes + 2;
+        let value = literal.get(start..literal.len().checked_sub(hashes + 1)?)?;
+        let offsets = (start..=start + value.len()).collect();
+        return Some((value.to_string(), offsets))
+    }
+
+    let content = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::new();
+    let mut offsets = Vec::new();
+    let mut chars = content.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next()?.1 {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                '0' => '\0',
+                '\\' => '\\',
+                '\'' => '\'',
+                '"' => '"',
+                'x' => {
+                    let hex: String = [chars.next()?.1, chars.next()?.1].iter().collect();
+                    char::from(u8::from_str_radix(&hex, 16).ok()?)
+                },
+                'u' => {
+                    chars.next().filter(|(_, c)| *c == '{')?;
+                    let mut hex = String::new();
+                    loop {
+                        match chars.next()?.1 {
+                            '}' => break,
+                            '_' => {},
+                            c => hex.push(c),
+                        }
+                    }
+                    char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                },
+                // A line continuation skips the following whitespace
+                '\n' => {
+                    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+                    continue
+                },
+                _ => return None,
+            }
+        } else {
+            c
+        };
+        value.push(c);
+        offsets.extend(std::iter::repeat_n(1 + i, c.len_utf8()));
+    }
+    offsets.push(literal.len() - 1);
+    Some((value, offsets))
+}
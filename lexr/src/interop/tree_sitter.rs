@@ -0,0 +1,203 @@
+//! Using lexr to write [tree-sitter](https://tree-sitter.github.io) external scanners.
+//!
+//! Tokens that are hard to express in a tree-sitter grammar, like indentation or heredocs, can be lexed by an external scanner.
+//! The scanner is a type implementing [`ExternalScanner`], usually by running a lexer on the input,
+//! and [`tree_sitter_scanner!`](crate::tree_sitter_scanner) exports it as the C functions tree-sitter expects:
+//!
+//! ```ignore
+//! #[derive(Clone, Default)]
+//! struct Scanner;
+//!
+//! impl ExternalScanner for Scanner {
+//!     const SYMBOLS: usize = 1;
+//!
+//!     fn scan(&mut self, input: &str, valid_symbols: &[bool]) -> Option<(u16, usize)> {
+//!         let (symbol, loc) = heredoc(input).try_next()?.ok()?;
+//!         valid_symbols[symbol as usize].then_some((symbol, loc.get_abs_loc().1))
+//!     }
+//! }
+//!
+//! lexr::tree_sitter_scanner!(my_language, Scanner);
+//! ```
+//!
+//! Tree-sitter provides the input one character at a time, and the end of a token must be marked while it is the current position,
+//! so the scanner is run on what has been read so far while the token it finds still reaches the end of that input.
+//! Once it finds a token ending before the end of the input, more input comes after the token, and the token is accepted if its end was marked.
+//! Otherwise the input is read until [`ExternalScanner::LOOKAHEAD`] bytes past the end of the last token it found,
+//! and the token is only accepted if the scanner still finds it in all of the input that was read.
+//! The scanner state is cloned for each attempt, and only the state of the accepted scan is kept.
+
+use std::ffi::{c_char, c_void};
+
+/// The size of the buffer tree-sitter provides for serializing the state of a scanner.
+pub const SERIALIZATION_BUFFER_SIZE: usize = 1024;
+
+/// The lexer tree-sitter passes to an external scanner.
+#[repr(C)]
+pub struct TSLexer {
+    pub lookahead: i32,
+    pub result_symbol: u16,
+    pub advance: unsafe extern "C" fn(*mut TSLexer, bool),
+    pub mark_end: unsafe extern "C" fn(*mut TSLexer),
+    pub get_column: unsafe extern "C" fn(*mut TSLexer) -> u32,
+    pub is_at_included_range_start: unsafe extern "C" fn(*const TSLexer) -> bool,
+    pub eof: unsafe extern "C" fn(*const TSLexer) -> bool,
+}
+
+/// An external scanner for a tree-sitter grammar.
+pub trait ExternalScanner: Clone + Default {
+    /// The number of external tokens declared in the grammar.
+    const SYMBOLS: usize;
+
+    /// How many bytes are read past the end of the last token found, before scanning stops.
+    ///
+    /// This must cover the lookahead of the patterns, and the longest input that is not a token by itself, but begins one.
+    const LOOKAHEAD: usize = 16;
+
+    /// Scans a token at the start of the input.
+    ///
+    /// Returns the index of the token among the external tokens, and its length in bytes, like the end of its [`SrcLoc`](crate::SrcLoc).
+    fn scan(&mut self, input: &str, valid_symbols: &[bool]) -> Option<(u16, usize)>;
+
+    /// Writes the state of the scanner. Tree-sitter only keeps [`SERIALIZATION_BUFFER_SIZE`] bytes,
+    /// and a larger state is dropped, so the scanner is restored to its default state instead.
+    fn serialize(&self, _buffer: &mut Vec<u8>) {}
+
+    /// Restores the state written by `serialize`. It is only called with the bytes of a serialized state.
+    fn deserialize(&mut self, _bytes: &[u8]) {}
+}
+
+#[doc(hidden)]
+pub fn create<S: ExternalScanner>() -> *mut c_void {
+    Box::into_raw(Box::new(S::default())).cast()
+}
+
+#[doc(hidden)]
+/// # Safety
+/// The payload must have been created by `create::<S>`.
+pub unsafe fn destroy<S: ExternalScanner>(payload: *mut c_void) {
+    drop(Box::from_raw(payload.cast::<S>()))
+}
+
+#[doc(hidden)]
+/// # Safety
+/// The arguments must be the ones tree-sitter passes to the scan function.
+pub unsafe fn scan<S: ExternalScanner>(payload: *mut c_void, lexer: *mut TSLexer, valid_symbols: *const bool) -> bool {
+    let scanner = &mut *payload.cast::<S>();
+    let valid_symbols = std::slice::from_raw_parts(valid_symbols, S::SYMBOLS);
+
+    let mut input = String::new();
+    let mut marked = None;
+    let accepted = loop {
+        let mut candidate = scanner.clone();
+        let found = candidate.scan(&input, valid_symbols);
+        match found {
+            // More input follows the token, so reading more would not change it
+            Some((symbol, len)) if len < input.len() => break (marked == Some(len)).then_some((symbol, candidate)),
+            Some((_, len)) => {
+                ((*lexer).mark_end)(lexer);
+                marked = Some(len);
+            },
+            None => (),
+        }
+
+        // Tokens found in a prefix of the input might not be found once more is read
+        let at_end = ((*lexer).eof)(lexer) || input.len() - marked.unwrap_or(0) >= S::LOOKAHEAD;
+        let next = char::from_u32((*lexer).lookahead as u32).filter(|_| !at_end);
+        let Some(c) = next else {
+            break found.map(|(symbol, _)| (symbol, candidate))
+        };
+        input.push(c);
+        ((*lexer).advance)(lexer, false);
+    };
+
+    match accepted {
+        Some((symbol, candidate)) => {
+            *scanner = candidate;
+            (*lexer).result_symbol = symbol;
+            true
+        },
+        None => false,
+    }
+}
+
+#[doc(hidden)]
+/// # Safety
+/// The payload must have been created by `create::<S>`, and the buffer must be [`SERIALIZATION_BUFFER_SIZE`] bytes.
+pub unsafe fn serialize<S: ExternalScanner>(payload: *mut c_void, buffer: *mut c_char) -> u32 {
+    let mut bytes = Vec::new();
+    (*payload.cast::<S>()).serialize(&mut bytes);
+    // A truncated state would be restored as a different one, and a panic can not unwind into tree-sitter
+    if bytes.len() > SERIALIZATION_BUFFER_SIZE {
+        return 0
+    }
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), buffer.cast::<u8>(), bytes.len());
+    bytes.len() as u32
+}
+
+#[doc(hidden)]
+/// # Safety
+/// The payload must have been created by `create::<S>`, and the buffer must hold `length` bytes.
+pub unsafe fn deserialize<S: ExternalScanner>(payload: *mut c_void, buffer: *const c_char, length: u32) {
+    let scanner = &mut *payload.cast::<S>();
+    *scanner = S::default();
+    if length > 0 {
+        scanner.deserialize(std::slice::from_raw_parts(buffer.cast::<u8>(), length as usize));
+    }
+}
+
+/// Exports an [`ExternalScanner`](crate::interop::tree_sitter::ExternalScanner) as the external scanner of a tree-sitter language.
+///
+/// `tree_sitter_scanner!(my_language, Scanner)` defines the functions `tree_sitter_my_language_external_scanner_create`,
+/// `..._destroy`, `..._scan`, `..._serialize` and `..._deserialize`.
+#[macro_export]
+macro_rules! tree_sitter_scanner {
+    ($lang:ident, $scanner:ty) => {
+        $crate::concat_idents!(name = tree_sitter_, $lang, _external_scanner_create {
+            #[no_mangle]
+            pub extern "C" fn name() -> *mut std::ffi::c_void {
+                $crate::interop::tree_sitter::create::<$scanner>()
+            }
+        });
+
+        $crate::concat_idents!(name = tree_sitter_, $lang, _external_scanner_destroy {
+            #[no_mangle]
+            /// # Safety
+            /// Must only be called by tree-sitter.
+            pub unsafe extern "C" fn name(payload: *mut std::ffi::c_void) {
+                $crate::interop::tree_sitter::destroy::<$scanner>(payload)
+            }
+        });
+
+        $crate::concat_idents!(name = tree_sitter_, $lang, _external_scanner_scan {
+            #[no_mangle]
+            /// # Safety
+            /// Must only be called by tree-sitter.
+            pub unsafe extern "C" fn name(
+                payload: *mut std::ffi::c_void,
+                lexer: *mut $crate::interop::tree_sitter::TSLexer,
+                valid_symbols: *const bool,
+            ) -> bool {
+                $crate::interop::tree_sitter::scan::<$scanner>(payload, lexer, valid_symbols)
+            }
+        });
+
+        $crate::concat_idents!(name = tree_sitter_, $lang, _external_scanner_serialize {
+            #[no_mangle]
+            /// # Safety
+            /// Must only be called by tree-sitter.
+            pub unsafe extern "C" fn name(payload: *mut std::ffi::c_void, buffer: *mut std::ffi::c_char) -> u32 {
+                $crate::interop::tree_sitter::serialize::<$scanner>(payload, buffer)
+            }
+        });
+
+        $crate::concat_idents!(name = tree_sitter_, $lang, _external_scanner_deserialize {
+            #[no_mangle]
+            /// # Safety
+            /// Must only be called by tree-sitter.
+            pub unsafe extern "C" fn name(payload: *mut std::ffi::c_void, buffer: *const std::ffi::c_char, length: u32) {
+                $crate::interop::tree_sitter::deserialize::<$scanner>(payload, buffer, length)
+            }
+        });
+    };
+}
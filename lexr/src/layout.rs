@@ -0,0 +1,157 @@
+use crate::SrcLoc;
+
+/// The offside rule of languages like Haskell and Elm, which turns indentation into virtual braces and semicolons for the parser.
+///
+/// The pass runs between the lexer and the parser. After a layout keyword, like `where` or `let`, the next token opens a block
+/// at its column, unless it is an explicit opening brace. Each following line starting at that column gets a separator,
+/// and the block is closed before the first line starting left of it, or at the end of the tokens.
+/// The virtual tokens are zero-width, at the start of the token they precede, or after the last token at the end.
+///
+/// The tokens should not include an eof token, as the blocks are closed after the last token.
+///
+/// ```
+/// use lexr::{lex_rule, Layout};
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// enum Token { Where, Ident(String), Eq, Open, Semi, Close }
+/// use Token::*;
+///
+/// lex_rule!{lex -> Token {
+///     ws => |_| continue,
+///     "where" => |_| Where,
+///     "=" => |_| Eq,
+///     ident => |s| Ident(s.to_string()),
+/// }}
+///
+/// let source = "
+/// f = g where
+///     g = h
+///     h = f
+/// i = f";
+/// let layout = Layout::new(Open, Semi, Close, |token| *token == Where).top_level();
+/// let tokens: Vec<_> = layout.apply(lex(source)).into_iter().map(|(token, _)| token).collect();
+/// let ident = |s: &str| Ident(s.to_string());
+/// assert_eq!(tokens, vec![
+///     Open, ident("f"), Eq, ident("g"), Where,
+///         Open, ident("g"), Eq, ident("h"), Semi, ident("h"), Eq, ident("f"), Close,
+///     Semi, ident("i"), Eq, ident("f"), Close,
+/// ]);
+/// ```
+pub struct Layout<T> {
+    open: T,
+    separator: T,
+    close: T,
+    is_keyword: Predicate<T>,
+    braces: Option<(Predicate<T>, Predicate<T>)>,
+    top_level: bool,
+}
+
+impl<T: Clone> Layout<T> {
+    /// Creates a layout inserting the virtual `open`, `separator` and `close` tokens, with blocks opened after the tokens `is_keyword` is true for.
+    pub fn new(open: T, separator: T, close: T, is_keyword: impl Fn(&T) -> bool + 'static) -> Self {
+        Self { open, separator, close, is_keyword: Box::new(is_keyword), braces: None, top_level: false }
+    }
+
+    /// Lets blocks be written explicitly with braces instead, where the layout does not apply.
+    pub fn explicit_braces(mut self, is_open: impl Fn(&T) -> bool + 'static, is_close: impl Fn(&T) -> bool + 'static) -> Self {
+        self.braces = Some((Box::new(is_open), Box::new(is_close)));
+        self
+    }
+
+    /// Opens a block at the first token, like the top level of a Haskell module.
+    pub fn top_level(mut self) -> Self {
+        self.top_level = true;
+        self
+    }
+
+    /// Inserts the virtual tokens into the tokens.
+    pub fn apply(&self, tokens: impl IntoIterator<Item = (T, SrcLoc)>) -> Vec<(T, SrcLoc)> {
+        let is_open = |token: &T| self.braces.as_ref().is_some_and(|(is_open, _)| is_open(token));
+        let is_close = |token: &T| self.braces.as_ref().is_some_and(|(_, is_close)| is_close(token));
+
+        let mut out = Vec::new();
+        // The blocks, with the column of each implicit block, or `None` for an explicit one
+        let mut blocks: Vec<Option<usize>> = Vec::new();
+        // Whether the next token opens a block
+        let mut pending = self.top_level;
+        let mut prev: Option<SrcLoc> = None;
+        for (token, loc) in tokens {
+            let (line, col) = loc.start();
+            let at = zero_width(loc);
+
+            if pending {
+                if is_open(&token) {
+                    blocks.push(None);
+                } else {
+                    out.push((self.open.clone(), at));
+                    let enclosing = match blocks.last() {
+                        Some(Some(enclosing)) => *enclosing,
+                        _ => 0,
+                    };
+                    if col > enclosing {
+                        blocks.push(Some(col));
+                    } else {
+                        // Not indented past the enclosing block, so the new block is empty
+                        out.push((self.close.clone(), at));
+                        self.new_line(&mut out, &mut blocks, col, at);
+                    }
+                }
+            } else if prev.is_some_and(|prev| line > prev.end().0) {
+                self.new_line(&mut out, &mut blocks, col, at);
+            }
+
+            if is_close(&token) {
+                while let Some(Some(_)) = blocks.last() {
+                    out.push((self.close.clone(), at));
+                    blocks.pop();
+                }
+                blocks.pop();
+            }
+            pending = (self.is_keyword)(&token);
+            prev = Some(loc);
+            out.push((token, loc));
+        }
+
+        let end = match prev {
+            Some(prev) if prev.get_abs_loc().0 < prev.get_abs_loc().1 => {
+                let (line, col) = prev.end();
+                let idx = prev.get_abs_loc().1;
+                SrcLoc::new((line, col + 1), (line, col + 1), (idx, idx))
+            },
+            Some(prev) => prev,
+            None => SrcLoc::new((1, 1), (1, 1), (0, 0)),
+        };
+        if pending {
+            out.push((self.open.clone(), end));
+            out.push((self.close.clone(), end));
+        }
+        for _ in blocks.into_iter().flatten() {
+            out.push((self.close.clone(), end));
+        }
+        out
+    }
+
+    /// Closes the blocks right of the first token of a line, and separates it from the previous line if it is at the column of the block.
+    fn new_line(&self, out: &mut Vec<(T, SrcLoc)>, blocks: &mut Vec<Option<usize>>, col: usize, at: SrcLoc) {
+        while let Some(Some(block)) = blocks.last() {
+            if col >= *block {
+                break
+            }
+            out.push((self.close.clone(), at));
+            blocks.pop();
+        }
+        if let Some(Some(block)) = blocks.last() {
+            if col == *block {
+                out.push((self.separator.clone(), at));
+            }
+        }
+    }
+}
+
+type Predicate<T> = Box<dyn Fn(&T) -> bool>;
+
+/// A zero-width location at the start of the location.
+fn zero_width(loc: SrcLoc) -> SrcLoc {
+    let idx = loc.get_abs_loc().0;
+    SrcLoc::new(loc.start(), loc.start(), (idx, idx))
+}
@@ -1,5 +1,7 @@
 use std::{rc::Rc, cell::RefCell};
 
+use crate::{budget::BudgetState, lints::LintState, Budget, CancelToken, DiagnosticSink, Lints, Source, SrcLoc};
+
 /// A buffer for lexing.
 /// 
 /// This is a wrapper around a string slice that stores the current state of the lexer.
@@ -13,6 +15,32 @@ pub struct LexBuf<'a> {
     pub col: Rc<RefCell<usize>>,
     pub idx: Rc<RefCell<usize>>,
     pub empty: Rc<RefCell<bool>>,
+    pub cancel: Option<CancelToken>,
+    budget: Option<Rc<RefCell<BudgetState>>>,
+    progress: Option<Rc<RefCell<Progress<'a>>>>,
+    lints: Option<Rc<RefCell<LintState<'a>>>>,
+    unchecked: Option<Rc<RefCell<Unchecked<'a>>>>,
+    /// Whether lines and columns are tracked, or only byte offsets.
+    positions: bool,
+}
+
+/// How far ahead of the position the bytes of a buffer created with [`LexBuf::from_utf8_lazy`] are checked to be UTF-8.
+const CHECKED_AHEAD: usize = 1 << 20;
+
+/// Bytes that are checked to be UTF-8 a chunk at a time, as the buffer reaches them.
+struct Unchecked<'a> {
+    bytes: &'a [u8],
+    /// The length of the prefix that is checked to be UTF-8.
+    checked: usize,
+    /// Whether the bytes after the checked prefix are not valid UTF-8.
+    invalid: bool,
+}
+
+struct Progress<'a> {
+    callback: Box<dyn FnMut(usize, usize) + 'a>,
+    interval: usize,
+    total: usize,
+    next: usize,
 }
 
 impl<'a> LexBuf<'a> {
@@ -23,13 +51,304 @@ impl<'a> LexBuf<'a> {
             col: self.col.clone(),
             idx: self.idx.clone(),
             empty: self.empty.clone(),
+            cancel: self.cancel.clone(),
+            budget: self.budget.clone(),
+            progress: self.progress.clone(),
+            lints: self.lints.clone(),
+            unchecked: self.unchecked.clone(),
+            positions: self.positions,
+        }
+    }
+
+    /// Creates an independent buffer at the same position, which is not shared with this one.
+    /// 
+    /// The copy keeps the cancel token and spends the same budget, but does not report progress or lints.
+    pub fn fork(&self) -> Self {
+        Self {
+            source: Rc::new(RefCell::new(*self.source.borrow())),
+            line: Rc::new(RefCell::new(*self.line.borrow())),
+            col: Rc::new(RefCell::new(*self.col.borrow())),
+            idx: Rc::new(RefCell::new(*self.idx.borrow())),
+            empty: Rc::new(RefCell::new(*self.empty.borrow())),
+            cancel: self.cancel.clone(),
+            budget: self.budget.clone(),
+            progress: None,
+            lints: None,
+            unchecked: self.unchecked.clone(),
+            positions: self.positions,
+        }
+    }
+
+    /// Lets lexing on the buffer be cancelled with the token.
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// Limits the work of lexing on the buffer to the budget.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(Rc::new(RefCell::new(BudgetState::new(budget))));
+        self
+    }
+
+    #[doc(hidden)]
+    /// Spends a match of the budget, which follows a reject if `rejected`, returning whether the budget is exceeded.
+    pub fn exceeds_budget(&self, rejected: bool) -> bool {
+        self.budget.as_ref().is_some_and(|budget| budget.borrow_mut().spend(rejected))
+    }
+
+    /// Calls `progress(bytes_done, total)` while lexing, each time at least `interval` bytes have been lexed, and at the end.
+    /// 
+    /// This can be used to show progress for large inputs.
+    pub fn with_progress(mut self, interval: usize, progress: impl FnMut(usize, usize) + 'a) -> Self {
+        let idx = *self.idx.borrow();
+        self.progress = Some(Rc::new(RefCell::new(Progress {
+            callback: Box::new(progress),
+            interval: interval.max(1),
+            total: idx + self.source.borrow().len() + self.unchecked.as_ref().map_or(0, |unchecked| {
+                let unchecked = unchecked.borrow();
+                unchecked.bytes.len() - unchecked.checked
+            }),
+            next: idx,
+        })));
+        self
+    }
+
+    /// Checks the lines of the source with the lints as they are consumed, reporting into the sink.
+    /// 
+    /// Text consumed again after a [`rewind`](Self::rewind) is only checked once.
+    pub fn with_lints(mut self, lints: Lints, sink: impl DiagnosticSink + 'a) -> Self {
+        let idx = *self.idx.borrow();
+        self.lints = Some(Rc::new(RefCell::new(LintState::new(lints, sink, idx))));
+        self
+    }
+
+    /// Tracks only byte offsets, not lines and columns, which saves going through each character of the input.
+    /// 
+    /// The lines and columns of the locations are then 0. They can be found when needed with [`SrcLoc::from_offsets`].
+    /// Lexers sharing the buffer, like sub rules, should not track positions on their own buffers, as they would disagree.
+    /// With lints, the characters are checked anyway, so positions are tracked.
+    pub fn without_positions(mut self) -> Self {
+        self.positions = false;
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn report_progress(&self) {
+        if let Some(progress) = &self.progress {
+            let mut progress = progress.borrow_mut();
+            let done = *self.idx.borrow();
+            if done >= progress.next {
+                progress.next = if done >= progress.total { usize::MAX } else { (done + progress.interval).min(progress.total) };
+                let total = progress.total;
+                (progress.callback)(done, total);
+            }
         }
     }
 }
 
+/// A saved position in a [`LexBuf`], which can be returned to with [`LexBuf::rewind`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Checkpoint<'a> {
+    source: &'a str,
+    line: usize,
+    col: usize,
+    idx: usize,
+    empty: bool,
+}
+
 impl<'a> LexBuf<'a> {
-    pub fn new(source: impl Into<LexBuf<'a>>) -> Self {
-        source.into()
+    /// Saves the current position of the buffer.
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint {
+            source: *self.source.borrow(),
+            line: *self.line.borrow(),
+            col: *self.col.borrow(),
+            idx: *self.idx.borrow(),
+            empty: *self.empty.borrow(),
+        }
+    }
+
+    /// Returns the buffer to a saved position.
+    /// 
+    /// As the buffer is shared, this also affects any lexer using it.
+    pub fn rewind(&self, checkpoint: &Checkpoint<'a>) {
+        *self.source.borrow_mut() = checkpoint.source;
+        *self.line.borrow_mut() = checkpoint.line;
+        *self.col.borrow_mut() = checkpoint.col;
+        *self.idx.borrow_mut() = checkpoint.idx;
+        *self.empty.borrow_mut() = checkpoint.empty;
+    }
+
+    /// Continues the buffer with more input, as if it followed the current input, which is skipped if not fully consumed.
+    /// 
+    /// The position is kept, so lines and byte indexes count from the start of the first input.
+    pub fn resume(&self, input: &'a str) {
+        let rest = self.source.borrow().len();
+        self.advance(rest);
+        *self.source.borrow_mut() = input;
+        *self.empty.borrow_mut() = false;
+    }
+
+    #[doc(hidden)]
+    /// A zero-width location at the current position.
+    pub fn loc(&self) -> SrcLoc {
+        let pos = if self.tracks_positions() { (*self.line.borrow(), *self.col.borrow()) } else { (0, 0) };
+        let idx = *self.idx.borrow();
+        SrcLoc::new(pos, pos, (idx, idx))
+    }
+
+    /// A zero-width location at the end of the input.
+    /// 
+    /// This is found by scanning the remaining input, without consuming it.
+    pub fn end_loc(&self) -> SrcLoc {
+        if !self.tracks_positions() {
+            let idx = *self.idx.borrow() + self.source.borrow().len();
+            return SrcLoc::new((0, 0), (0, 0), (idx, idx))
+        }
+        let mut line = *self.line.borrow();
+        let mut col = *self.col.borrow();
+        let src = self.source.borrow();
+        for c in src.chars() {
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        let idx = *self.idx.borrow() + src.len();
+        SrcLoc::new((line, col), (line, col), (idx, idx))
+    }
+
+    /// Whether lines and columns are tracked, which is unless the buffer is [`without_positions`](Self::without_positions).
+    pub fn tracks_positions(&self) -> bool {
+        self.positions || self.lints.is_some()
+    }
+
+    #[doc(hidden)]
+    /// Whether the current position is at the start of a line.
+    pub fn at_line_start(&self) -> bool {
+        *self.col.borrow() == 1
+    }
+
+    #[doc(hidden)]
+    /// Whether the position `offset` bytes ahead is at the end of a line, or the end of the input.
+    pub fn at_line_end(&self, offset: usize) -> bool {
+        let rest = &self.source.borrow()[offset..];
+        rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n")
+    }
+
+    #[doc(hidden)]
+    /// Consumes `length` bytes from the source, updating the position.
+    /// 
+    /// Returns the location of the consumed text.
+    pub fn advance(&self, length: usize) -> SrcLoc {
+        let mut src = self.source.borrow_mut();
+        let mut line = self.line.borrow_mut();
+        let mut col = self.col.borrow_mut();
+        let mut idx = self.idx.borrow_mut();
+
+        let mut lints = self.lints.as_ref().map(|lints| lints.borrow_mut());
+        if lints.is_none() && !self.positions {
+            // The column only tells if the position is at the start of a line, for `bol`
+            if length > 0 {
+                *col = if src[..length].ends_with('\n') { 1 } else { 2 };
+            }
+            *src = &src[length..];
+            self.check_ahead(&mut src);
+            let start_idx = *idx;
+            *idx += length;
+            return SrcLoc::new((0, 0), (0, 0), (start_idx, *idx))
+        }
+
+        let start = (*line, *col);
+        let mut end = start;
+        for (i, c) in src[..length].char_indices() {
+            end = (*line, *col);
+            if let Some(lints) = &mut lints {
+                lints.check(c, end, *idx + i);
+            }
+            if c == '\n' {
+                *line += 1;
+                *col = 1;
+            } else {
+                *col += 1;
+            }
+        }
+
+        *src = &src[length..];
+        self.check_ahead(&mut src);
+        let start_idx = *idx;
+        *idx += length;
+
+        if let Some(lints) = &mut lints {
+            if src.is_empty() {
+                lints.finish(*idx);
+            }
+        }
+
+        SrcLoc::new(start, end, (start_idx, *idx))
+    }
+
+    pub fn new(source: impl Source<'a>) -> Self {
+        source.into_buf()
+    }
+
+    /// Creates a buffer over a byte slice, if it is valid UTF-8.
+    pub fn from_utf8(source: &'a [u8]) -> Result<Self, std::str::Utf8Error> {
+        std::str::from_utf8(source).map(LexBuf::from)
+    }
+
+    /// Creates a buffer over a byte slice, which is checked to be UTF-8 a chunk at a time as it is lexed, instead of all of it up front.
+    /// 
+    /// Lexing returns [`LexError::InvalidUtf8`](crate::LexError::InvalidUtf8) where the bytes are not valid UTF-8.
+    /// Patterns only see the next megabyte of the input, so a single match, or the lookahead of a pattern, can not be longer than that,
+    /// and `\z` in a pattern only matches at the end of the input within that distance.
+    pub fn from_utf8_lazy(source: &'a [u8]) -> Self {
+        let buf = LexBuf::from("");
+        // The checked part of the source starts out empty
+        *buf.source.borrow_mut() = unsafe { std::str::from_utf8_unchecked(&source[..0]) };
+        let buf = LexBuf { unchecked: Some(Rc::new(RefCell::new(Unchecked { bytes: source, checked: 0, invalid: false }))), ..buf };
+        buf.check_ahead(&mut buf.source.borrow_mut());
+        buf
+    }
+
+    /// Extends the source with the bytes after it that are checked to be UTF-8, until it reaches [`CHECKED_AHEAD`] bytes or the end.
+    fn check_ahead(&self, src: &mut &'a str) {
+        let Some(unchecked) = &self.unchecked else { return };
+        let mut unchecked = unchecked.borrow_mut();
+        let bytes = unchecked.bytes;
+        // The source is not within the bytes after resuming with other input
+        let end = (src.as_ptr() as usize + src.len()).wrapping_sub(bytes.as_ptr() as usize);
+        if end > unchecked.checked || src.len() > end {
+            return
+        }
+        let start = end - src.len();
+        while unchecked.checked - start < CHECKED_AHEAD && unchecked.checked < bytes.len() && !unchecked.invalid {
+            let chunk_end = (unchecked.checked + CHECKED_AHEAD).min(bytes.len());
+            match std::str::from_utf8(&bytes[unchecked.checked..chunk_end]) {
+                Ok(_) => unchecked.checked = chunk_end,
+                Err(error) => {
+                    // A character cut off by the end of the chunk is checked with the next chunk
+                    unchecked.invalid = error.error_len().is_some() || chunk_end == bytes.len();
+                    unchecked.checked += error.valid_up_to();
+                },
+            }
+        }
+        // Safety: The bytes up to `checked` are valid UTF-8, and `start` is the start of the source, at a character boundary
+        *src = unsafe { std::str::from_utf8_unchecked(&bytes[start..unchecked.checked]) };
+    }
+
+    #[doc(hidden)]
+    /// Whether the source of a buffer checked as it is lexed ends because the rest is not valid UTF-8.
+    pub fn stopped_at_invalid_utf8(&self) -> bool {
+        self.unchecked.as_ref().is_some_and(|unchecked| unchecked.borrow().invalid)
     }
 }
 
@@ -41,6 +360,12 @@ impl<'a> From<&'a str> for LexBuf<'a> {
             col: Rc::new(RefCell::new(1)),
             idx: Rc::new(RefCell::new(0)),
             empty: Rc::new(RefCell::new(false)),
+            cancel: None,
+            budget: None,
+            progress: None,
+            lints: None,
+            unchecked: None,
+            positions: true,
         }
     }
 }
@@ -53,6 +378,12 @@ impl<'a> From<&'a String> for LexBuf<'a> {
             col: Rc::new(RefCell::new(1)),
             idx: Rc::new(RefCell::new(0)),
             empty: Rc::new(RefCell::new(false)),
+            cancel: None,
+            budget: None,
+            progress: None,
+            lints: None,
+            unchecked: None,
+            positions: true,
         }
     }
 }
\ No newline at end of file
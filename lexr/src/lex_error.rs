@@ -0,0 +1,78 @@
+use std::fmt::Display;
+
+use crate::{LexerSpec, RuleId, SrcLoc};
+
+#[derive(Clone, Debug, PartialEq)]
+/// An error that occurred while lexing.
+/// 
+/// Returned by [`Lexer::try_next`](crate::Lexer::try_next). When iterating a lexer directly, these are raised as panics instead,
+/// except for cancellation, which just ends the iteration.
+pub enum LexError {
+    /// None of the patterns matched the input at the location, starting with the character.
+    UnexpectedChar(char, SrcLoc),
+    /// Lexing was cancelled with a [`CancelToken`](crate::CancelToken) before the location.
+    Cancelled(SrcLoc),
+    /// Lexing used up its [`Budget`](crate::Budget) before the location.
+    BudgetExceeded(SrcLoc),
+    /// The input is not valid UTF-8 at the location, found when lexing a buffer checked as it is lexed, like a [`MappedSource`](crate::MappedSource).
+    InvalidUtf8(SrcLoc),
+    /// The lexer would never make progress, as a rule keeps matching the empty input without producing a token.
+    Stuck(Box<Repro>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// What is needed to reproduce an error in a lexer, to include in bug reports.
+pub struct Repro {
+    /// The name of the lexer function.
+    pub lexer: &'static str,
+    /// The [fingerprint](LexerSpec::fingerprint) of the rules of the lexer, to tell if a report is for the same rules.
+    pub fingerprint: u64,
+    /// The rule involved in the error.
+    pub rule: RuleId,
+    /// The location of the error.
+    pub loc: SrcLoc,
+    /// The input at the location, up to the end of the line, and at most 80 characters.
+    pub input: String,
+}
+
+impl Repro {
+    #[doc(hidden)]
+    pub fn new(spec: &LexerSpec, rule: RuleId, loc: SrcLoc, rest: &str) -> Self {
+        let line = rest.split('\n').next().unwrap_or_default();
+        Self { lexer: spec.name, fingerprint: spec.fingerprint(), rule, loc, input: line.chars().take(80).collect() }
+    }
+}
+
+impl LexError {
+    /// The location of the error.
+    pub fn loc(&self) -> SrcLoc {
+        match self {
+            LexError::UnexpectedChar(_, loc) => *loc,
+            LexError::Cancelled(loc) => *loc,
+            LexError::BudgetExceeded(loc) => *loc,
+            LexError::InvalidUtf8(loc) => *loc,
+            LexError::Stuck(repro) => repro.loc,
+        }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexError::UnexpectedChar(c, loc) => write!(f, "Unexpected character '{}' at {}", c, loc),
+            LexError::Cancelled(loc) => write!(f, "Lexing was cancelled at {}", loc),
+            LexError::BudgetExceeded(loc) => write!(f, "Lexing exceeded its budget at {}", loc),
+            LexError::InvalidUtf8(loc) => write!(f, "Invalid UTF-8 at {}", loc),
+            LexError::Stuck(repro) => write!(f, "Lexer is stuck, as {} keeps matching the empty input: {}", repro.rule.name, repro),
+        }
+    }
+}
+
+impl Display for Repro {
+    /// Writes the repro on one line, like `lexer lex, rules 5d3a..., rule 2 at 1:3, input "b"`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "lexer {}, rules {:016x}, rule {} at {}, input {:?}", self.lexer, self.fingerprint, self.rule.index + 1, self.loc, self.input)
+    }
+}
+
+impl std::error::Error for LexError {}
@@ -3,6 +3,78 @@ pub use concat_idents::concat_idents;
 pub use lazy_static;
 pub use regex;
 
+/// A regex fragment of a pattern that is used as it is, without being grouped.
+///
+/// Fragments are grouped when a pattern has several of them, so alternatives with `|` stay within their fragment.
+/// A verbatim fragment is not, which allows flags like `(?i)` to apply to the fragments following it:
+///
+///     use lexr::{lex_rule, verbatim};
+///
+///     lex_rule!{lex -> &'static str {
+///         verbatim("(?i)") "select" => |_| "select",
+///     }}
+///
+///     assert_eq!(lex("SELECT").into_token_vec(), vec!["select"]);
+///
+/// The regexes are still anchored to the start of the remaining input as a whole.
+pub fn verbatim(regex: &str) -> Verbatim<'_> {
+    Verbatim(regex)
+}
+
+/// A fragment of a pattern that is not grouped, created with [`verbatim`].
+#[derive(Clone, Copy, Debug)]
+pub struct Verbatim<'a>(pub &'a str);
+
+#[doc(hidden)]
+/// A fragment of the regex of a pattern.
+pub trait RegexFragment {
+    fn push_to(&self, regex: &mut String, grouped: bool);
+}
+
+/// Whether the fragment is only a repetition, like `*` or `{2,3}?`, which applies to the fragment before it.
+fn is_repetition(fragment: &str) -> bool {
+    let fragment = fragment.strip_suffix('?').filter(|f| !f.is_empty()).unwrap_or(fragment);
+    match fragment.strip_prefix('{').and_then(|f| f.strip_suffix('}')) {
+        Some(counts) => !counts.is_empty() && counts.split(',').count() <= 2 && counts.chars().all(|c| c.is_ascii_digit() || c == ','),
+        None => matches!(fragment, "*" | "+" | "?"),
+    }
+}
+
+impl RegexFragment for str {
+    fn push_to(&self, regex: &mut String, grouped: bool) {
+        if grouped && !is_repetition(self) {
+            regex.push_str("(?:");
+            regex.push_str(self);
+            regex.push(')');
+        } else {
+            regex.push_str(self);
+        }
+    }
+}
+
+impl RegexFragment for String {
+    fn push_to(&self, regex: &mut String, grouped: bool) {
+        self.as_str().push_to(regex, grouped)
+    }
+}
+
+impl RegexFragment for Verbatim<'_> {
+    fn push_to(&self, regex: &mut String, _grouped: bool) {
+        regex.push_str(self.0)
+    }
+}
+
+impl<F: RegexFragment + ?Sized> RegexFragment for &F {
+    fn push_to(&self, regex: &mut String, grouped: bool) {
+        (**self).push_to(regex, grouped)
+    }
+}
+
+#[doc(hidden)]
+pub fn push_fragment(regex: &mut String, fragment: &impl RegexFragment, grouped: bool) {
+    fragment.push_to(regex, grouped)
+}
+
 #[macro_export]
 /// Define a lexer function with provided rules.
 ///
@@ -42,10 +114,175 @@ pub use regex;
 ///     ]);
 ///
 macro_rules! lex_rule {
-    ($v:vis $name:ident $(<$($lt:lifetime),+>)? $(($($arg:ident: $arg_typ:ty),*))? -> $token:ty {
-        $($regpat:tt $($regex:expr)* => |$id:pat_param $(,$src_id:pat_param $(,$loc_id:pat_param)?)?| $closure:expr),* $(,)?
+    ($(#[doc = $doc:literal])* $v:vis $name:ident $(<$($lt:lifetime),+>)? $(($($arg:ident: $arg_typ:ty),*))? -> $token:ty {
+        $($rules:tt)*
     }) => {
-    lexr::concat_idents!(name = _LEXER_, $name {
+        $crate::lex_rule!(@rules {{[$($doc)*] $v $name $(<$($lt),+>)? $(($($arg: $arg_typ),*))? -> $token} [false] [] [true]} [] [] $($rules)*);
+    };
+
+    // Munches the rules one at a time, collecting them before generating the lexer.
+    (@rules $header:tt [$($rules:tt)*] [$($eof:tt)*] $(,)?) => {
+        $crate::lex_rule!(@docs $header [] [] [$($rules)*] [$($eof)*]);
+    };
+
+    (@rules $header:tt $rules:tt $eof:tt config {$($config:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::lex_rule!(@config $header $rules $eof [$($config)*] $($($rest)*)?);
+    };
+
+    (@rules $header:tt [$($rules:tt)*] [] on_eof => |$loc_id:pat_param| $eof:expr $(, $($rest:tt)*)?) => {
+        $crate::lex_rule!(@rules $header [$($rules)*] [($loc_id) $eof] $($($rest)*)?);
+    };
+
+    (@rules $header:tt [$($rules:tt)*] [] on_eof => $eof:expr $(, $($rest:tt)*)?) => {
+        $crate::lex_rule!(@rules $header [$($rules)*] [() $eof] $($($rest)*)?);
+    };
+
+    (@rules $header:tt [$($rules:tt)*] [$($eof:tt)*] on_eof => $($rest:tt)*) => {
+        ::core::compile_error!("Only a single 'on_eof' declaration is allowed");
+    };
+
+    (@rules $header:tt [$($rules:tt)*] $eof:tt #[doc = $doc:literal] $($rest:tt)+) => {
+        $crate::lex_rule!(@rules $header [$($rules)* (doc $doc)] $eof $($rest)+);
+    };
+
+    (@rules $header:tt [$($rules:tt)*] [$($eof:tt)*] $pat:tt => |$id:pat_param $(, @$binder:ident $bound:pat_param)+| $closure:expr $(, $($rest:tt)*)?) => {
+        $crate::lex_rule!(@binders $header [$($rules)*] [$($eof)*] {[$pat] ($id) () () () () $closure} [$(@$binder $bound),+] $($($rest)*)?);
+    };
+
+    (@rules $header:tt [$($rules:tt)*] [$($eof:tt)*] $pat:tt => |$id:pat_param $(,$src_id:pat_param $(,$loc_id:pat_param)?)?| $closure:expr $(, $($rest:tt)*)?) => {
+        $crate::lex_rule!(@rules $header [$($rules)* {[$pat] ($id) ($($src_id)?) ($($($loc_id)?)?) () () $closure}] [$($eof)*] $($($rest)*)?);
+    };
+
+    (@rules $header:tt [$($rules:tt)*] [$($eof:tt)*] $($rest:tt)+) => {
+        $crate::lex_rule!(@pattern $header [$($rules)*] [$($eof)*] [] $($rest)+);
+    };
+
+    // Applies the options of a config block, which are kept in the header and the eof declaration.
+    (@config $header:tt $rules:tt $eof:tt [$(,)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@rules $header $rules $eof $($rest)*);
+    };
+
+    (@config {$signature:tt [$old:tt] $regex:tt $positions:tt} $rules:tt $eof:tt [longest_match: $longest:literal $(, $($config:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@config {$signature [$longest] $regex $positions} $rules $eof [$($($config)*)?] $($rest)*);
+    };
+
+    (@config {$signature:tt $longest:tt $regex:tt [$old:tt]} $rules:tt $eof:tt [positions: $positions:literal $(, $($config:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@config {$signature $longest $regex [$positions]} $rules $eof [$($($config)*)?] $($rest)*);
+    };
+
+    // The regex settings are kept as calls on `RegexOptions::new()`
+    (@config {$signature:tt $longest:tt [$($regex:tt)*] $positions:tt} $rules:tt $eof:tt [unicode: $unicode:literal $(, $($config:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@config {$signature $longest [$($regex)* .unicode($unicode)] $positions} $rules $eof [$($($config)*)?] $($rest)*);
+    };
+
+    (@config {$signature:tt $longest:tt [$($regex:tt)*] $positions:tt} $rules:tt $eof:tt [regex_size_limit: $limit:expr $(, $($config:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@config {$signature $longest [$($regex)* .size_limit($limit)] $positions} $rules $eof [$($($config)*)?] $($rest)*);
+    };
+
+    (@config {$signature:tt $longest:tt [$($regex:tt)*] $positions:tt} $rules:tt $eof:tt [dfa_size_limit: $limit:expr $(, $($config:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@config {$signature $longest [$($regex)* .dfa_size_limit($limit)] $positions} $rules $eof [$($($config)*)?] $($rest)*);
+    };
+
+    (@config $header:tt $rules:tt [] [eof_token: $eof:expr $(, $($config:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@config $header $rules [() $eof] [$($($config)*)?] $($rest)*);
+    };
+
+    (@config $header:tt $rules:tt [$($eof:tt)+] [eof_token: $($config:tt)*] $($rest:tt)*) => {
+        ::core::compile_error!("Only a single 'on_eof' declaration is allowed, which 'eof_token' also is");
+    };
+
+    (@config $header:tt $rules:tt $eof:tt [$key:ident $($config:tt)*] $($rest:tt)*) => {
+        ::core::compile_error!(::core::concat!("Unknown config `", ::core::stringify!($key), "`, expected `longest_match`, `eof_token`, `positions`, `unicode`, `regex_size_limit` or `dfa_size_limit`"));
+    };
+
+    // Collects the tokens of a pattern spanning multiple tokens.
+    (@pattern $header:tt [$($rules:tt)*] [$($eof:tt)*] [$($pat:tt)+] => |$id:pat_param $(, @$binder:ident $bound:pat_param)+| $closure:expr $(, $($rest:tt)*)?) => {
+        $crate::lex_rule!(@binders $header [$($rules)*] [$($eof)*] {[$($pat)+] ($id) () () () () $closure} [$(@$binder $bound),+] $($($rest)*)?);
+    };
+
+    (@pattern $header:tt [$($rules:tt)*] [$($eof:tt)*] [$($pat:tt)+] => |$id:pat_param $(,$src_id:pat_param $(,$loc_id:pat_param)?)?| $closure:expr $(, $($rest:tt)*)?) => {
+        $crate::lex_rule!(@rules $header [$($rules)* {[$($pat)+] ($id) ($($src_id)?) ($($($loc_id)?)?) () () $closure}] [$($eof)*] $($($rest)*)?);
+    };
+
+    // A literal rule may give its token directly, without a closure
+    (@pattern $header:tt [$($rules:tt)*] [$($eof:tt)*] [lit $lit:tt] => $token:expr $(, $($rest:tt)*)?) => {
+        $crate::lex_rule!(@rules $header [$($rules)* {[lit $lit] (_) () () () () $token}] [$($eof)*] $($($rest)*)?);
+    };
+
+    (@pattern $header:tt [$($rules:tt)*] [$($eof:tt)*] [$($pat:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::lex_rule!(@pattern $header [$($rules)*] [$($eof)*] [$($pat)* $next] $($rest)*);
+    };
+
+    // Attaches the doc comments of each rule to it, at the front, as the action can not be followed by more.
+    (@docs $header:tt [$($done:tt)*] [$($doc:literal)*] [(doc $next:literal) $($rules:tt)*] $eof:tt) => {
+        $crate::lex_rule!(@docs $header [$($done)*] [$($doc)* $next] [$($rules)*] $eof);
+    };
+
+    (@docs $header:tt [$($done:tt)*] [$($doc:literal)*] [{$($rule:tt)*} $($rules:tt)*] $eof:tt) => {
+        $crate::lex_rule!(@docs $header [$($done)* {[$($doc)*] $($rule)*}] [] [$($rules)*] $eof);
+    };
+
+    (@docs $header:tt $done:tt [] [] $eof:tt) => {
+        $crate::lex_rule!(@lexer $header $done $eof);
+    };
+
+    (@docs $header:tt $done:tt [$($doc:literal)+] [] $eof:tt) => {
+        ::core::compile_error!("A doc comment must be followed by a rule");
+    };
+
+    // Places the named binders of an action, like `@loc loc`, where the positional parameters would be.
+    (@binders $header:tt [$($rules:tt)*] [$($eof:tt)*] {[$($pat:tt)+] ($id:pat_param) ($($src_id:pat_param)?) ($($loc_id:pat_param)?) ($($rule_id:pat_param)?) ($($norm:tt)*) $closure:expr} [] $($rest:tt)*) => {
+        $crate::lex_rule!(@rules $header [$($rules)* {[$($pat)+] ($id) ($($src_id)?) ($($loc_id)?) ($($rule_id)?) ($($norm)*) $closure}] [$($eof)*] $($rest)*);
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt {$pat:tt $id:tt () $loc_id:tt $rule_id:tt $norm:tt $closure:expr} [@buf $src_id:pat_param $(, $($binders:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@binders $header $rules $eof {$pat $id ($src_id) $loc_id $rule_id $norm $closure} [$($($binders)*)?] $($rest)*);
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt {$pat:tt $id:tt $src_id:tt () $rule_id:tt $norm:tt $closure:expr} [@loc $loc_id:pat_param $(, $($binders:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@binders $header $rules $eof {$pat $id $src_id ($loc_id) $rule_id $norm $closure} [$($($binders)*)?] $($rest)*);
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt {$pat:tt $id:tt $src_id:tt $loc_id:tt () $norm:tt $closure:expr} [@rule $rule_id:pat_param $(, $($binders:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@binders $header $rules $eof {$pat $id $src_id $loc_id ($rule_id) $norm $closure} [$($($binders)*)?] $($rest)*);
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt {$pat:tt $id:tt $src_id:tt $loc_id:tt $rule_id:tt () $closure:expr} [@nfc $norm_id:pat_param $(, $($binders:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@binders $header $rules $eof {$pat $id $src_id $loc_id $rule_id (nfc $norm_id) $closure} [$($($binders)*)?] $($rest)*);
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt {$pat:tt $id:tt $src_id:tt $loc_id:tt $rule_id:tt () $closure:expr} [@nfkc $norm_id:pat_param $(, $($binders:tt)*)?] $($rest:tt)*) => {
+        $crate::lex_rule!(@binders $header $rules $eof {$pat $id $src_id $loc_id $rule_id (nfkc $norm_id) $closure} [$($($binders)*)?] $($rest)*);
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt $rule:tt [@buf $($binders:tt)*] $($rest:tt)*) => {
+        ::core::compile_error!("The binder `@buf` is given more than once");
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt $rule:tt [@loc $($binders:tt)*] $($rest:tt)*) => {
+        ::core::compile_error!("The binder `@loc` is given more than once");
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt $rule:tt [@rule $($binders:tt)*] $($rest:tt)*) => {
+        ::core::compile_error!("The binder `@rule` is given more than once");
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt $rule:tt [@nfc $($binders:tt)*] $($rest:tt)*) => {
+        ::core::compile_error!("Only one of the binders `@nfc` and `@nfkc` can be given");
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt $rule:tt [@nfkc $($binders:tt)*] $($rest:tt)*) => {
+        ::core::compile_error!("Only one of the binders `@nfc` and `@nfkc` can be given");
+    };
+
+    (@binders $header:tt $rules:tt $eof:tt $rule:tt [@$binder:ident $($binders:tt)*] $($rest:tt)*) => {
+        ::core::compile_error!(::core::concat!("Unknown binder `@", ::core::stringify!($binder), "`, expected `@buf`, `@loc`, `@rule`, `@nfc` or `@nfkc`"));
+    };
+
+    (@lexer {{[$($lexer_doc:literal)*] $v:vis $name:ident $(<$($lt:lifetime),+>)? $(($($arg:ident: $arg_typ:ty),*))? -> $token:ty} [$longest:literal] [$($regex_options:tt)*] [$positions:literal]} [$(
+        {[$($doc:literal)*] [$($pat:tt)+] ($id:pat_param) ($($src_id:pat_param)?) ($($loc_id:pat_param)?) ($($rule_id:pat_param)?) ($($form:ident $norm_id:pat_param)?) $closure:expr}
+    )*] [$($eof:tt)*]) => {
+    $crate::concat_idents!(__LexrLexer = _LEXER_, $name {
         #[allow(non_camel_case_types)]
         #[doc(hidden)]
         /// Automatically generated lexer struct. Do not access its fields directly!
@@ -53,127 +290,474 @@ macro_rules! lex_rule {
         /// The `tokens` method returns an iterator over the tokens, stripping away the source locations.
         /// 
         /// `vec` and `token_vec` methods are provided for convenience.
-        $v struct name<'_buf, $($($lt),+)?> {
-            buf: lexr::LexBuf<'_buf>,
+        $v struct __LexrLexer<'_buf, $($($lt),+)?> {
+            __lexr_buf: $crate::LexBuf<'_buf>,
+            __lexr_finished: bool,
+            __lexr_queue: ::std::collections::VecDeque<($token, $crate::SrcLoc, $crate::RuleId)>,
+            // The rules that matched the empty input at the position, which would match again forever
+            __lexr_empty: (usize, ::std::vec::Vec<usize>),
             $($($arg: $arg_typ),*)?
         }
 
-        impl<'_buf $(,$($lt),+)?> From<name<'_buf, $($($lt),+)?>> for lexr::Lexer<$token, name<'_buf $(,$($lt),+)?>> {
-            fn from(lexer: name<'_buf $(,$($lt),+)?>) -> Self {
-                lexr::Lexer::new(lexer)
+        // The bounds go through `Identity` to depend on the lifetime, as bounds on concrete types must hold
+        impl<'_buf $(,$($lt),+)?> ::core::clone::Clone for __LexrLexer<'_buf, $($($lt),+)?>
+        where
+            <$token as $crate::Identity<'_buf>>::Is: ::core::clone::Clone,
+            $($(<$arg_typ as $crate::Identity<'_buf>>::Is: ::core::clone::Clone),*)?
+        {
+            /// Snapshots the lexer, with a buffer that is not shared with this one.
+            fn clone(&self) -> Self {
+                __LexrLexer {
+                    __lexr_buf: self.__lexr_buf.fork(),
+                    __lexr_finished: self.__lexr_finished,
+                    __lexr_queue: self.__lexr_queue.clone(),
+                    __lexr_empty: self.__lexr_empty.clone(),
+                    $($($arg: self.$arg.clone()),*)?
+                }
+            }
+        }
+
+        impl<'_buf $(,$($lt),+)?> __LexrLexer<'_buf, $($($lt),+)?> {
+            #[doc(hidden)]
+            /// The spec of the lexer, for `lexer_spec!`, which needs no lexer.
+            pub fn __lexr_spec() -> &'static $crate::LexerSpec {
+                $crate::lazy_static::lazy_static!{
+                    static ref SPEC: $crate::LexerSpec = $crate::LexerSpec {
+                        name: ::core::stringify!($name),
+                        rules: ::std::vec![$({
+                            let mut spec = $crate::lex_rule!(@split (rule_spec (::core::stringify!($closure))) regex [] [] [] $($pat)+);
+                            spec.name = ::core::stringify!($($pat)+);
+                            spec.doc = $crate::lex_rule!(@doc $($doc)*);
+                            spec
+                        }),*],
+                        doc: $crate::lex_rule!(@doc $($lexer_doc)*),
+                        on_eof: $crate::lex_rule!(@eof_spec $($eof)*),
+                        longest_match: $longest,
+                        regex_options: $crate::regex_cache::RegexOptions::new() $($regex_options)*,
+                        positions: $positions,
+                    };
+                }
+                &SPEC
+            }
+        }
+
+        impl<'_buf $(,$($lt),+)?> ::core::convert::From<__LexrLexer<'_buf, $($($lt),+)?>> for $crate::Lexer<$token, __LexrLexer<'_buf $(,$($lt),+)?>> {
+            fn from(lexer: __LexrLexer<'_buf $(,$($lt),+)?>) -> Self {
+                $crate::Lexer::new(lexer)
             }
         }
 
-        impl<'_src, $($($lt),+)?> Iterator for name<'_src, $($($lt),+)?> {
-            type Item = ($token, lexr::SrcLoc);
+        impl<'_src, $($($lt),+)?> ::core::iter::Iterator for __LexrLexer<'_src, $($($lt),+)?> {
+            type Item = ($token, $crate::SrcLoc);
+
+            fn next(&mut self) -> ::core::option::Option<Self::Item> {
+                match $crate::RuleLexer::try_next(self)? {
+                    ::core::result::Result::Ok(item) => ::core::option::Option::Some(item),
+                    ::core::result::Result::Err($crate::LexError::Cancelled(_)) => ::core::option::Option::None,
+                    ::core::result::Result::Err(error) => ::core::panic!("{}", error),
+                }
+            }
+        }
+
+        impl<'_src, $($($lt),+)?> ::core::iter::FusedIterator for __LexrLexer<'_src, $($($lt),+)?> {}
+
+        impl<'_src, $($($lt),+)?> $crate::RuleLexer<'_src> for __LexrLexer<'_src, $($($lt),+)?> {
+            fn buf(&self) -> &$crate::LexBuf<'_src> {
+                &self.__lexr_buf
+            }
+
+            fn is_finished(&self) -> bool {
+                self.__lexr_finished && self.__lexr_queue.is_empty()
+            }
+
+            fn resume(&mut self, input: &'_src str) {
+                self.__lexr_buf.resume(input);
+                self.__lexr_finished = false;
+            }
+
+            fn spec(&self) -> &'static $crate::LexerSpec {
+                Self::__lexr_spec()
+            }
 
-            #[allow(unreachable_code)]
-            fn next(&mut self) -> Option<Self::Item> {
+            #[allow(unreachable_code, clippy::diverging_sub_expression)]
+            fn try_next_with_rule(&mut self) -> ::core::option::Option<::core::result::Result<(Self::Item, $crate::RuleId), $crate::LexError>> {
                 $($(let $arg: $arg_typ = self.$arg);*)?;
 
-                let start_idx = *self.buf.idx.borrow();
+                // The regexes of the rules are compiled with these settings
+                const __LEXR_REGEX_OPTIONS: $crate::regex_cache::RegexOptions = $crate::regex_cache::RegexOptions::new() $($regex_options)*;
 
-                let mut matched = false;
+                // Rules up to this one are skipped, as it was rejected
+                let mut skip = 0;
                 loop {
-                    // These allow for seamless matching of eof
-                    matched = false;
-                    let mut src = self.buf.source.borrow_mut();
-                    if *self.buf.empty.borrow() { break }
-                    if src.len() == 0 { *self.buf.empty.borrow_mut() = true; }
-                    
+                    // Tokens emitted by an earlier action come first
+                    if let ::core::option::Option::Some((token, loc, rule_id)) = self.__lexr_queue.pop_front() {
+                        return ::core::option::Option::Some(::core::result::Result::Ok(((token, loc), rule_id)))
+                    }
+                    if self.__lexr_finished { return ::core::option::Option::None }
+                    if self.__lexr_buf.is_cancelled() {
+                        self.__lexr_finished = true;
+                        return ::core::option::Option::Some(::core::result::Result::Err($crate::LexError::Cancelled(self.__lexr_buf.loc())));
+                    }
+                    if self.__lexr_buf.exceeds_budget(skip > 0) {
+                        self.__lexr_finished = true;
+                        return ::core::option::Option::Some(::core::result::Result::Err($crate::LexError::BudgetExceeded(self.__lexr_buf.loc())));
+                    }
+                    self.__lexr_buf.report_progress();
+
+                    // Eof rules may match the empty input once, after which the buffer is marked as empty
+                    let at_end = self.__lexr_buf.source.borrow().is_empty();
+                    if at_end && self.__lexr_buf.stopped_at_invalid_utf8() {
+                        self.__lexr_finished = true;
+                        return ::core::option::Option::Some(::core::result::Result::Err($crate::LexError::InvalidUtf8(self.__lexr_buf.loc())));
+                    }
+                    if at_end && skip == 0 {
+                        if *self.__lexr_buf.empty.borrow() { return $crate::lex_rule!(@on_eof self $($eof)*) }
+                        *self.__lexr_buf.empty.borrow_mut() = true;
+                    }
+
+                    // With the longest match, only the rule matching the most input is tried, the earliest of them on ties
+                    let longest: ::core::option::Option<usize> = if $longest {
+                        let mut best: ::core::option::Option<(usize, usize)> = ::core::option::Option::None;
+                        let mut rule = 0;
+                        $(
+                        rule += 1;
+                        if rule > skip {
+                            if let ::core::option::Option::Some(length) = $crate::lex_rule!(@split (find self) regex [] [] [] $($pat)+) {
+                                if best.is_none_or(|(_, best_length)| length > best_length) {
+                                    best = ::core::option::Option::Some((rule, length));
+                                }
+                            }
+                        }
+                        )*
+                        best.map(|(rule, _)| rule)
+                    } else { ::core::option::Option::None };
+
+                    let mut rule = 0;
                     $(
-                    let regex = lex_rule!(@regex_rule $regpat $($regex)*);
-                    if let Some(mat) = regex.find(&src) {
-                        matched = true;
-                        let length = mat.end();
-                        
-                        let start = (*self.buf.line.borrow(), *self.buf.col.borrow());
-                        let mut end = start;
-                        
-                        let mut source_iter = src.chars();
-                        for i in 0..length {
-                            let c = source_iter.next().unwrap();
-                            if i == length - 1 {
-                                end = (*self.buf.line.borrow(), *self.buf.col.borrow());
+                    rule += 1;
+                    let tried = rule > skip && (!$longest || longest == ::core::option::Option::Some(rule));
+                    let found = if tried { $crate::lex_rule!(@split (find self) regex [] [] [] $($pat)+) } else { ::core::option::Option::None };
+                    if let ::core::option::Option::Some(length) = found {
+                        let rule_id = $crate::RuleId::new(rule - 1, ::core::stringify!($($pat)+));
+                        // Matching the empty input again at the same position, whether the action continued or returned a token, never ends
+                        if length == 0 && !at_end {
+                            let idx = *self.__lexr_buf.idx.borrow();
+                            if self.__lexr_empty.0 != idx {
+                                self.__lexr_empty = (idx, ::std::vec::Vec::new());
                             }
-                            if c == '\n' {
-                                *self.buf.line.borrow_mut() += 1;
-                                *self.buf.col.borrow_mut() = 1;
-                            } else {
-                                *self.buf.col.borrow_mut() += 1;
+                            if self.__lexr_empty.1.contains(&rule_id.index) {
+                                self.__lexr_finished = true;
+                                let spec = $crate::RuleLexer::spec(self);
+                                let repro = $crate::Repro::new(spec, rule_id, self.__lexr_buf.loc(), *self.__lexr_buf.source.borrow());
+                                return ::core::option::Option::Some(::core::result::Result::Err($crate::LexError::Stuck(::std::boxed::Box::new(repro))));
                             }
+                            self.__lexr_empty.1.push(rule_id.index);
                         }
 
-                        *src = &src[length..];
-                        let end_idx = start_idx + length;
-                        self.buf.idx.replace(end_idx);
+                        let checkpoint = self.__lexr_buf.checkpoint();
+                        let src = *self.__lexr_buf.source.borrow();
+                        let loc = self.__lexr_buf.advance(length);
 
-                        let $id = mat.as_str();
-                        $($(let $loc_id = lexr::SrcLoc::new(start, end, (start_idx, end_idx));)?)?
-                        drop(src);
+                        let $id = &src[..length];
+                        $(let $loc_id = loc;)?
+                        $(let $rule_id = rule_id;)?
+                        $(let $norm_id = $crate::normalize::$form(&src[..length]);)?
+                        skip = 0;
                         let token = {
-                            $(let $src_id = self.buf.share();)?
+                            // Undoes the match, and tries the following rules instead
+                            #[allow(unused_macros)]
+                            macro_rules! reject {
+                                () => {{
+                                    skip = rule;
+                                    if length == 0 && !at_end {
+                                        self.__lexr_empty.1.pop();
+                                    }
+                                    self.__lexr_buf.rewind(&checkpoint);
+                                    continue
+                                }};
+                            }
+
+                            // Queues a token to be returned before the one returned by the action
+                            #[allow(unused_macros)]
+                            macro_rules! emit {
+                                ($emitted:expr) => { self.__lexr_queue.push_back(($emitted, loc, rule_id)) };
+                                ($emitted:expr, $emitted_loc:expr) => { self.__lexr_queue.push_back(($emitted, $emitted_loc, rule_id)) };
+                            }
+
+                            $(let $src_id = self.__lexr_buf.share();)?
                             $closure
                         };
 
-                        return Some((token, lexr::SrcLoc::new(start, end, (start_idx, end_idx))));
+                        if self.__lexr_queue.is_empty() { return ::core::option::Option::Some(::core::result::Result::Ok(((token, loc), rule_id))) }
+                        self.__lexr_queue.push_back((token, loc, rule_id));
+                        continue;
                     })*
 
-                    break
-                }
+                    if at_end { return $crate::lex_rule!(@on_eof self $($eof)*) }
 
-                if !*self.buf.empty.borrow() && !matched {
-                    if let Some(c) = self.buf.source.borrow().chars().next() {
-                        panic!("Unexpected character '{}' at {}", c, lexr::SrcLoc::new((*self.buf.line.borrow(), *self.buf.col.borrow()), (*self.buf.line.borrow(), *self.buf.col.borrow()), (*self.buf.idx.borrow(), *self.buf.idx.borrow())));
-                    }
+                    let c = self.__lexr_buf.source.borrow().chars().next().unwrap();
+                    self.__lexr_finished = true;
+                    return ::core::option::Option::Some(::core::result::Result::Err($crate::LexError::UnexpectedChar(c, self.__lexr_buf.loc())));
                 }
 
-                None
+                // An action used 'break'. Tokens it emitted are still returned
+                self.__lexr_finished = true;
+                self.__lexr_queue.pop_front().map(|(token, loc, rule_id)| ::core::result::Result::Ok(((token, loc), rule_id)))
             }
         }
 
+        $crate::lex_rule!{@lexer_fn $name [$($lexer_doc)*] [$({[$($doc)*] [$($pat)+]})*]
+            #[must_use]
+            $v fn $name<'_buf $(,$($lt),+)?>(buf: impl $crate::Source<'_buf> $(,$($arg: $arg_typ),*)?) -> $crate::Lexer<$token, __LexrLexer<'_buf $(,$($lt),+)?>> {
+                let buf = $crate::Source::into_buf(buf);
+                $crate::Lexer::new(__LexrLexer {
+                    __lexr_buf: if $positions { buf } else { buf.without_positions() },
+                    __lexr_finished: false,
+                    __lexr_queue: ::std::collections::VecDeque::new(),
+                    __lexr_empty: (0, ::std::vec::Vec::new()),
+                    $($($arg),*)?
+                })
+            }
+        }
+    });};
+
+    // Reached the end of the input. The lexer is finished, after emitting the eof token if declared.
+    // Documents the lexer function with the doc comments of the lexer, followed by its rules, or hides it if it has none.
+    // The struct is named again, as its name is not replaced within macro calls.
+    (@lexer_fn $name:ident [] $rules:tt $($item:tt)+) => {
+    $crate::concat_idents!(__LexrLexer = _LEXER_, $name {
         #[doc(hidden)]
-        #[must_use]
         /// Creates a new lexer from a string slice.
         /// 
         /// A [`Lexer`](crate::Lexer) is returned, which can be used to iterate over the tokens.
-        $v fn $name<'_buf $(,$($lt),+)?>(buf: impl Into<lexr::LexBuf<'_buf>> $(,$($arg: $arg_typ),*)?) -> lexr::Lexer<$token, name<'_buf $(,$($lt),+)?>> {
-            lexr::Lexer::new(name {
-                buf: buf.into(),
-                $($($arg),*)?
-            })
-        }
+        $($item)+
     });};
 
-    (@regex_rule _) => {{
-        lexr::lazy_static::lazy_static! {
-            static ref REGEX: lexr::regex::Regex = lexr::regex::Regex::new(r"(?s)^.").unwrap();
-        }; 
-        &REGEX
+    (@lexer_fn $name:ident [$($lexer_doc:literal)+] [$({[$($doc:literal)*] [$($pat:tt)+]})*] $($item:tt)+) => {
+    $crate::concat_idents!(__LexrLexer = _LEXER_, $name {
+        $(#[doc = $lexer_doc])+
+        #[doc = ""]
+        #[doc = "# Rules"]
+        #[doc = ""]
+        #[doc = "The rules, in the order they are tried:"]
+        #[doc = ""]
+        $(#[doc = $crate::lex_rule!(@doc_item [$($pat)+] $($doc)*)])*
+        $($item)+
+    });};
+
+    (@doc_item [$($pat:tt)+]) => {
+        ::core::concat!("- `", ::core::stringify!($($pat)+), "`")
+    };
+
+    (@doc_item [$($pat:tt)+] $($doc:literal)+) => {
+        ::core::concat!("- `", ::core::stringify!($($pat)+), "`:", $($doc),+)
+    };
+
+    // The lines of doc comments, without the space after `///`.
+    (@doc) => {
+        ::core::option::Option::None
+    };
+
+    (@doc $($doc:literal)+) => {
+        ::core::option::Option::Some([$($doc),+].map(|line: &str| line.strip_prefix(' ').unwrap_or(line)).join("\n"))
+    };
+
+    (@on_eof $self:ident) => {{
+        $self.__lexr_finished = true;
+        ::core::option::Option::None
     }};
 
-    (@regex_rule eof) => {{
-        lexr::lazy_static::lazy_static!{
-            static ref REGEX: lexr::regex::Regex = lexr::regex::Regex::new(r"^\z").unwrap();
-        }; 
-        &REGEX
+    (@on_eof $self:ident ($($loc_id:pat_param)?) $eof:expr) => {{
+        $self.__lexr_finished = true;
+        let loc = $self.__lexr_buf.loc();
+        $(let $loc_id = loc;)?
+        let rule_id = $crate::RuleId::new($crate::RuleLexer::spec($self).rules.len(), "on_eof");
+        ::core::option::Option::Some(::core::result::Result::Ok((($eof, loc), rule_id)))
     }};
 
-    (@regex_rule ws) => {{
-        lexr::lazy_static::lazy_static!{
-            static ref REGEX: lexr::regex::Regex = lexr::regex::Regex::new(r"^[ \n\r\t]").unwrap();
+    // Separates anchors and trailing context from the regex of a pattern, then continues with `@$then`.
+    (@split $then:tt $part:ident [$($regex:tt)*] [$($trail:tt)*] [$($anchor:tt)*] bol $($rest:tt)*) => {
+        $crate::lex_rule!(@split $then $part [$($regex)*] [$($trail)*] [$($anchor)* bol] $($rest)*)
+    };
+
+    (@split $then:tt $part:ident [$($regex:tt)*] [$($trail:tt)*] [$($anchor:tt)*] eol $($rest:tt)*) => {
+        $crate::lex_rule!(@split $then $part [$($regex)*] [$($trail)*] [$($anchor)* eol] $($rest)*)
+    };
+
+    (@split $then:tt $part:ident [$($regex:tt)*] [$($trail:tt)*] [$($anchor:tt)*] followed_by($($lookahead:tt)+) $($rest:tt)*) => {
+        $crate::lex_rule!(@split $then $part [$($regex)*] [$($trail)*] [$($anchor)* (followed_by $($lookahead)+)] $($rest)*)
+    };
+
+    (@split $then:tt $part:ident [$($regex:tt)*] [$($trail:tt)*] [$($anchor:tt)*] not_followed_by($($lookahead:tt)+) $($rest:tt)*) => {
+        $crate::lex_rule!(@split $then $part [$($regex)*] [$($trail)*] [$($anchor)* (not_followed_by $($lookahead)+)] $($rest)*)
+    };
+
+    (@split $then:tt regex [$($regex:tt)*] [] [$($anchor:tt)*] / $($rest:tt)*) => {
+        $crate::lex_rule!(@split $then trail [$($regex)*] [] [$($anchor)*] $($rest)*)
+    };
+
+    (@split $then:tt regex [$($regex:tt)*] [] [$($anchor:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::lex_rule!(@split $then regex [$($regex)* $next] [] [$($anchor)*] $($rest)*)
+    };
+
+    (@split $then:tt trail [$($regex:tt)*] [$($trail:tt)*] [$($anchor:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::lex_rule!(@split $then trail [$($regex)*] [$($trail)* $next] [$($anchor)*] $($rest)*)
+    };
+
+    (@split ($($then:tt)+) $part:ident [$($regex:tt)+] [$($trail:tt)*] [$($anchor:tt)*]) => {
+        $crate::lex_rule!(@ $($then)+ [$($regex)+] [$($trail)*] [$($anchor)*])
+    };
+
+    // Finds the length of the match of a pattern. Literals are compared directly
+    (@find $self:ident [lit $lit:expr] [] [$($anchor:tt)*]) => {{
+        let lit: &str = $lit;
+        $self.__lexr_buf.source.borrow().starts_with(lit)
+            .then_some(lit.len())
+            $(.filter(|&length| $crate::lex_rule!(@anchor $self $anchor length)))*
+    }};
+
+    (@find $self:ident [matcher $matcher:expr] [] [$($anchor:tt)*]) => {{
+        $crate::lazy_static::lazy_static!{
+            static ref MATCHER: ::std::boxed::Box<dyn $crate::Matcher + ::core::marker::Send + ::core::marker::Sync> = ::std::boxed::Box::new($matcher);
+        };
+        $crate::Matcher::find_at_start(&**MATCHER, &$self.__lexr_buf.source.borrow())
+            $(.filter(|&length| $crate::lex_rule!(@anchor $self $anchor length)))*
+    }};
+
+    (@find $self:ident [matcher $matcher:expr] [$($trail:tt)+] [$($anchor:tt)*]) => {
+        ::core::compile_error!("A pattern with a `matcher` can not have trailing context")
+    };
+
+    (@find $self:ident [$($regex:tt)+] [] [$($anchor:tt)*]) => {{
+        let matcher = $crate::lex_rule!(@regex_rule $($regex)+);
+        matcher.find(&$self.__lexr_buf.source.borrow())
+            $(.filter(|&length| $crate::lex_rule!(@anchor $self $anchor length)))*
+    }};
+
+    // The whole pattern is matched, but only the part before the trailing context is consumed
+    (@find $self:ident [$($regex:tt)+] [$($trail:tt)+] [$($anchor:tt)*]) => {{
+        let regex = $crate::lex_rule!(@trailing_regex_rule [$($regex)+] [$($trail)+]);
+        regex.captures(&$self.__lexr_buf.source.borrow())
+            .map(|captures| captures.get(1).unwrap().end())
+            $(.filter(|&length| $crate::lex_rule!(@anchor $self $anchor length)))*
+    }};
+
+    // Describes a rule for its spec
+    (@rule_spec $action:tt [matcher $matcher:expr] [$($trail:tt)*] [$($anchor:tt)*]) => {{
+        let mut spec = $crate::RuleSpec::new(::std::string::String::new(), $action);
+        spec.matcher = ::core::option::Option::Some(::core::stringify!($matcher));
+        $($crate::lex_rule!(@anchor_spec spec $anchor);)*
+        spec
+    }};
+
+    (@rule_spec $action:tt [$($regex:tt)+] [$($trail:tt)*] [$($anchor:tt)*]) => {{
+        let mut spec = $crate::RuleSpec::new($crate::lex_rule!(@regex_str $($regex)+), $action);
+        $($crate::lex_rule!(@anchor_spec spec $anchor);)*
+        $crate::lex_rule!(@trailing_spec spec $($trail)*);
+        spec
+    }};
+
+    (@trailing_spec $spec:ident) => {};
+
+    (@trailing_spec $spec:ident $($trail:tt)+) => {
+        $spec.trailing = ::core::option::Option::Some($crate::lex_rule!(@regex_str $($trail)+))
+    };
+
+    (@anchor_spec $spec:ident bol) => {
+        $spec.bol = true
+    };
+
+    (@anchor_spec $spec:ident eol) => {
+        $spec.eol = true
+    };
+
+    (@anchor_spec $spec:ident (followed_by $($lookahead:tt)+)) => {
+        $spec.followed_by.push($crate::lex_rule!(@regex_str $($lookahead)+))
+    };
+
+    (@anchor_spec $spec:ident (not_followed_by $($lookahead:tt)+)) => {
+        $spec.not_followed_by.push($crate::lex_rule!(@regex_str $($lookahead)+))
+    };
+
+    (@eof_spec) => {
+        ::core::option::Option::None
+    };
+
+    (@eof_spec ($($loc_id:pat_param)?) $eof:expr) => {
+        ::core::option::Option::Some(::core::stringify!($eof))
+    };
+
+    (@anchor $self:ident bol $length:ident) => {
+        $self.__lexr_buf.at_line_start()
+    };
+
+    (@anchor $self:ident eol $length:ident) => {
+        $self.__lexr_buf.at_line_end($length)
+    };
+
+    (@anchor $self:ident (followed_by $($lookahead:tt)+) $length:ident) => {
+        $crate::lex_rule!(@regex_rule $($lookahead)+).is_match(&$self.__lexr_buf.source.borrow()[$length..])
+    };
+
+    (@anchor $self:ident (not_followed_by $($lookahead:tt)+) $length:ident) => {
+        !$crate::lex_rule!(@regex_rule $($lookahead)+).is_match(&$self.__lexr_buf.source.borrow()[$length..])
+    };
+
+    (@regex_rule $($pat:tt)+) => {{
+        $crate::lazy_static::lazy_static!{
+            static ref MATCHER: $crate::scan::Matcher = $crate::scan::Matcher::with_options(&$crate::lex_rule!(@regex_str $($pat)+), __LEXR_REGEX_OPTIONS).unwrap();
         }; 
-        &REGEX
+        &MATCHER
     }};
 
-    (@regex_rule $($regex:expr)+) => {{
-        lexr::lazy_static::lazy_static!{
-            static ref REGEX: lexr::regex::Regex = lexr::regex::Regex::new({
-                let mut r_str = "^".to_string();
-                $(r_str.push_str($regex);)+
-                r_str
-            }.as_str()).unwrap();
+    (@trailing_regex_rule [$($pat:tt)+] [$($trail:tt)+]) => {{
+        $crate::lazy_static::lazy_static!{
+            static ref REGEX: $crate::regex::Regex = $crate::regex_cache::get_with(&::std::format!(
+                "^({})(?:{})", $crate::lex_rule!(@regex_str $($pat)+), $crate::lex_rule!(@regex_str $($trail)+)
+            ), __LEXR_REGEX_OPTIONS).unwrap();
         }; 
         &REGEX
     }};
-}
\ No newline at end of file
+
+    // The regex string of a pattern, without anchors
+    (@regex_str _) => {
+        ::std::string::String::from(r"(?su:.)")
+    };
+
+    (@regex_str eof) => {
+        ::std::string::String::from(r"\z")
+    };
+
+    (@regex_str ws) => {
+        ::std::string::String::from(r"[ \n\r\t]")
+    };
+
+    (@regex_str ident) => {
+        ::std::string::String::from($crate::classes::IDENT)
+    };
+
+    (@regex_str unicode_ident) => {
+        ::std::string::String::from($crate::classes::UNICODE_IDENT)
+    };
+
+    (@regex_str matcher $($matcher:tt)*) => {
+        ::core::compile_error!("A `matcher` must be the whole pattern, and can not be combined with regexes or used in lookahead")
+    };
+
+    (@regex_str lit $lit:expr) => {
+        $crate::regex::escape($lit)
+    };
+
+    (@regex_str $regex:expr) => {{
+        let mut r_str = ::std::string::String::new();
+        $crate::push_fragment(&mut r_str, &$regex, false);
+        r_str
+    }};
+
+    // Each of several fragments is grouped, so alternatives in one do not extend into the others
+    (@regex_str $($regex:expr)+) => {{
+        let mut r_str = ::std::string::String::new();
+        $($crate::push_fragment(&mut r_str, &$regex, true);)+
+        r_str
+    }};
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::{BoxedLexer, LexBuf, LexError, SrcLoc};
+
+/// A lexer kept warm for lexing many small inputs, like the snippets sent to a server, without allocating for each of them.
+///
+/// Creating a lexer allocates its buffer, and the first use of each rule looks up its compiled regex. A session creates its lexer once,
+/// with the regexes compiled up front, and [restarts](Self::lex) it on each input, collecting the tokens into a buffer it reuses.
+/// It also holds an [`Interner`], so the text of identifiers is only allocated the first time it is seen:
+///
+/// ```
+/// use lexr::{lex_rule, LexSession};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Token { Ident, Number(u32) }
+///
+/// lex_rule!{lex -> Token {
+///     ws => |_| continue,
+///     ident => |_| Token::Ident,
+///     "[0-9]+" => |s| Token::Number(s.parse().unwrap()),
+/// }}
+///
+/// let mut session = LexSession::new(|buf| lex(buf).boxed());
+/// let tokens = session.lex("x 1").unwrap();
+/// assert_eq!(tokens.iter().map(|(token, _)| token).collect::<Vec<_>>(), [&Token::Ident, &Token::Number(1)]);
+///
+/// for snippet in ["x 1", "y 2 x"] {
+///     session.lex(snippet).unwrap();
+///     let (tokens, interner) = session.tokens_and_interner();
+///     for (_, loc) in tokens.iter().filter(|(token, _)| *token == Token::Ident) {
+///         interner.intern(loc.text(snippet));
+///     }
+/// }
+/// assert_eq!(session.tokens_and_interner().1.len(), 2);
+/// assert!(session.lex("x ?").is_err());
+/// ```
+///
+/// The tokens can not borrow the input, which only lives for the call. Their text can be found from their locations, as above.
+pub struct LexSession<T> {
+    // Over an empty input between calls, so it never refers to an input that is gone
+    lexer: BoxedLexer<'static, T>,
+    tokens: Vec<(T, SrcLoc)>,
+    interner: Interner,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A string interned by an [`Interner`], which is the index of the string in it.
+pub struct Symbol(pub u32);
+
+#[derive(Clone, Debug, Default)]
+/// Stores each distinct string once, identifying it by a [`Symbol`].
+pub struct Interner {
+    symbols: HashMap<Box<str>, Symbol>,
+    strings: Vec<Box<str>>,
+}
+
+impl<T> LexSession<T> {
+    /// Creates a session with the lexer created by the function over a buffer, compiling the regexes of its rules now.
+    ///
+    /// Panics if a regex of the rules is invalid, like the lexer would when the rule is first tried.
+    pub fn new(lex: impl for<'a> FnOnce(LexBuf<'a>) -> BoxedLexer<'a, T>) -> Self {
+        let lexer = lex(LexBuf::from(""));
+        if let Err(error) = lexer.warm_up() {
+            panic!("Invalid regex in the rules of {}: {}", lexer.spec().name, error)
+        }
+        // Safety: The empty input is static. Other inputs are replaced before `lex` returns, and a panic in an action can only
+        // leave one behind in the lexer, which is replaced without being read when restarting. As the function is generic over the lifetime
+        // of the buffer, neither it nor the tokens can keep references to the input.
+        let lexer = unsafe { std::mem::transmute::<BoxedLexer<'_, T>, BoxedLexer<'static, T>>(lexer) };
+        Self { lexer, tokens: Vec::new(), interner: Interner::new() }
+    }
+
+    /// Lexes the input from its start, returning the tokens, or the error if it could not be lexed.
+    ///
+    /// The tokens are kept until the next input is lexed, in a buffer that is reused, so only inputs with more tokens than
+    /// any before allocate. Like when iterating, cancellation is not an error.
+    pub fn lex(&mut self, input: &str) -> Result<&[(T, SrcLoc)], LexError> {
+        // Safety: See `new`
+        let input = unsafe { std::mem::transmute::<&str, &'static str>(input) };
+        self.lexer.restart(input);
+        self.tokens.clear();
+        let result = loop {
+            match self.lexer.try_next() {
+                Some(Ok(pair)) => self.tokens.push(pair),
+                Some(Err(LexError::Cancelled(_))) | None => break Ok(()),
+                Some(Err(error)) => break Err(error),
+            }
+        };
+        self.lexer.restart("");
+        result.map(|()| self.tokens.as_slice())
+    }
+
+    /// The tokens of the last input, along with the interner, for interning the text of the tokens.
+    pub fn tokens_and_interner(&mut self) -> (&[(T, SrcLoc)], &mut Interner) {
+        (&self.tokens, &mut self.interner)
+    }
+
+    /// The interner of the session.
+    pub fn interner(&mut self) -> &mut Interner {
+        &mut self.interner
+    }
+}
+
+impl Interner {
+    /// Creates an empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The symbol of the string, which is allocated only if it has not been interned before.
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(text) {
+            return symbol
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        self.symbols.insert(text.into(), symbol);
+        self.strings.push(text.into());
+        symbol
+    }
+
+    /// The string of the symbol.
+    ///
+    /// Panics if the symbol is not from this interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    /// The number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether no strings have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
@@ -1,5 +1,5 @@
-use std::iter::Map;
-use crate::SrcLoc;
+use std::iter::{FusedIterator, Map};
+use crate::{validate::SpanValidator, BoxedLexer, DiagnosticSink, TokenStreams, TokenWindow, LexBuf, LexError, LexerSpec, RuleId, SrcLoc};
 
 /// A lexer produces tokens and locations from a defined lexing rule.
 /// 
@@ -8,20 +8,90 @@ use crate::SrcLoc;
 /// If it is unable to match any of the patterns, it will panic and report the unexpected character.
 pub struct Lexer<T, Ite: Iterator<Item = (T, crate::SrcLoc)>> {
     iter: Ite,
+    pushed_back: Vec<(T, SrcLoc)>,
 }
 
 impl<T, Ite: Iterator<Item = (T, crate::SrcLoc)>> Lexer<T, Ite> {
     #[doc(hidden)]
     pub fn new(iter: Ite) -> Self {
-        Self { iter }
+        Self { iter, pushed_back: Vec::new() }
     }
 }
 
+/// A token and its location, along with the id of the rule that produced it, as returned by [`Lexer::with_rules`].
+/// 
+/// Tokens that were pushed back have no rule.
+pub type RuledToken<T> = (T, SrcLoc, Option<RuleId>);
+
+#[doc(hidden)]
+/// Implemented by the lexer structs generated by [`lex_rule!`](crate::lex_rule!).
+/// 
+/// Gives the [`Lexer`] access to the underlying buffer, and to errors without panicking.
+pub trait RuleLexer<'src>: FusedIterator {
+    fn buf(&self) -> &LexBuf<'src>;
+
+    fn try_next(&mut self) -> Option<Result<Self::Item, LexError>> {
+        Some(self.try_next_with_rule()?.map(|(item, _)| item))
+    }
+
+    fn try_next_with_rule(&mut self) -> Option<Result<(Self::Item, RuleId), LexError>>;
+
+    fn spec(&self) -> &'static LexerSpec;
+
+    fn is_finished(&self) -> bool;
+
+    fn resume(&mut self, input: &'src str);
+}
+
+#[doc(hidden)]
+/// Makes a bound depend on a lifetime, which allows bounds on concrete types that may not hold in generated impls.
+pub trait Identity<'a> {
+    type Is: ?Sized;
+}
+
+impl<T: ?Sized> Identity<'_> for T {
+    type Is = T;
+}
+
+impl<T: Clone, Ite: Iterator<Item = (T, crate::SrcLoc)> + Clone> Clone for Lexer<T, Ite> {
+    fn clone(&self) -> Self {
+        Self { iter: self.iter.clone(), pushed_back: self.pushed_back.clone() }
+    }
+}
+
+impl<'src, R: RuleLexer<'src> + ?Sized> RuleLexer<'src> for Box<R> {
+    fn buf(&self) -> &LexBuf<'src> {
+        (**self).buf()
+    }
+
+    fn try_next(&mut self) -> Option<Result<Self::Item, LexError>> {
+        (**self).try_next()
+    }
+
+    fn try_next_with_rule(&mut self) -> Option<Result<(Self::Item, RuleId), LexError>> {
+        (**self).try_next_with_rule()
+    }
+
+    fn spec(&self) -> &'static LexerSpec {
+        (**self).spec()
+    }
+
+    fn is_finished(&self) -> bool {
+        (**self).is_finished()
+    }
+
+    fn resume(&mut self, input: &'src str) {
+        (**self).resume(input)
+    }
+}
+
+impl<T, Ite: FusedIterator<Item = (T, crate::SrcLoc)>> FusedIterator for Lexer<T, Ite> {}
+
 impl<T, Ite: Iterator<Item = (T, crate::SrcLoc)>> Iterator for Lexer<T, Ite> {
     type Item = (T, SrcLoc);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
+        self.pushed_back.pop().or_else(|| self.iter.next())
     }
 }
 
@@ -50,4 +120,214 @@ impl<T, Ite: Iterator<Item = (T, crate::SrcLoc)>> Lexer<T, Ite> {
     pub fn next_token(&mut self) -> Option<T> {
         self.next().map(|a| a.0)
     }
+
+    /// Puts a token back into the lexer, so it is returned by the next call to `next`.
+    /// 
+    /// Tokens pushed back are returned in reverse order, the last pushed being the first returned.\
+    /// The token does not have to come from the lexer, so this can also be used to inject synthesized tokens.
+    pub fn push_back(&mut self, token: T, loc: SrcLoc) {
+        self.pushed_back.push((token, loc));
+    }
+}
+
+impl<'src, T, Ite: Iterator<Item = (T, crate::SrcLoc)> + RuleLexer<'src>> Lexer<T, Ite> {
+    /// Creates an independent copy of the lexer at the same position, for lexing ahead speculatively.
+    /// 
+    /// Lexing with the fork does not affect this lexer, so the fork can just be dropped if it went down the wrong path.
+    /// This requires the token type and the arguments of the lexer to be `Clone`.
+    pub fn fork(&self) -> Self where Self: Clone {
+        self.clone()
+    }
+
+    /// An estimate of how many tokens are left, based on the length of the remaining input.
+    /// 
+    /// This assumes an average of 4 bytes per token, and is only meant for reserving capacity.
+    pub fn capacity_hint(&self) -> usize {
+        self.iter.buf().source.borrow().len() / 4 + 1 + self.pushed_back.len()
+    }
+
+    /// Gets the next token and location, or the error if the input could not be lexed.
+    /// 
+    /// Unlike `next`, this does not panic on unexpected input. After an error, the lexer is finished.
+    pub fn try_next(&mut self) -> Option<Result<(T, SrcLoc), LexError>> {
+        match self.pushed_back.pop() {
+            Some(pair) => Some(Ok(pair)),
+            None => self.iter.try_next(),
+        }
+    }
+
+    /// Like `try_next`, but also gets the id of the rule that produced the token.
+    pub fn try_next_with_rule(&mut self) -> Option<Result<RuledToken<T>, LexError>> {
+        match self.pushed_back.pop() {
+            Some((token, loc)) => Some(Ok((token, loc, None))),
+            None => Some(self.iter.try_next_with_rule()?.map(|((token, loc), rule)| (token, loc, Some(rule)))),
+        }
+    }
+
+    /// Returns an iterator over the tokens and locations, along with the id of the rule that produced each token.
+    /// 
+    /// This allows post-processing, like highlighting, to depend on the rule without encoding it in the tokens.
+    /// Like iterating the lexer, this panics on unexpected input.
+    pub fn with_rules(mut self) -> impl FusedIterator<Item = RuledToken<T>> + 'src where T: 'src, Ite: 'src {
+        std::iter::from_fn(move || match self.try_next_with_rule()? {
+            Ok(item) => Some(item),
+            Err(LexError::Cancelled(_)) => None,
+            Err(error) => panic!("{}", error),
+        }).fuse()
+    }
+
+    /// A zero-width location at the end of the input.
+    pub fn end_loc(&self) -> SrcLoc {
+        self.iter.buf().end_loc()
+    }
+
+    /// Whether the lexer is finished, meaning that it will only return `None` from now on.
+    /// 
+    /// This is the case once it has returned `None` or an error, unless tokens have been pushed back since.
+    pub fn is_finished(&self) -> bool {
+        self.pushed_back.is_empty() && self.iter.is_finished()
+    }
+
+    /// Continues lexing with more input following the earlier input, like the next entry of a REPL session.
+    /// 
+    /// Locations continue from the earlier input, so errors point at the line in the whole session,
+    /// and byte indexes are into all the input so far, concatenated. Any earlier input not yet lexed, like after an error, is skipped.
+    /// The arguments of the lexer are kept, and eof rules match again at the end of the new input.
+    pub fn resume(&mut self, input: &'src str) {
+        self.iter.resume(input)
+    }
+
+    /// Starts over on new input, like a new lexer with the same arguments, but reusing the allocations of this one.
+    /// 
+    /// Unlike [`resume`](Self::resume), locations count from the start of the new input. Tokens pushed back are dropped,
+    /// but tokens emitted by an action and not yet returned are not, so the lexer should be finished.
+    pub(crate) fn restart(&mut self, input: &'src str) {
+        self.pushed_back.clear();
+        // The old input is dropped without reading it, so it is not skipped like when resuming
+        let buf = self.iter.buf();
+        *buf.source.borrow_mut() = "";
+        *buf.line.borrow_mut() = 1;
+        *buf.col.borrow_mut() = 1;
+        *buf.idx.borrow_mut() = 0;
+        self.iter.resume(input);
+    }
+
+    /// A description of the rules of the lexer.
+    pub fn spec(&self) -> &'static LexerSpec {
+        self.iter.spec()
+    }
+
+    /// Compiles the regexes of all rules now, instead of when each rule is first tried. See [`LexerSpec::warm_up`].
+    pub fn warm_up(&self) -> Result<(), regex::Error> {
+        self.spec().warm_up()
+    }
+
+    /// A table of the rules of the lexer, in the order they are tried, for auditing large lexers.
+    pub fn describe(&self) -> String {
+        self.spec().to_string()
+    }
+
+    /// Buffers the tokens in a window retaining the last token read and the `history` tokens before it, for backtracking in bounded memory.
+    pub fn window(self, history: usize) -> TokenWindow<T, Self> {
+        TokenWindow::new(self, history)
+    }
+
+    /// Lexes the whole input, viewing the tokens both with and without the trivia, for which `is_trivia` is true.
+    pub fn streams(self, is_trivia: impl FnMut(&T) -> bool) -> TokenStreams<T> {
+        TokenStreams::new(self, is_trivia)
+    }
+
+    /// Returns an iterator over the tokens and locations, along with the text of each token in the source.
+    /// 
+    /// The source must be the one being lexed. This lets tokens carry no text themselves, which avoids allocating for each token.
+    pub fn with_text<'s>(self, source: &'s str) -> impl Iterator<Item = (T, SrcLoc, &'s str)> + 's where T: 's, Ite: 's {
+        self.map(move |(token, loc)| (token, loc, loc.text(source)))
+    }
+
+    /// Returns an iterator over the tokens and locations, checking the location of each token in debug builds.
+    /// 
+    /// The source must be the one being lexed. Each location must be within the source, not overlap the previous token,
+    /// and have lines and columns matching its byte offsets, or this panics. This catches actions that emit tokens with wrong locations,
+    /// or move the buffer in ways that break them. In release builds, the tokens are not checked.
+    pub fn validated<'s>(self, source: &'s str) -> impl Iterator<Item = (T, SrcLoc)> + 's where T: 's, Ite: 's {
+        let mut validator = SpanValidator::new(source);
+        self.inspect(move |(_, loc)| if cfg!(debug_assertions) { validator.check(*loc) })
+    }
+
+    /// Returns an iterator handing off lexing to an embedded lexer after each trigger token, like a script in HTML.
+    /// 
+    /// After a token for which `is_trigger` is true, `inner` is called with a buffer shared with this lexer,
+    /// and the tokens of the lexer it returns are yielded until it ends, after which this lexer continues where it stopped.
+    /// As the position is shared, the locations of both are into the same source. The inner lexer can end with `break`,
+    /// and if it ends at the end of the input instead, the eof rule of this lexer still matches.
+    pub fn embed<I: IntoIterator<Item = (T, SrcLoc)>>(
+        mut self,
+        mut is_trigger: impl FnMut(&T) -> bool,
+        mut inner: impl FnMut(LexBuf<'src>) -> I,
+    ) -> impl Iterator<Item = (T, SrcLoc)> {
+        // The inner lexer, and whether the end of the input had been matched before it
+        let mut embedded: Option<(I::IntoIter, bool)> = None;
+        std::iter::from_fn(move || {
+            if let Some((tokens, empty)) = &mut embedded {
+                match tokens.next() {
+                    Some(pair) => return Some(pair),
+                    None => {
+                        *self.iter.buf().empty.borrow_mut() = *empty;
+                        embedded = None;
+                    },
+                }
+            }
+            let (token, loc) = self.next()?;
+            if is_trigger(&token) {
+                let buf = self.iter.buf().share();
+                let empty = *buf.empty.borrow();
+                embedded = Some((inner(buf).into_iter(), empty));
+            }
+            Some((token, loc))
+        })
+    }
+
+    /// Clears the vector and fills it with the token/SrcLoc pairs.
+    /// 
+    /// This reuses the allocation of the vector, which is useful when lexing repeatedly, like in a REPL.
+    pub fn lex_into(self, buffer: &mut Vec<(T, SrcLoc)>) {
+        buffer.clear();
+        buffer.reserve(self.capacity_hint());
+        buffer.extend(self);
+    }
+
+    /// Erases the type of the underlying lexer, so lexers from different rules have the same type.
+    pub fn boxed(self) -> BoxedLexer<'src, T> where Ite: 'src {
+        Lexer { iter: Box::new(self.iter), pushed_back: self.pushed_back }
+    }
+
+    /// Collects the token/SrcLoc pairs into a vector, ending with the error if the input could not be lexed.
+    /// 
+    /// Unlike `into_vec`, this does not panic on unexpected input.
+    pub fn into_result_vec(mut self) -> Vec<Result<(T, SrcLoc), LexError>> {
+        std::iter::from_fn(|| self.try_next()).collect()
+    }
+
+    /// Collects the token/SrcLoc pairs into a vector, reporting the error into the sink if the input could not be lexed.
+    /// 
+    /// The vector has the tokens before the error. Like when iterating, cancellation is not reported.
+    pub fn into_vec_reporting(mut self, sink: &mut impl DiagnosticSink) -> Vec<(T, SrcLoc)> {
+        let mut tokens = Vec::with_capacity(self.capacity_hint());
+        while let Some(result) = self.try_next() {
+            match result {
+                Ok(pair) => tokens.push(pair),
+                Err(LexError::Cancelled(_)) => break,
+                Err(error) => sink.report(error.into()),
+            }
+        }
+        tokens
+    }
+
+    /// Collects the token/SrcLoc pairs into the given vector, reusing its allocation.
+    /// 
+    /// Any previous content of the vector is discarded.
+    pub fn collect_reusing(self, mut buffer: Vec<(T, SrcLoc)>) -> Vec<(T, SrcLoc)> {
+        self.lex_into(&mut buffer);
+        buffer
+    }
 }
\ No newline at end of file
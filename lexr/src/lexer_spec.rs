@@ -0,0 +1,559 @@
+use std::{collections::HashMap, fmt::{Display, Write}};
+
+use regex::Regex;
+
+use crate::{regex_cache::{self, RegexOptions}, scan::Matcher};
+
+#[derive(Clone, Debug, PartialEq)]
+/// A description of the rules of a lexer, as written in [`lex_rule!`](crate::lex_rule!).
+///
+/// It is obtained from a lexer with [`Lexer::spec`](crate::Lexer::spec), and can be used by tools working with lexers in general.
+pub struct LexerSpec {
+    /// The name of the lexer function.
+    pub name: &'static str,
+    /// The rules, in the order they are tried.
+    pub rules: Vec<RuleSpec>,
+    /// The source of the `on_eof` declaration, if any.
+    pub on_eof: Option<&'static str>,
+    /// Whether the rule matching the most input is used, set with `longest_match` in a `config` block, instead of the first rule that matches.
+    pub longest_match: bool,
+    /// The settings the regexes of the rules are compiled with, set with `unicode`, `regex_size_limit` and `dfa_size_limit` in a `config` block.
+    pub regex_options: RegexOptions,
+    /// Whether lines and columns are tracked, which is unless disabled with `positions: false` in a `config` block.
+    pub positions: bool,
+    /// The doc comments on the lexer, if any, which also document its function along with the rules.
+    pub doc: Option<String>,
+}
+
+#[macro_export]
+/// Gets the [`LexerSpec`] of a lexer defined with [`lex_rule!`](crate::lex_rule!) by its name, without creating a lexer.
+///
+/// # Examples
+///
+///     use lexr::{lex_rule, lexer_spec};
+///
+///     lex_rule!{lex -> u32 {
+///         ws => |_| continue,
+///         "[0-9]+" => |s| s.parse().unwrap(),
+///     }}
+///
+///     let spec = lexer_spec!(lex);
+///     assert_eq!(spec.name, "lex");
+///     assert_eq!(spec.rules[1].name, "\"[0-9]+\"");
+///
+macro_rules! lexer_spec {
+    ($lexer:ident) => {
+        $crate::concat_idents!(lexer = _LEXER_, $lexer { lexer::__lexr_spec() })
+    };
+}
+
+#[macro_export]
+/// Gets the [fingerprint](crate::lexer_spec::fingerprint) of the rules of lexers defined with [`lex_rule!`](crate::lex_rule!), by their names.
+///
+/// All the lexers of a language should be given, like the main lexer and its sub rules, so a change to any of them changes the fingerprint.
+///
+/// # Examples
+///
+///     use lexr::{lex_rule, lexer_fingerprint};
+///
+///     lex_rule!{lex -> () {
+///         "#" => |_, buf| { comment(buf).deplete(); continue },
+///         _ => |_| (),
+///     }}
+///
+///     lex_rule!{comment -> () {
+///         "\n" => |_| break,
+///         _ => |_| continue,
+///     }}
+///
+///     const CACHE_VERSION: u64 = 1;
+///     let version = lexer_fingerprint!(lex, comment) ^ CACHE_VERSION;
+///     # assert_ne!(version, lexer_fingerprint!(lex));
+///
+macro_rules! lexer_fingerprint {
+    ($($lexer:ident),+ $(,)?) => {
+        $crate::lexer_spec::fingerprint(&[$($crate::lexer_spec!($lexer)),+])
+    };
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A description of a single rule of a lexer.
+pub struct RuleSpec {
+    /// The pattern as written, like `"[0-9]+" / "px"`, which is also the name of its [`RuleId`].
+    pub name: &'static str,
+    /// The regex matching the pattern, without anchors and lookahead. It is empty for a pattern with a `matcher`.
+    pub regex: String,
+    /// The source of the [`Matcher`](crate::Matcher) of a pattern with a `matcher`, which is used instead of a regex.
+    pub matcher: Option<&'static str>,
+    /// The regex of the trailing context after `/`, if any.
+    pub trailing: Option<String>,
+    /// Whether the pattern is anchored with `bol`.
+    pub bol: bool,
+    /// Whether the pattern is anchored with `eol`.
+    pub eol: bool,
+    /// The regexes of the `followed_by` lookaheads.
+    pub followed_by: Vec<String>,
+    /// The regexes of the `not_followed_by` lookaheads.
+    pub not_followed_by: Vec<String>,
+    /// The source of the action.
+    pub action: &'static str,
+    /// The doc comments on the rule, if any, like what the tokens it produces are in the language.
+    pub doc: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Identifies the rule that produced a token.
+///
+/// It is given to actions with the `@rule` binder, and returned with the tokens by [`Lexer::with_rules`](crate::Lexer::with_rules).
+pub struct RuleId {
+    /// The index of the rule in [`LexerSpec::rules`]. The `on_eof` declaration comes after the last rule.
+    pub index: usize,
+    /// The pattern of the rule as written, like `"[0-9]+" / "px"`, or `on_eof`.
+    pub name: &'static str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A rule that can never match, as an earlier rule always matches where it would.
+///
+/// Found by [`LexerSpec::unreachable_rules`]. The indexes are those of [`LexerSpec::rules`].
+pub struct UnreachableRule {
+    /// The index of the rule that can never match.
+    pub index: usize,
+    /// The index of the earlier rule that matches instead.
+    pub shadowed_by: usize,
+}
+
+impl RuleId {
+    #[doc(hidden)]
+    pub fn new(index: usize, name: &'static str) -> Self {
+        Self { index, name }
+    }
+}
+
+impl RuleSpec {
+    #[doc(hidden)]
+    pub fn new(regex: String, action: &'static str) -> Self {
+        Self {
+            name: "",
+            regex,
+            matcher: None,
+            trailing: None,
+            bol: false,
+            eol: false,
+            followed_by: Vec::new(),
+            not_followed_by: Vec::new(),
+            action,
+            doc: None,
+        }
+    }
+
+    /// The regex with anchors, lookahead and trailing context, in the syntax shared by most regex engines.
+    ///
+    /// Unlike in the lexer, the regex is not anchored to the start of the input.
+    /// A pattern with a `matcher` has no regex, so this only has its anchors and lookahead.
+    pub fn full_regex(&self) -> String {
+        let mut regex = String::new();
+        if self.bol {
+            regex.push('^');
+        }
+        write!(regex, "(?:{})", self.regex).unwrap();
+        if let Some(trailing) = &self.trailing {
+            write!(regex, "(?={})", trailing).unwrap();
+        }
+        for lookahead in &self.followed_by {
+            write!(regex, "(?={})", lookahead).unwrap();
+        }
+        for lookahead in &self.not_followed_by {
+            write!(regex, "(?!{})", lookahead).unwrap();
+        }
+        if self.eol {
+            regex.push('$');
+        }
+        regex
+    }
+
+    /// Whether the action mentions the identifier, like the name of a token.
+    pub fn mentions(&self, ident: &str) -> bool {
+        self.action.split(|c: char| !(c.is_alphanumeric() || c == '_')).any(|word| word == ident)
+    }
+
+    /// Whether the rule matches wherever its regex does, without anchors, lookahead or trailing context that may stop it,
+    /// and without an action that may reject the match. A custom matcher can not be analyzed, so it may not match anywhere.
+    fn is_unconditional(&self) -> bool {
+        self.matcher.is_none() && !self.bol && !self.eol && self.trailing.is_none() && self.followed_by.is_empty() && self.not_followed_by.is_empty()
+            && !self.mentions("reject")
+    }
+
+    /// Whether this rule matches wherever the other one does, so the other can never match after it.
+    /// The regexes of the rules are compiled, anchored to the start, with `anchored`.
+    ///
+    /// This is conservative, and only recognizes some common cases.
+    fn shadows(&self, other: &RuleSpec, anchored: &mut impl FnMut(&str) -> Option<Regex>) -> bool {
+        // Assertions in the regex depend on what follows the match
+        if !self.is_unconditional() || ["$", r"\b", r"\B", r"\z"].iter().any(|a| self.regex.contains(a)) {
+            return false
+        }
+        let Some(regex) = anchored(&self.regex) else { return false };
+
+        // Matching the empty input, it matches everywhere
+        if regex.is_match("") {
+            return true
+        }
+        if other.matcher.is_some() {
+            return false
+        }
+        // The wildcard matches any character, so only rules matching the empty input, like `eof`, are left for the end of the input
+        if self.regex == "(?su:.)" {
+            return anchored(&other.regex).is_some_and(|other| !other.is_match(""))
+        }
+        if self.regex == other.regex {
+            return true
+        }
+        // A literal is matched if this matches a prefix of it, whatever follows
+        let is_literal = !other.regex.is_empty() && !other.regex.contains(|c| r"\.+*?()|[]{}^$".contains(c));
+        is_literal && regex.is_match(&other.regex)
+    }
+}
+
+impl LexerSpec {
+    /// The rule with the id, or `None` for the `on_eof` declaration.
+    pub fn rule(&self, id: RuleId) -> Option<&RuleSpec> {
+        self.rules.get(id.index)
+    }
+
+    /// Finds rules that can never match, as an earlier rule always matches first, like an identifier rule before a keyword.
+    ///
+    /// The analysis is conservative: a reported rule is unreachable, but not all unreachable rules are found.
+    /// Rules with anchors, lookahead, trailing context or an action that may reject the match are never considered to shadow others.
+    pub fn unreachable_rules(&self) -> Vec<UnreachableRule> {
+        // The anchored regexes are only used here, so they are kept out of the regex cache
+        let mut compiled: HashMap<String, Option<Regex>> = HashMap::new();
+        let mut anchored = |regex: &str| compiled.entry(regex.to_string())
+            .or_insert_with(|| self.regex_options.build(&format!("^(?:{})", regex)).ok())
+            .clone();
+
+        let mut unreachable = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            // With the longest match, an earlier rule only wins on ties, which are certain for the same pattern
+            let shadows = |earlier: &RuleSpec| match self.longest_match {
+                true => earlier.is_unconditional() && earlier.regex == rule.regex,
+                false => earlier.shadows(rule, &mut anchored),
+            };
+            if let Some(shadowed_by) = self.rules[..index].iter().position(shadows) {
+                unreachable.push(UnreachableRule { index, shadowed_by });
+            }
+        }
+        unreachable
+    }
+
+    /// Describes the [unreachable rules](Self::unreachable_rules), one per line, or returns `None` if there are none.
+    ///
+    /// The rules are numbered from 1, like in the table written by `Display`.
+    pub fn warnings(&self) -> Option<String> {
+        let unreachable = self.unreachable_rules();
+        if unreachable.is_empty() {
+            return None
+        }
+        let lines: Vec<_> = unreachable.iter().map(|u| format!(
+            "warning: rule {} ({}) in {} can never match, as rule {} ({}) always matches first",
+            u.index + 1, self.rules[u.index], self.name, u.shadowed_by + 1, self.rules[u.shadowed_by],
+        )).collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Compiles the regexes of all rules into the [regex cache](crate::regex_cache), so they are not compiled when the lexer is first used.
+    ///
+    /// Patterns matched by a [scanner](crate::scan) or a [`Matcher`](crate::Matcher) instead of a regex are not compiled.
+    ///
+    /// Servers can call this at startup for each lexer, like `lex("").spec().warm_up()?`, to avoid a latency spike on the first request.
+    /// It also finds invalid regexes at startup, which would otherwise panic when their rule is first tried.
+    pub fn warm_up(&self) -> Result<(), regex::Error> {
+        for rule in &self.rules {
+            match &rule.trailing {
+                _ if rule.matcher.is_some() => (),
+                Some(trailing) => regex_cache::get_with(&format!("^({})(?:{})", rule.regex, trailing), self.regex_options).map(drop)?,
+                None => Matcher::with_options(&rule.regex, self.regex_options).map(drop)?,
+            };
+            for lookahead in rule.followed_by.iter().chain(&rule.not_followed_by) {
+                Matcher::with_options(lookahead, self.regex_options)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A hash of the rules, which is the same for the same rules in any build, to match bug reports to versions of a lexer.
+    ///
+    /// For a language lexed by several lexers, like a main lexer with sub rules, see [`fingerprint`].
+    pub fn fingerprint(&self) -> u64 {
+        fingerprint(&[self])
+    }
+
+    /// Generates an approximate [TextMate grammar](https://macromates.com/manual/en/language_grammars) from the rules.
+    ///
+    /// Each rule becomes a match pattern, named by the first scope in `scopes` with a token that its action mentions,
+    /// like `("Number", "constant.numeric")`. Rules without a scope are kept, so they still consume their input.
+    ///
+    /// The grammar is approximate, as TextMate tries the patterns at every position, instead of where the last token ended.
+    pub fn to_textmate_json(&self, scope_name: &str, scopes: &[(&str, &str)]) -> String {
+        let mut json = String::new();
+        writeln!(json, "{{").unwrap();
+        writeln!(json, "  \"name\": {},", json_string(self.name)).unwrap();
+        writeln!(json, "  \"scopeName\": {},", json_string(scope_name)).unwrap();
+        writeln!(json, "  \"patterns\": [").unwrap();
+
+        // The end of the input is not a pattern in TextMate, and custom matchers have no regex
+        let rules: Vec<_> = self.rules.iter().filter(|rule| rule.regex != r"\z" && rule.matcher.is_none()).collect();
+        for (i, rule) in rules.iter().enumerate() {
+            write!(json, "    {{ \"match\": {}", json_string(&rule.full_regex())).unwrap();
+            if let Some((_, scope)) = scopes.iter().find(|(token, _)| rule.mentions(token)) {
+                write!(json, ", \"name\": {}", json_string(scope)).unwrap();
+            }
+            writeln!(json, " }}{}", if i + 1 < rules.len() { "," } else { "" }).unwrap();
+        }
+
+        writeln!(json, "  ]").unwrap();
+        write!(json, "}}").unwrap();
+        json
+    }
+
+    /// Draws how the lexer picks a rule as a [Graphviz](https://graphviz.org) graph in the DOT language, for debugging why an input took a path.
+    ///
+    /// Each rule is a node with its pattern as written and its action. Without the longest match, an edge leads from each rule to the next,
+    /// which is tried if it does not match, and from the last one to the error. With it, all rules are tried from the start.
+    /// The end of the input leads to the `on_eof` declaration, and [unreachable rules](Self::unreachable_rules) have a dashed edge
+    /// from the rule that shadows them. Render it with `dot -Tsvg`.
+    ///
+    /// The regex crate does not expose its automata, so the graph is of the rules, not of the states within a regex.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph {} {{", dot_string(self.name)).unwrap();
+        writeln!(dot, "  rankdir=LR;").unwrap();
+        writeln!(dot, "  node [shape=box];").unwrap();
+        writeln!(dot, "  start [shape=circle, label=\"\"];").unwrap();
+        writeln!(dot, "  error [shape=octagon, label=\"error\"];").unwrap();
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            writeln!(dot, "  rule{} [label={}];", i + 1, dot_string(&format!("{}: {}\n{}", i + 1, rule.name, rule.action))).unwrap();
+        }
+        if let Some(on_eof) = self.on_eof {
+            writeln!(dot, "  on_eof [label={}];", dot_string(&format!("on_eof\n{}", on_eof))).unwrap();
+            writeln!(dot, "  start -> on_eof [label=\"end of input\"];").unwrap();
+        }
+
+        if self.longest_match {
+            for i in 1..=self.rules.len() {
+                writeln!(dot, "  start -> rule{} [label=\"longest match\"];", i).unwrap();
+            }
+            writeln!(dot, "  start -> error [label=\"no match\"];").unwrap();
+        } else {
+            let nodes: Vec<_> = (1..=self.rules.len()).map(|i| format!("rule{}", i)).chain(["error".to_string()]).collect();
+            writeln!(dot, "  start -> {};", nodes[0]).unwrap();
+            for pair in nodes.windows(2) {
+                writeln!(dot, "  {} -> {} [label=\"no match\"];", pair[0], pair[1]).unwrap();
+            }
+        }
+
+        for unreachable in self.unreachable_rules() {
+            writeln!(dot, "  rule{} -> rule{} [style=dashed, label=\"shadows\"];", unreachable.shadowed_by + 1, unreachable.index + 1).unwrap();
+        }
+        write!(dot, "}}").unwrap();
+        dot
+    }
+
+    /// Writes the doc comments of the lexer and a list of its rules with their doc comments in Markdown,
+    /// like in the documentation of the lexer function, for keeping a written spec of the language in sync with the lexer.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# `{}`\n\n", self.name);
+        if let Some(doc) = &self.doc {
+            writeln!(markdown, "{}\n", doc).unwrap();
+        }
+        writeln!(markdown, "The rules, in the order they are tried:\n").unwrap();
+        for rule in &self.rules {
+            match &rule.doc {
+                Some(doc) => writeln!(markdown, "- `{}`: {}", rule.name, doc.lines().collect::<Vec<_>>().join(" ")).unwrap(),
+                None => writeln!(markdown, "- `{}`", rule.name).unwrap(),
+            }
+        }
+        markdown
+    }
+}
+
+impl Display for RuleSpec {
+    /// Writes the pattern as it is written in the macro, with the regexes as string literals.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.bol {
+            write!(f, "bol ")?;
+        }
+        match self.matcher {
+            Some(matcher) => write!(f, "matcher {}", matcher)?,
+            None => write!(f, "{:?}", self.regex)?,
+        }
+        if self.eol {
+            write!(f, " eol")?;
+        }
+        for lookahead in &self.followed_by {
+            write!(f, " followed_by({:?})", lookahead)?;
+        }
+        for lookahead in &self.not_followed_by {
+            write!(f, " not_followed_by({:?})", lookahead)?;
+        }
+        if let Some(trailing) = &self.trailing {
+            write!(f, " / {:?}", trailing)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for LexerSpec {
+    /// Writes a table of the rules in the order they are tried, with their patterns and actions.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let patterns: Vec<_> = self.rules.iter().map(|rule| rule.to_string()).collect();
+        let width = patterns.iter().map(|p| p.chars().count()).max().unwrap_or(0).max("pattern".len());
+        let number_width = self.rules.len().to_string().len();
+
+        write!(f, "{}", self.name)?;
+        if self.longest_match {
+            write!(f, " (longest match)")?;
+        }
+        if !self.regex_options.is_default() {
+            write!(f, " ({})", self.regex_options)?;
+        }
+        if !self.positions {
+            write!(f, " (no positions)")?;
+        }
+        writeln!(f)?;
+        write!(f, "  {:>number_width$}  {:width$}  action", "#", "pattern")?;
+        for (i, (rule, pattern)) in self.rules.iter().zip(&patterns).enumerate() {
+            write!(f, "\n  {:>number_width$}  {:width$}  {}", i + 1, pattern, rule.action)?;
+        }
+        if let Some(on_eof) = self.on_eof {
+            write!(f, "\n  {:>number_width$}  {:width$}  {}", "", "on_eof", on_eof)?;
+        }
+        Ok(())
+    }
+}
+
+/// A hash of the rules of the lexers, which is the same for the same rules in any build.
+///
+/// Caches of lexed or parsed files, and serialized token streams, can store it to tell when the language has changed,
+/// and must be invalidated. [`lexer_fingerprint!`](crate::lexer_fingerprint!) gets it for lexers by name.
+/// For a single lexer, this is its [`LexerSpec::fingerprint`].
+///
+/// It hashes the names of the lexers, their config and the regexes of the rules as built by this crate.
+/// The patterns as written, custom matchers and actions are only known as the source text printed by the compiler,
+/// which may space it differently between versions, so they are hashed with the whitespace between tokens collapsed,
+/// and the text of string and char literals kept as written.
+///
+/// This is a function of the specs, which are built at runtime, so there is no `FINGERPRINT` constant,
+/// but it needs no lexer to be created, nor any input.
+pub fn fingerprint(specs: &[&LexerSpec]) -> u64 {
+    let mut text = String::new();
+    for spec in specs {
+        write!(text, "{}\0{}\0{}\0{}\0", spec.name, spec.longest_match, spec.positions, spec.regex_options).unwrap();
+        for rule in &spec.rules {
+            let mut fields = vec![rule.regex.as_str(), rule.trailing.as_deref().unwrap_or("\0")];
+            fields.extend(rule.followed_by.iter().map(String::as_str));
+            fields.push("\0");
+            fields.extend(rule.not_followed_by.iter().map(String::as_str));
+            write!(text, "{}\0{}\0{}\0", fields.join("\0"), rule.bol, rule.eol).unwrap();
+            for source in [rule.name, rule.matcher.unwrap_or(""), rule.action] {
+                collapse_whitespace(source, &mut text);
+                text.push('\0');
+            }
+        }
+        if let Some(on_eof) = spec.on_eof {
+            collapse_whitespace(on_eof, &mut text);
+        }
+        text.push('\n');
+    }
+    fnv1a(text.as_bytes())
+}
+
+/// Writes Rust source text without the whitespace between its tokens, except a space between two words, like `move x`.
+/// String and char literals are written as they are, so `"else if"` and `"elseif"` stay different.
+fn collapse_whitespace(source: &str, text: &mut String) {
+    let chars: Vec<char> = source.chars().collect();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut space = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            space = true;
+            i += 1;
+            continue;
+        }
+        if space && is_word(c) && text.ends_with(is_word) {
+            text.push(' ');
+        }
+        space = false;
+        let end = match c {
+            '"' => {
+                // A raw string, like r#"a"#, ends at a quote followed by as many hashes as it starts with
+                let hashes = chars[..i].iter().rev().take_while(|&&c| c == '#').count();
+                if i > hashes && chars[i - hashes - 1] == 'r' {
+                    let closing = (i + 1..chars.len())
+                        .find(|&j| chars[j] == '"' && chars[j + 1..].iter().take_while(|&&c| c == '#').count() >= hashes);
+                    closing.map_or(chars.len(), |j| j + hashes + 1)
+                } else {
+                    literal_end(&chars, i, '"')
+                }
+            }
+            // A char literal, unlike a lifetime or a label, like 'a, is closed
+            '\'' if chars.get(i + 1) == Some(&'\\') || chars.get(i + 2) == Some(&'\'') => literal_end(&chars, i, '\''),
+            _ => i + 1,
+        };
+        text.extend(&chars[i..end]);
+        i = end;
+    }
+}
+
+/// The end of a string or char literal starting at `start`, after its closing quote.
+fn literal_end(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// FNV-1a, as the hashers of the standard library may change between versions.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// A quoted string in the DOT language, where only quotes and backslashes are escaped, and newlines break lines of labels.
+fn dot_string(s: &str) -> String {
+    let mut dot = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => dot.push_str("\\\""),
+            '\\' => dot.push_str("\\\\"),
+            '\n' => dot.push_str("\\n"),
+            c => dot.push(c),
+        }
+    }
+    dot.push('"');
+    dot
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
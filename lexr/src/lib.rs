@@ -44,17 +44,49 @@ lex_rule!{NAME(ARGS) -> TOKEN {
 - [ACTION](#actions) is an expression that is executed if the pattern matches. The expression must produce a token or `continue` or `break`.
 
 The rules consist of a pattern and an action resulting in a token.\
-The order of the patterns is important, as the first that matches is chosen.
+The order of the patterns is important, as the first that matches is chosen, unless the [config](#config) asks for the longest match.
 
 ## Patterns
 
 Patterns are matched to the beginning of the input in the order they are defined.
 
 Patterns can be the following:
-- One ore more string slice literals or constants. These strings are concatenated together, and used for regex matching.
+- One ore more string slice literals or constants, which are regexes matched one after the other.
 - A wildcard `_` that matches any single character. This does not match eof.
 - `eof`, which matches the end of the input. This is optional, and if not provided, end of file is just ignored.
 - `ws`, which matches any whitespace character.
+- `ident`, which matches an ASCII identifier, and `unicode_ident`, which matches a Unicode identifier like in Rust.
+  Regexes for these and other common classes, like `XID_START` and `DIGIT`, are in the `classes` module.
+- `lit` followed by a string, like `lit "->"`, which matches the string itself instead of a regex. Its action can also be just the token, like `lit "->" => Arrow`.
+- `matcher` followed by an expression implementing [`Matcher`](crate::Matcher), like `matcher scan_number`, which matches without a regex.
+  Functions from the input to the length of the match are matchers, so specialized scanners can be written by hand,
+  and regexes of the `regex` crate, and of `regex-automata` with the feature of that name, can be used as they are. See the [`matcher`](crate::matcher) module.
+
+Each string is its own group, so an alternative in one does not extend into the others or past the anchor: `"a|b" "c"` matches `ac` or `bc`.
+A string that is only a repetition, like `"*"` or `"{2}"`, repeats the whole string before it: with `const WORD: &str = "[a-z]+ ";`, `WORD "*"` matches any number of words.
+To use a string as it is, for instance for flags like `(?i)` that should apply to the following strings, wrap it in `verbatim`, like `lexr::verbatim("(?i)") "select"`.
+
+A pattern can also be anchored to lines, using the position tracked by the lexer:
+- `bol` before a pattern only lets it match at the start of a line, like `bol "#" "define"`.
+- `eol` after a pattern only lets it match if it ends at the end of a line or the input, like `"[a-z]+" eol`.
+
+Lookahead can be emulated with `followed_by(...)` and `not_followed_by(...)` after a pattern.
+These take a pattern that must, or must not, match right after the match, without consuming it.
+For instance `r"[0-9]+\." not_followed_by(r"\.")` matches the float `1.`, but not the `1.` in the range `1..2`.
+
+Trailing context can be given with `/`, like in flex. In `"a+" / "ab"` the whole pattern must match, but only the part before `/` is consumed.
+Unlike `followed_by`, the two parts are matched together, so `"a+" / "ab"` matches `aa` in `aaab`.
+
+As patterns are matched against the remaining input only, anchors like `(?m)^` or `\b` in a regex can not see the text before the match. Use `bol` and `eol` instead.
+
+The regexes are compiled the first time a rule is tried, and kept in a cache shared by all lexers, the `regex_cache` module,
+so rules and lexers with the same patterns compile them only once.
+Compiling lazily makes the first use of a lexer slower. Servers can compile all regexes of a lexer at startup with `lex("").warm_up()?`,
+which also reports invalid regexes then, instead of panicking when their rule is first tried.
+Servers lexing many small inputs can keep a [`LexSession`](crate::LexSession), which compiles them when created, and then reuses one lexer and token buffer
+for all inputs, like `session.lex(snippet)?`, without allocating for each of them.
+Patterns of a common shape, made of ASCII classes where only the last is repeated, like `ws`, `ident` or `"[0-9]+"`,
+are matched by byte scanners from the `scan` module instead of the regex engine, testing eight bytes at a time where they can.
 
 Here is an example showing the different legal patterns
 ```
@@ -97,13 +129,23 @@ Only the first argument is required, the rest are optional. They can all be igno
 This means that if no arguments are needed, the signature can be written as `|_|`.\
 For instance if only the location is of interest, the other arguments can be ignored with an underscore: `|_, _, loc|`.
 
+The buffer and location can also be bound by name after the matched string, in any order: `|_, @loc loc|` or `|s, @buf buf, @loc loc|`.\
+The `@rule` binder gives the `RuleId` of the rule, with its index and its pattern as written.\
+With the `unicode-normalization` feature, `@nfc text` and `@nfkc text` give the matched string normalized to NFC or NFKC, so identifiers that look the same compare equal.\
+Naming anything else, or naming a binder twice, is a compile error.
+
 ### Action
 
-The actions themselves can be any expression that returns a token or `continues` or `breaks`.
+The actions themselves can be any expression that returns a token or `continues`, `breaks` or `reject!()`s.
 
 Continue and break works as follows:
 - `continue` - This skips the current token and returns the next token instead.
 - `break` - This stops the lexer and thus the iterator will return None when this is encountered.
+- `reject!()` - This undoes the match, and lets the following rules try to match instead. For instance to check that a number fits in a `u32`.
+
+An action can also produce several tokens, by calling `emit!(token)` for each extra token before returning or using `continue`.
+The emitted tokens are returned in order, before the token returned by the action.
+They get the location of the match, unless another is given with `emit!(token, loc)`.
 
 Notably it is possible to call [sub rules](# Sub Rules) from the action.
 
@@ -144,6 +186,155 @@ let tokens = lex("aa 12 x aa").into_token_vec();
 assert_eq!(tokens, vec![A, A, Num(12)]);
 ```
 
+## Config
+
+Options of a lexer are given in a `config` block among the rules, usually first:
+- `longest_match: true` tries all rules, and uses the one matching the most input, the earliest on ties, like flex.
+  Otherwise the first rule that matches is used. A rejected match is followed by the longest match of the rules after it.
+- `eof_token: TOKEN` emits the token at the end of the input, like an `on_eof` declaration.
+- `positions: false` only tracks byte offsets, not lines and columns, which saves going through each character of the input.
+  The lines and columns of the locations are then 0, and can be found when needed with [`SrcLoc::from_offsets`](crate::SrcLoc::from_offsets).
+  A buffer can be made to only track offsets with [`LexBuf::without_positions`](crate::LexBuf::without_positions).
+- `unicode: false` compiles the regexes of the rules without Unicode, so classes like `\w` and `\d` only match ASCII, which is faster.
+  Unicode classes like `\p{L}` then need `(?u:...)` in the pattern.
+- `regex_size_limit: BYTES` and `dfa_size_limit: BYTES` set the limits of the regex crate on the size of a compiled regex
+  and the cache of its lazy DFA. The settings are the same on all platforms, so the lexer behaves identically everywhere.
+
+```
+use lexr::lex_rule;
+#[derive(Debug, PartialEq)]
+enum Token {
+    If, Assign, Eq, Ident, Eof
+}
+use Token::*;
+
+lex_rule!{lex -> Token {
+    config { longest_match: true, eof_token: Eof },
+    ws => |_| continue,
+    lit "if" => If,
+    lit "=" => Assign,
+    lit "==" => Eq,
+    "[a-z]+" => |_| Ident,
+}}
+
+// The identifier `iffy` is longer than the keyword, which wins the tie with the identifier `if`
+assert_eq!(lex("if a == iffy").into_token_vec(), vec![If, Ident, Eq, Ident, Eof]);
+```
+
+## Deriving
+
+The rules can also be written on the token enum itself, by deriving [`Lexer`](derive@crate::Lexer), which keeps each pattern next to its variant.
+Each `#[rule(...)]` on a variant is a rule, tried in the order they are declared, with a pattern string or `lit = "..."`.
+A variant with a field parses the match with `FromStr`, or with the function given with `parse_with`, which can return the field,
+or an `Option` or `Result` of it, where `None` and `Err` reject the match. The `#[lex(...)]` on the enum takes patterns to skip with `skip = "..."`,
+and `longest_match`. `Token::lexer(source)` then returns a regular lexer:
+```
+#[derive(lexr::Lexer, Debug, PartialEq)]
+#[lex(skip = r"\s+")]
+enum Token {
+    #[rule(lit = "let")]
+    Let,
+    #[rule("[a-z]+", parse_with = str::to_string)]
+    Ident(String),
+    #[rule("[0-9]+")]
+    Number(u32),
+    #[rule(r"'.'", parse_with = |s: &str| s.chars().nth(1))]
+    Char(char),
+}
+use Token::*;
+
+let tokens = Token::lexer("let x 12 'y'").into_token_vec();
+assert_eq!(tokens, vec![Let, Ident("x".to_string()), Number(12), Char('y')]);
+```
+
+Other derives on the same enum, like [`TokenClass`](crate::token_class::TokenClass), reuse the variants for metadata.
+
+# End of file
+
+The end of the input can be handled in two ways.
+
+The `eof` pattern is a regular rule, and its action runs the first time any lexer on the buffer reaches the end.
+As sub rules share the buffer, a sub rule matching `eof` means that the calling lexer will not see it.
+
+Alternatively, an `on_eof` declaration can be added among the rules. It is not a closure, but just the token to emit.
+The token is emitted exactly once when the lexer reaches the end of the input, even if a sub rule consumed the rest of the input.
+After that, the lexer is finished and will only return `None`. If omitted, no token is emitted at the end.
+
+`on_eof` is not run if the lexer is stopped with `break`.
+
+```
+use lexr::lex_rule;
+#[derive(Debug, PartialEq)]
+enum Token {
+    A, Eof
+}
+use Token::*;
+
+lex_rule!{lex -> Token {
+    "a" => |_| A,
+    "#" => |_, buf| { comment(buf).deplete(); continue },
+    on_eof => Eof,
+}}
+
+lex_rule!{comment -> () {
+    eof => |_| break,
+    _ => |_| continue,
+}}
+
+let mut lexer = lex("aa# comment");
+assert_eq!(lexer.next_token(), Some(A));
+assert_eq!(lexer.next_token(), Some(A));
+assert_eq!(lexer.next_token(), Some(Eof));
+assert_eq!(lexer.next_token(), None);
+```
+
+The declaration can also be a closure taking the location of the end of the input, `on_eof => |loc| ...`.\
+Each sub rule has its own `on_eof`, and the arguments of the lexer are available in it.
+This can be used to report, for instance, an unterminated string spanning from the opening delimiter:
+
+```
+use lexr::{lex_rule, SrcLoc};
+#[derive(Debug, PartialEq)]
+enum Token {
+    Str(SrcLoc), Unterminated(SrcLoc)
+}
+use Token::*;
+
+lex_rule!{lex -> Token {
+    "\"" => |_, buf, loc| string(buf, loc).next_token().unwrap(),
+}}
+
+lex_rule!{string(start: SrcLoc) -> Token {
+    "\"" => |_, _, loc| Str(start.combine(loc)),
+    _ => |_| continue,
+    on_eof => |loc| Unterminated(start.combine(loc)),
+}}
+
+let tokens = lex("\"abc\"\"de").into_token_vec();
+assert_eq!(tokens, vec![
+    Str(SrcLoc::new((1, 1), (1, 5), (0, 5))),
+    Unterminated(SrcLoc::new((1, 6), (1, 9), (5, 8))),
+]);
+```
+
+# Iteration
+
+Lexers are fused iterators, and behave as follows:
+- After the end of the input, including any `eof` rule and `on_eof` token, `next` returns `None`.
+- After an error, `next` returns `None`. The error itself is returned by `try_next`, or raised as a panic by `next`.
+- After an action uses `break`, the tokens it emitted are returned, and then `None`.
+- If an action panics, the match is still consumed, so if the panic is caught, the lexer continues after the match.
+- Tokens pushed back with `push_back` are returned even after the lexer has finished.
+- If a rule matches the empty input again at the same position, as its action continued or returned a token without consuming anything,
+  so the same rule would match forever, the lexer returns `LexError::Stuck` instead.
+  It has a `Repro` with the lexer, a fingerprint of its rules, the rule, the location and the input there, for bug reports.
+
+`is_finished` tells whether the lexer will only return `None` from now on.
+
+To parse arbitrarily long inputs in bounded memory, `lexer.window(history)` gives a [`TokenWindow`](crate::TokenWindow)
+that retains only the last token read and the `history` tokens before it. A parser can rewind to a position it has read to backtrack,
+and get the retained tokens with `history()` for the context of an error, while rewinding further back fails with a `WindowError`.
+
 # Args
 
 The arguments are passed to the lexer function, and can be used to pass arguments to a lexer.
@@ -212,14 +403,286 @@ lex_rule!{comment(depth: u16) -> () {
 let tokens = lex("a (* comment (* inner *) comment *) aa").into_token_vec();
 assert_eq!(tokens, vec![A, A, A, Eof]);
 ```
+
+A sub language can also be embedded with its own tokens, like a script in HTML, with `lexer.embed(is_trigger, inner)`.
+After each trigger token, the lexer returned by `inner` lexes from the shared buffer until it ends, and then the outer lexer continues:
+```
+use lexr::lex_rule;
+#[derive(Debug, PartialEq)]
+enum Html {
+    Text, Script, Js(Js)
+}
+
+#[derive(Debug, PartialEq)]
+enum Js {
+    Ident, Semi
+}
+
+lex_rule!{html -> Html {
+    ws => |_| continue,
+    "<script>" => |_| Html::Script,
+    "[^<]+" => |_| Html::Text,
+}}
+
+lex_rule!{js -> Js {
+    ws => |_| continue,
+    "</script>" => |_| break,
+    ident => |_| Js::Ident,
+    ";" => |_| Js::Semi,
+}}
+
+let tokens: Vec<_> = html("hi<script>x;</script>bye")
+    .embed(|token| *token == Html::Script, |buf| js(buf).map(|(token, loc)| (Html::Js(token), loc)))
+    .collect();
+assert_eq!(tokens.iter().map(|(token, _)| token).collect::<Vec<_>>(),
+    vec![&Html::Text, &Html::Script, &Html::Js(Js::Ident), &Html::Js(Js::Semi), &Html::Text]);
+assert_eq!(tokens[4].1.get_abs_loc(), (21, 24));
+```
+
+# Cancellation and progress
+
+Lexing can be cancelled from another thread by giving the buffer a [`CancelToken`](crate::CancelToken), like `lex(LexBuf::new(source).with_cancel(token))`.
+The token is checked before each match, also in sub rules, and once it is cancelled `try_next` returns `LexError::Cancelled`, while iterating the lexer just stops.
+
+To bound the work spent on untrusted input, like in a web service, the buffer can be given a [`Budget`](crate::Budget) limiting the number of matches, rejects and the time spent:
+`lex(LexBuf::new(source).with_budget(Budget::new().max_matches(100_000).timeout(Duration::from_millis(50))))`.
+Once a limit is exceeded, `try_next` returns `LexError::BudgetExceeded`.
+
+Similarly, `LexBuf::new(source).with_progress(interval, |done, total| ...)` reports how many bytes have been lexed, for showing progress on large inputs.
+
+With the `mmap` feature, large files can be lexed without reading them into a `String`, by mapping them into memory with a [`MappedSource`](crate::MappedSource):
+`let mapped = MappedSource::open(path)?;` and then `lex(&mapped)`. The file is unmapped when the `MappedSource` is dropped.
+The file is checked to be UTF-8 a megabyte at a time as it is lexed, and `try_next` returns `LexError::InvalidUtf8` where it is not.
+
+Text that is not stored as a single string, like the rope of an editor, can implement [`TextSource`](crate::TextSource) and be lexed a window at a time by a [`ChunkedLexer`](crate::ChunkedLexer).
+With the `ropey` feature, this is implemented for `ropey::Rope`.
+
+Lexers borrow their source. To return a lexer from the function that read the source, an [`OwnedLexer`](crate::OwnedLexer) can own it: `OwnedLexer::new(source, |buf| lex(buf).boxed())`.
+
+In a REPL, each entry can be lexed by the same lexer with `lexer.resume(entry)` once it has reached the end of the previous one.
+The lexer keeps its arguments, and locations continue from the earlier entries, so errors point at the line in the whole session.
+To point at the line in the entry instead, like `input 3, line 2, column 4`, each entry can also be added to a [`Session`](crate::Session), which locates the entry of a location.
+
+# Span-only tokens
+
+Tokens do not have to own their text. A token can be just its kind, with the text taken from the source on demand with `loc.text(source)`,
+or parsed with `loc.parse(source)`. This avoids allocating for each token, and `lexer.with_text(source)` gives the text along with each token:
+```
+use lexr::lex_rule;
+#[derive(Debug, PartialEq)]
+enum Kind {
+    Num, Ident
+}
+
+lex_rule!{lex -> Kind {
+    ws => |_| continue,
+    "[0-9]+" => |_| Kind::Num,
+    ident => |_| Kind::Ident,
+}}
+
+let source = "x 42";
+let (kind, loc) = lex(source).last().unwrap();
+assert_eq!((kind, loc.parse::<u32>(source)), (Kind::Num, Ok(42)));
+assert_eq!(lex(source).with_text(source).map(|(_, _, text)| text).collect::<Vec<_>>(), vec!["x", "42"]);
+```
+Parsers can then keep the source next to the tokens, and read the text of a token only where it is needed, like for identifiers and literals.
+
+A formatter needs the trivia, like whitespace and comments, that a parser would rather skip. To serve both from one pass,
+the trivia rules can return tokens, and `lexer.streams(is_trivia)` gives `TokenStreams` with `raw()` yielding all tokens,
+and `significant()` yielding only those that are not trivia. `trivia_before(i)` gives the trivia before the `i`th significant token.
+
+For languages with an offside rule, like Haskell and Elm, [`Layout`](crate::Layout) turns the indentation into virtual braces and
+semicolons between the lexer and the parser. `Layout::new(open, separator, close, is_keyword)` opens a block at the column of the
+token after each layout keyword, and `layout.apply(lexer)` inserts the virtual tokens with zero-width locations.
+
+Preprocessor-style conditional regions, like `#ifdef`/`#else`/`#endif`, can be evaluated at the token level with
+[`Conditionals`](crate::Conditionals), from a classifier marking the directive tokens and a predicate evaluating their conditions.
+`conditionals.apply(lexer)` gives the tokens of the taken branches along with the disabled regions, for editors to gray out.
+
+Linters and test annotations can read directives like `// lexr:ignore-next-line` from the trivia with a
+[`DirectiveScanner`](crate::DirectiveScanner). `DirectiveScanner::new("lexr:").scan(source, trivia)` parses each directive
+into its name and `key=value` arguments, with the location of each.
+
+# Testing
+
+[`lex_tests!`](crate::lex_tests!) defines a unit test with a regression suite for the rules of a lexer, next to its definition.
+Each case gives an input and the tokens it should produce, optionally with their locations, or the location where lexing should fail, optionally after tokens.
+Cases with `not` give tokens the input should not produce, to lock in which of two overlapping rules wins:
+```ignore
+lex_tests!{lexer = lex;
+    "1+2" => [Num(1), Plus, Num(2)];
+    "10 + 2" => spans [(Num(10), "1:1-2"), (Plus, "1:4"), (Num(2), "1:6")];
+    "1 - 2" => error "1:3";
+    "1 + -" => [Num(1), Plus] then error "1:5";
+    "12" => not [Num(1), Num(2)];
+}
+```
+
+Actions that emit tokens with their own locations, or move the buffer, can be checked with `lexer.validated(source)`.
+In debug builds, it panics if a location is outside the source, overlaps the previous token, or has lines and columns not matching its byte offsets.
+
+# Interop
+
+Lexers can be used with other parsing libraries through the adapters in the [`interop`](crate::interop) module.
+Adapters that depend on a library are enabled by a feature with the name of the library:
+- `chumsky` - [`interop::chumsky::input`](crate::interop::chumsky::input) turns a lexer into a chumsky input spanned by `SrcLoc`.
+- `nom` - [`interop::nom::Tokens`](crate::interop::nom::Tokens) is a nom input over lexed tokens.
+- `tree-sitter` - [`interop::tree_sitter`](crate::interop::tree_sitter) exports a lexer as the external scanner of a tree-sitter grammar. This needs no dependency.
+- `proc-macro2` - [`interop::proc_macro2::LiteralSource`](crate::interop::proc_macro2::LiteralSource) lexes a string literal in a procedural macro, and maps the tokens to spans in the literal.
+
+The [`interop::lalrpop::LalrpopLexer`](crate::interop::lalrpop::LalrpopLexer) adapter needs no feature, and makes a lexer usable as an external lexer for LALRPOP.
+
+Token definitions written for logos can be reused with the [`compat::Logos`](crate::compat::Logos) derive, which generates a lexer from the `#[token]`, `#[regex]` and `#[logos(skip)]` attributes.
+In most cases, migrating only requires replacing `use logos::Logos` with `use lexr::compat::Logos`.
+
+# Diagnostics
+
+Errors and warnings can be reported as a [`Diagnostic`](crate::Diagnostic), with a severity, a code like `L0001` and a location,
+into a [`DiagnosticSink`](crate::DiagnosticSink) chosen by the application. Sinks can collect them in a `Vec<Diagnostic>`,
+print them with `PrintSink`, or forward them with a closure. `lexer.into_vec_reporting(&mut sink)` reports the lexing error instead of panicking,
+and a `LexError` or `SecurityWarning` converts into a diagnostic with `into()`.
+Locations display like `1:3-5`, and `loc.display()` has options for other formats, like `loc.display().path("src/main.rs").start_only()` for `src/main.rs:1:3`.
+Lines and columns count from 1, while accessors like `loc.line0()` and `loc.start0()` count from 0, like the language server protocol.
+`loc.display().zero_based()` displays a location counting from 0. An application serving several consumers can keep a `Base` in its configuration,
+and pass it to `loc.display().base(base)` and `loc.start_in(base)`, and `lex_tests!` takes one with `base = Zero;` for the locations of its cases.
+`loc.display_snippet(source, tab_width)` shows the lines covered by a location with the covered part underlined by carets,
+lined up with the text despite tabs and full-width characters, for showing any error in a terminal.
+Built-in diagnostics have stable codes, listed with explanations in the `CATALOG`, and found by code with `diagnostic::lookup`.
+A `FilterSink` wrapping another sink can `suppress` diagnostics or `remap` their severity by code.
+A diagnostic can carry `suggestions`, fixes made of edits to the source that editors can offer as quick-fixes,
+added with `with_suggestion(message, loc, replacement)` and applied with `suggestion.apply(source)`.
+Simple style checks run while lexing, with [`Lints`](crate::Lints) given to the buffer, like
+`LexBuf::new(source).with_lints(Lints::new().trailing_whitespace(Severity::Warning), sink)`.
+They report trailing whitespace, mixed indentation and long lines, each with its own severity, once the lexer has consumed the line.
+
+# Editing
+
+Tools like refactorings can collect edits at the locations of tokens in an `EditBuilder`, with `replace`, `delete`, `insert_before` and `insert_after`.
+`edits.apply(source)` applies them all at once, as the locations refer to the original source, or returns the edits that overlap.
+
+After an edit, the source is lexed again. To keep caches keyed by token, `StableTokens` gives the tokens `TokenId`s,
+and `tokens.update(source, new_tokens)` keeps the ids of the tokens before and after the edit that have the same text.
+
+Caches can store locations with `loc.to_bytes()`, which are the same on all platforms. When a file is read again,
+a `FileStamp` tells cheaply if it is unchanged. Otherwise, a [`SpanStamp`](crate::SpanStamp) of each location, with a hash of the text it covered,
+tells which are stale with `span_stamp::stale(stamps, source)`, and `stamp.relocate(source, window)` finds text that has moved a little.
+
+# Batches
+
+`batch::process(paths, lexer_fn, parser_fn)` reads, lexes and parses many files on a pool of threads, like a compiler driver.
+It returns the result of each file, in the order of the paths, along with statistics like the number of failed files and tokens.
+
+# Security
+
+The `security` module checks for source code that displays differently from how it is lexed, like in the Trojan Source attacks.
+`security::check(source, &tokens, is_ident)` finds bidirectional control characters anywhere in the source,
+and identifiers that mix scripts or look like other identifiers, like `admin` with a Cyrillic `а`. Each warning has the location of the offending code.
+Lookalikes are only found for a handful of Cyrillic and Greek letters, so the check is a heuristic, not a full confusables check.
+
+# Introspection
+
+The rules of a lexer are described by a [`LexerSpec`](crate::LexerSpec), obtained with `lexer.spec()`, or by the name of the lexer with `lexer_spec!(lex)`.
+It lists the pattern as written, regex, anchors and action of each rule, along with the config, for tools that work with lexers in general.
+For instance, `to_textmate_json` generates an approximate TextMate grammar for syntax highlighting, given the scopes of the tokens.
+`to_dot` draws the order the rules are tried in, and which rules shadow others, as a Graphviz graph.
+With the `debugger` feature, [`debugger::repl`](crate::debugger::repl) reads lines of input and shows which rule matches each token,
+and rules can be turned off and on between lines with `:toggle N`, to tune the rules without rebuilding the lexer.
+`spec.unreachable_rules()` finds rules that can never match because an earlier rule always matches first,
+like a keyword after an identifier rule, and `spec.warnings()` describes them.
+`lexer_fingerprint!(lex, comment)` hashes the specs of the lexers of a language, the same in any build and with any compiler,
+so caches and serialized token streams can tell when the language has changed.
+
+Doc comments can be written on the lexer, before its name, and on its rules, before their patterns, as `/// An identifier`.
+They are kept as `spec.doc` and the `doc` of each rule, and a lexer with doc comments has its function documented with them,
+followed by a list of its rules with their patterns and doc comments, so the documented lexical spec of a language is generated from the lexer itself.
+`spec.to_markdown()` writes the same list, for documentation kept outside of rustdoc. The doc comments of variants deriving `Lexer` document their rules.
+
+`lexer.with_rules()` iterates over the tokens along with the `RuleId` of the rule that produced each of them,
+so post-processing like highlighting can depend on the rule without encoding it in the tokens.
+The id indexes the rules of the spec, with `spec.rule(id)`.
+
+Tokens are moved and stored often, so a large variant makes every token large. Deriving `shape::TokenShape` for the token enum
+lets `shape::analyze::<Token>(threshold)` report the size of the enum and the variants with larger fields,
+and `#[token_shape(max_size = 16)]` fails compilation if the enum grows larger than that.
+
+Editor features like brace matching and auto-indent need to know little about the tokens. Deriving `token_class::TokenClass`
+with `#[class(open = "paren")]`, `#[class(close = "paren")]`, `#[class(terminator)]`, `#[class(comment)]`, `#[class(string)]` or `#[class(import)]`
+on the variants gives `token.class()`, and `token_class::match_braces` and `token_class::depths` pair the braces
+and give the nesting depth of each token from that alone. `token_class::folding_ranges` gives the delimiters, comments
+and import blocks spanning several lines, ready to serve as the folding ranges of a language server.
+
+For performance work, `CorpusStats` lexes a corpus into statistics: the frequency, average and longest length of each kind of token,
+a histogram of token lengths, and the ratio of trivia not covered by any token. Its `Display` writes them as a table.
 */
 
 pub mod lexer;
 pub mod lex_rule;
 pub mod lex_buf;
 pub mod src_loc;
-
-pub use lexer::Lexer;
+pub mod lex_error;
+pub mod lexer_spec;
+pub mod cancel_token;
+pub mod budget;
+#[cfg(feature = "mmap")]
+pub mod mapped_source;
+pub mod text_source;
+pub mod source;
+pub mod owned_lexer;
+pub mod interop;
+pub mod compat;
+pub mod classes;
+pub mod security;
+pub mod edit;
+pub mod stable_tokens;
+pub mod batch;
+pub mod diagnostic;
+pub mod lints;
+pub mod regex_cache;
+pub mod scan;
+pub mod matcher;
+pub mod shape;
+pub mod token_class;
+pub mod conformance;
+pub mod corpus;
+pub mod streams;
+pub mod session;
+pub mod lex_session;
+pub mod layout;
+pub mod conditional;
+pub mod comment_directive;
+pub mod window;
+pub mod span_stamp;
+#[cfg(feature = "debugger")]
+pub mod debugger;
+mod validate;
+#[cfg(feature = "unicode-normalization")]
+pub mod normalize;
+
+pub use lexer::{Lexer, RuleLexer, Identity, RuledToken};
+pub use lexr_derive::Lexer;
 pub use lex_rule::*;
-pub use lex_buf::LexBuf;
-pub use src_loc::SrcLoc;
\ No newline at end of file
+pub use lex_buf::{LexBuf, Checkpoint};
+pub use src_loc::{SrcLoc, LocDisplay, Base};
+pub use lex_error::{LexError, Repro};
+pub use lexer_spec::{LexerSpec, RuleSpec, RuleId, UnreachableRule};
+pub use matcher::Matcher;
+pub use cancel_token::CancelToken;
+pub use budget::Budget;
+#[cfg(feature = "mmap")]
+pub use mapped_source::MappedSource;
+pub use text_source::{TextSource, ChunkedLexer};
+pub use source::Source;
+pub use owned_lexer::{OwnedLexer, BoxedLexer};
+pub use edit::{TextEdit, EditBuilder, EditConflict};
+pub use stable_tokens::{StableTokens, TokenId};
+pub use lints::Lints;
+pub use corpus::CorpusStats;
+pub use streams::TokenStreams;
+pub use session::Session;
+pub use lex_session::{LexSession, Interner, Symbol};
+pub use layout::Layout;
+pub use conditional::{Conditionals, Directive, Spliced, ConditionalError};
+pub use comment_directive::{DirectiveScanner, CommentDirective, DirectiveArg};
+pub use window::{TokenWindow, WindowError};
+pub use span_stamp::{SpanStamp, FileStamp};
+pub use diagnostic::{Diagnostic, Suggestion, DiagnosticSink, PrintSink, FilterSink, Severity, DiagnosticInfo, CATALOG, codes};
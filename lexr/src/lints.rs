@@ -0,0 +1,157 @@
+use crate::{codes, Diagnostic, DiagnosticSink, Severity, SrcLoc};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Style checks of the lines of the source, run while lexing, so they need no separate pass over the text.
+///
+/// Each check is off unless given a severity. The lints are given to the buffer with [`LexBuf::with_lints`](crate::LexBuf::with_lints),
+/// and each line is checked once the lexer has consumed it:
+///
+/// ```
+/// use lexr::{lex_rule, Diagnostic, LexBuf, Lints, Severity};
+///
+/// lex_rule!{lex -> () {
+///     ws => |_| continue,
+///     "[a-z]+" => |_| (),
+/// }}
+///
+/// let mut diagnostics = Vec::new();
+/// let lints = Lints::new().trailing_whitespace(Severity::Warning).max_line_length(8, Severity::Note);
+/// lex(LexBuf::new("short \nmuch too long").with_lints(lints, |d: Diagnostic| diagnostics.push(d))).deplete();
+///
+/// assert_eq!(diagnostics[0].to_string(), "warning[L0101]: Trailing whitespace at 1:6");
+/// assert_eq!(diagnostics[1].to_string(), "note[L0103]: Line is longer than 8 characters at 2:9-13");
+/// ```
+pub struct Lints {
+    trailing_whitespace: Option<Severity>,
+    mixed_indentation: Option<Severity>,
+    max_line_length: Option<(usize, Severity)>,
+}
+
+impl Lints {
+    /// Creates lints with all checks off.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports spaces and tabs at the end of lines.
+    pub fn trailing_whitespace(mut self, severity: Severity) -> Self {
+        self.trailing_whitespace = Some(severity);
+        self
+    }
+
+    /// Reports indentation with both tabs and spaces, or with other characters than the first indented line.
+    pub fn mixed_indentation(mut self, severity: Severity) -> Self {
+        self.mixed_indentation = Some(severity);
+        self
+    }
+
+    /// Reports lines with more characters than the maximum.
+    pub fn max_line_length(mut self, max: usize, severity: Severity) -> Self {
+        self.max_line_length = Some((max, severity));
+        self
+    }
+}
+
+/// The state of the lints while lexing, kept by the buffer.
+pub(crate) struct LintState<'a> {
+    lints: Lints,
+    sink: Box<dyn DiagnosticSink + 'a>,
+    /// The byte index up to which the source has been checked, so text consumed again after a rewind is not checked twice.
+    done: usize,
+    /// Whether the first indented line used tabs, once seen.
+    indent_tabs: Option<bool>,
+    line: LineState,
+}
+
+#[derive(Default)]
+struct LineState {
+    in_indent: bool,
+    tabs: bool,
+    spaces: bool,
+    /// The byte index of the first character.
+    start: Option<usize>,
+    /// The position of the last indentation character, and the index after it.
+    indent_end: Option<((usize, usize), usize)>,
+    /// The position and index of the first whitespace character of the current run, if the line ends with whitespace so far.
+    trailing: Option<((usize, usize), usize)>,
+    /// The position and index of the first character beyond the maximum line length.
+    too_long: Option<((usize, usize), usize)>,
+    /// The position and index of the last character.
+    last: Option<((usize, usize), usize)>,
+}
+
+impl<'a> LintState<'a> {
+    pub(crate) fn new(lints: Lints, sink: impl DiagnosticSink + 'a, done: usize) -> Self {
+        Self { lints, sink: Box::new(sink), done, indent_tabs: None, line: LineState { in_indent: true, ..Default::default() } }
+    }
+
+    /// Checks the character at the position and byte index, unless it has already been checked.
+    pub(crate) fn check(&mut self, c: char, pos: (usize, usize), idx: usize) {
+        if idx < self.done {
+            return
+        }
+        self.done = idx + c.len_utf8();
+
+        self.line.start.get_or_insert(idx);
+        match c {
+            '\n' => self.end_line(),
+            // Part of the line break
+            '\r' => {},
+            ' ' | '\t' => {
+                if self.line.in_indent {
+                    self.line.tabs |= c == '\t';
+                    self.line.spaces |= c == ' ';
+                    self.line.indent_end = Some((pos, idx + 1));
+                }
+                self.line.trailing.get_or_insert((pos, idx));
+            },
+            _ => {
+                self.line.in_indent = false;
+                self.line.trailing = None;
+            },
+        }
+        if c != '\n' && c != '\r' {
+            if self.lints.max_line_length.is_some_and(|(max, _)| pos.1 == max + 1) {
+                self.line.too_long = Some((pos, idx));
+            }
+            self.line.last = Some((pos, idx + c.len_utf8()));
+        }
+    }
+
+    /// Checks the last line, at the end of the input.
+    pub(crate) fn finish(&mut self, idx: usize) {
+        if idx >= self.done && self.line.last.is_some() {
+            self.done = idx + 1;
+            self.end_line();
+        }
+    }
+
+    fn end_line(&mut self) {
+        let line = std::mem::replace(&mut self.line, LineState { in_indent: true, ..Default::default() });
+        let Some((last, end_idx)) = line.last else { return };
+
+        if let (Some(severity), Some((start, start_idx))) = (self.lints.trailing_whitespace, line.trailing) {
+            let loc = SrcLoc::new(start, last, (start_idx, end_idx));
+            self.sink.report(Diagnostic::new(severity, codes::TRAILING_WHITESPACE, "Trailing whitespace", loc).with_suggestion("remove it", loc, ""));
+        }
+
+        if line.tabs || line.spaces {
+            let indent_tabs = *self.indent_tabs.get_or_insert(line.tabs);
+            if let Some(severity) = self.lints.mixed_indentation {
+                if line.tabs && line.spaces || line.tabs != indent_tabs {
+                    let (indent_last, indent_end) = line.indent_end.unwrap();
+                    let loc = SrcLoc::new((indent_last.0, 1), indent_last, (line.start.unwrap(), indent_end));
+                    let message = if line.tabs && line.spaces { "Indentation mixes tabs and spaces" }
+                        else if line.tabs { "Indentation uses tabs, but earlier lines use spaces" }
+                        else { "Indentation uses spaces, but earlier lines use tabs" };
+                    self.sink.report(Diagnostic::new(severity, codes::MIXED_INDENTATION, message, loc));
+                }
+            }
+        }
+
+        if let (Some((max, severity)), Some((start, start_idx))) = (self.lints.max_line_length, line.too_long) {
+            let loc = SrcLoc::new(start, last, (start_idx, end_idx));
+            self.sink.report(Diagnostic::new(severity, codes::LONG_LINE, format!("Line is longer than {} characters", max), loc));
+        }
+    }
+}
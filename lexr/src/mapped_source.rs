@@ -0,0 +1,38 @@
+use std::{fs::File, io, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{LexBuf, Source};
+
+/// A file mapped into memory, so it can be lexed without reading it into a `String`.
+///
+/// The buffer borrows the mapped file, so it is lexed as a [`Source`] with `lex(&mapped)`,
+/// and the file is unmapped when this is dropped.
+///
+/// The file is not read when it is opened. It is checked to be UTF-8 a chunk at a time as it is lexed, like [`LexBuf::from_utf8_lazy`].
+pub struct MappedSource {
+    map: Mmap,
+}
+
+impl MappedSource {
+    /// Maps the file into memory.
+    ///
+    /// The file must not be modified while it is mapped, as the contents would change under the lexer.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: Modifying the file while it is mapped is documented as not allowed
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self { map })
+    }
+
+    /// The contents of the file, which may not be valid UTF-8.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl<'a> Source<'a> for &'a MappedSource {
+    fn into_buf(self) -> LexBuf<'a> {
+        LexBuf::from_utf8_lazy(self.as_bytes())
+    }
+}
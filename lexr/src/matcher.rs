@@ -0,0 +1,53 @@
+//! Custom matchers for patterns that are better matched by other engines or by hand than by a regex.
+//!
+//! A pattern can be `matcher` followed by an expression implementing [`Matcher`], which is created the first time the rule is tried.
+//! Functions and closures from the input to the length of the match are matchers, so specialized scanners can be written by hand:
+//!
+//! ```
+//! use lexr::lex_rule;
+//!
+//! // Digits with `_` separators, which must not end the number
+//! fn number(input: &str) -> Option<usize> {
+//!     let len = input.bytes().take_while(|b| b.is_ascii_digit() || *b == b'_').count();
+//!     let len = input[..len].trim_end_matches('_').len();
+//!     (len > 0 && !input.starts_with('_')).then_some(len)
+//! }
+//!
+//! lex_rule!{lex -> u64 {
+//!     ws => |_| continue,
+//!     matcher lexr::regex::Regex::new("^0x[0-9a-f]+").unwrap() => |s| u64::from_str_radix(&s[2..], 16).unwrap(),
+//!     matcher number => |s| s.replace('_', "").parse().unwrap(),
+//! }}
+//!
+//! assert_eq!(lex("1_000 0x1f").into_token_vec(), vec![1000, 31]);
+//! ```
+//!
+//! A rule with a matcher has no regex in its [`RuleSpec`](crate::RuleSpec), so it is not part of the analyses of the [`LexerSpec`](crate::LexerSpec).
+
+/// Matches a pattern at the start of the input.
+pub trait Matcher {
+    /// The length in bytes of the match at the start of the input, if any.
+    fn find_at_start(&self, input: &str) -> Option<usize>;
+}
+
+impl<F: Fn(&str) -> Option<usize>> Matcher for F {
+    fn find_at_start(&self, input: &str) -> Option<usize> {
+        self(input)
+    }
+}
+
+/// The regex should be anchored with `^`, or else it searches the whole input when it does not match at the start.
+impl Matcher for regex::Regex {
+    fn find_at_start(&self, input: &str) -> Option<usize> {
+        self.find(input).filter(|mat| mat.start() == 0).map(|mat| mat.end())
+    }
+}
+
+/// The regex is anchored when searching, so it needs no `^`.
+#[cfg(feature = "regex-automata")]
+impl Matcher for regex_automata::meta::Regex {
+    fn find_at_start(&self, input: &str) -> Option<usize> {
+        let input = regex_automata::Input::new(input).anchored(regex_automata::Anchored::Yes);
+        self.search(&input).map(|mat| mat.end())
+    }
+}
@@ -0,0 +1,38 @@
+//! Unicode normalization of matched text, with the `unicode-normalization` feature.
+//!
+//! The `@nfc` and `@nfkc` binders of an action bind the matched text in these forms,
+//! so identifiers that look the same, like `é` as one or as two code points, compare equal:
+//!
+//! ```
+//! use lexr::lex_rule;
+//!
+//! lex_rule!{lex -> String {
+//!     unicode_ident => |_, @nfc ident| ident.into_owned(),
+//!     " " => |_| continue,
+//! }}
+//!
+//! let tokens = lex("caf\u{e9} cafe\u{301}").into_token_vec();
+//! assert_eq!(tokens[0], tokens[1]);
+//! ```
+//!
+//! The text is only copied if it is not already normalized.
+
+use std::borrow::Cow;
+
+use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+/// The text in Normalization Form C, which composes characters without changing their meaning.
+pub fn nfc(text: &str) -> Cow<'_, str> {
+    match is_nfc_quick(text.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(text),
+        _ => Cow::Owned(text.nfc().collect()),
+    }
+}
+
+/// The text in Normalization Form KC, which also replaces compatibility characters, like `ﬁ` with `fi`.
+pub fn nfkc(text: &str) -> Cow<'_, str> {
+    match is_nfkc_quick(text.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(text),
+        _ => Cow::Owned(text.nfkc().collect()),
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{LexBuf, LexError, Lexer, RuleLexer, SrcLoc};
+
+/// A [`Lexer`](struct@crate::Lexer) with the type of the underlying lexer erased, as returned by [`Lexer::boxed`].
+pub type BoxedLexer<'src, T> = Lexer<T, Box<dyn RuleLexer<'src, Item = (T, SrcLoc)> + 'src>>;
+
+/// A lexer that owns its source, so it can be returned from the function that read the source.
+/// 
+/// The lexer is created by a function given a buffer over the source, which returns a boxed lexer:
+/// ```
+/// use lexr::{lex_rule, OwnedLexer};
+/// #[derive(Debug, PartialEq)]
+/// enum Token { A, B }
+/// 
+/// lex_rule!{lex -> Token {
+///     "a" => |_| Token::A,
+///     "b" => |_| Token::B,
+/// }}
+/// 
+/// fn read() -> OwnedLexer<Token> {
+///     let source = String::from("ab");
+///     OwnedLexer::new(source, |buf| lex(buf).boxed())
+/// }
+/// 
+/// let tokens: Vec<_> = read().map(|(token, _)| token).collect();
+/// assert_eq!(tokens, vec![Token::A, Token::B]);
+/// ```
+/// 
+/// The tokens can not borrow the source. Instead, the text of a token can be looked up from its location with [`OwnedLexer::slice`].
+pub struct OwnedLexer<T> {
+    // Declared before the source, so it is dropped first
+    lexer: BoxedLexer<'static, T>,
+    source: String,
+}
+
+impl<T> OwnedLexer<T> {
+    /// Creates a lexer owning the source, using the function to create the lexer over a buffer of it.
+    pub fn new(source: impl Into<String>, lex: impl for<'a> FnOnce(LexBuf<'a>) -> BoxedLexer<'a, T>) -> Self {
+        let source = source.into();
+        let lexer = lex(LexBuf::from(source.as_str()));
+        // Safety: The source is neither modified nor dropped before the lexer, and moving the string does not move its contents.
+        // As the function is generic over the lifetime of the buffer, neither it nor the tokens can have kept references to the source.
+        let lexer = unsafe { std::mem::transmute::<BoxedLexer<'_, T>, BoxedLexer<'static, T>>(lexer) };
+        Self { lexer, source }
+    }
+
+    /// The source being lexed.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The text of the source at the location.
+    pub fn slice(&self, loc: SrcLoc) -> &str {
+        let (start, end) = loc.get_abs_loc();
+        &self.source[start..end]
+    }
+
+    /// Gets the next token and location, or the error if the input could not be lexed.
+    pub fn try_next(&mut self) -> Option<Result<(T, SrcLoc), LexError>> {
+        self.lexer.try_next()
+    }
+
+    /// Whether the lexer is finished, like [`Lexer::is_finished`].
+    pub fn is_finished(&self) -> bool {
+        self.lexer.is_finished()
+    }
+
+    /// Puts a token back into the lexer, like [`Lexer::push_back`].
+    pub fn push_back(&mut self, token: T, loc: SrcLoc) {
+        self.lexer.push_back(token, loc)
+    }
+}
+
+impl<T> Iterator for OwnedLexer<T> {
+    type Item = (T, SrcLoc);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lexer.next()
+    }
+}
+
+impl<T> std::iter::FusedIterator for OwnedLexer<T> {}
@@ -0,0 +1,133 @@
+//! A global cache of compiled regexes, shared by all lexers.
+//!
+//! Each pattern is compiled once, the first time any rule uses it, so rules and lexers with the same patterns,
+//! like lexers for several dialects of a language, share the compiled regexes.
+//!
+//! ```
+//! use lexr::regex_cache;
+//!
+//! let a = regex_cache::get("^(?:[0-9]+)").unwrap();
+//! let b = regex_cache::get("^(?:[0-9]+)").unwrap();
+//! assert_eq!(a.as_str(), b.as_str());
+//! assert!(regex_cache::contains("^(?:[0-9]+)"));
+//! ```
+//!
+//! Regexes compiled with other [`RegexOptions`] are cached separately.
+
+use std::{collections::HashMap, fmt::Display, sync::Mutex};
+
+use lazy_static::lazy_static;
+use regex::{Regex, RegexBuilder};
+
+lazy_static! {
+    static ref CACHE: Mutex<HashMap<(String, RegexOptions), Regex>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The settings regexes are compiled with, set for the rules of a lexer in its `config` block.
+///
+/// By default, Unicode is on, and the limits are those of the regex crate, which are the same on all platforms.
+pub struct RegexOptions {
+    /// Whether classes like `\w`, `\d` and `\s` and case-insensitive matching are Unicode-aware.
+    pub unicode: bool,
+    /// The limit on the size of a compiled regex in bytes, if not the default.
+    pub size_limit: Option<usize>,
+    /// The limit on the size of the cache of the lazy DFA of a regex in bytes, if not the default.
+    pub dfa_size_limit: Option<usize>,
+}
+
+impl RegexOptions {
+    /// The default settings.
+    pub const fn new() -> Self {
+        Self { unicode: true, size_limit: None, dfa_size_limit: None }
+    }
+
+    /// Turns Unicode on or off. Without Unicode, `\w` only matches ASCII, which is faster,
+    /// and Unicode classes like `\p{L}` are errors unless they are turned on with `(?u:...)` in the pattern.
+    pub const fn unicode(mut self, unicode: bool) -> Self {
+        self.unicode = unicode;
+        self
+    }
+
+    /// Sets the limit on the size of a compiled regex in bytes.
+    pub const fn size_limit(mut self, limit: usize) -> Self {
+        self.size_limit = Some(limit);
+        self
+    }
+
+    /// Sets the limit on the size of the cache of the lazy DFA of a regex in bytes.
+    pub const fn dfa_size_limit(mut self, limit: usize) -> Self {
+        self.dfa_size_limit = Some(limit);
+        self
+    }
+
+    /// Whether these are the default settings.
+    pub fn is_default(&self) -> bool {
+        *self == Self::new()
+    }
+
+    /// Compiles the pattern with the settings, without caching it.
+    pub(crate) fn build(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        let mut builder = RegexBuilder::new(pattern);
+        builder.unicode(self.unicode);
+        if let Some(limit) = self.size_limit {
+            builder.size_limit(limit);
+        }
+        if let Some(limit) = self.dfa_size_limit {
+            builder.dfa_size_limit(limit);
+        }
+        builder.build()
+    }
+}
+
+impl Default for RegexOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for RegexOptions {
+    /// Writes the settings that are not the default, like `unicode: false, size_limit: 1000`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut settings = Vec::new();
+        if !self.unicode {
+            settings.push("unicode: false".to_string());
+        }
+        if let Some(limit) = self.size_limit {
+            settings.push(format!("regex_size_limit: {}", limit));
+        }
+        if let Some(limit) = self.dfa_size_limit {
+            settings.push(format!("dfa_size_limit: {}", limit));
+        }
+        write!(f, "{}", settings.join(", "))
+    }
+}
+
+/// The compiled regex of the pattern, compiling it only if it is not cached yet.
+///
+/// The returned regex shares its compiled program with the cached one, so it is cheap to keep.
+pub fn get(pattern: &str) -> Result<Regex, regex::Error> {
+    get_with(pattern, RegexOptions::new())
+}
+
+/// The compiled regex of the pattern with the settings, compiling it only if it is not cached yet.
+pub fn get_with(pattern: &str, options: RegexOptions) -> Result<Regex, regex::Error> {
+    let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let key = (pattern.to_string(), options);
+    if let Some(regex) = cache.get(&key) {
+        return Ok(regex.clone())
+    }
+    let regex = options.build(pattern)?;
+    cache.insert(key, regex.clone());
+    Ok(regex)
+}
+
+/// Whether the pattern has been compiled with the default settings.
+pub fn contains(pattern: &str) -> bool {
+    CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).contains_key(&(pattern.to_string(), RegexOptions::new()))
+}
+
+/// The number of distinct patterns that have been compiled.
+pub fn len() -> usize {
+    CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).len()
+}
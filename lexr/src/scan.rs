@@ -0,0 +1,246 @@
+//! Fast matching of the most common shapes of patterns, without the regex engine.
+//!
+//! Patterns made of ASCII character classes, where only the last class is repeated, like `ws`, `ident`, `"[0-9]+"`
+//! or `"[a-z]" "[a-z0-9_]*"`, are matched by looking up each byte in a table. Other patterns use the regex engine.
+//! A repeated class of up to four ranges, like the rest of an identifier, is tested eight bytes at a time, so long runs are matched quickly.
+//! The matcher is chosen automatically when the rule is first tried.
+//!
+//! `cargo bench --bench scan` compares the scanners with the regex engine.
+
+use regex::Regex;
+
+use crate::regex_cache::{self, RegexOptions};
+
+#[doc(hidden)]
+/// Matches a pattern at the start of the input, with a scanner if the pattern has a known shape, or otherwise a regex.
+pub enum Matcher {
+    Scan(Scanner),
+    Regex(Regex),
+}
+
+#[doc(hidden)]
+/// A sequence of ASCII classes matched once each, where the last may be repeated.
+pub struct Scanner {
+    once: Vec<ByteSet>,
+    repeated: Option<(ByteSet, Repeat)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Repeat {
+    /// `*`
+    Any,
+    /// `+`
+    AtLeastOne,
+}
+
+#[derive(Clone, Copy)]
+struct ByteSet {
+    table: [bool; 128],
+    // The set as a few ranges of bytes, for testing eight bytes at a time
+    ranges: Option<[Range; MAX_RANGES]>,
+}
+
+/// The number of ranges a set can have to be tested a word at a time.
+const MAX_RANGES: usize = 4;
+
+/// The highest bit of each byte of a word.
+const HIGH: u64 = 0x8080808080808080;
+
+/// A range of ASCII bytes, as the words that test it: a byte is in it if the highest bit is set both in
+/// the byte plus `0x80 - lo`, and in `0x80 + hi` minus the byte. As the bytes are ASCII, neither carries into the next byte.
+#[derive(Clone, Copy)]
+struct Range {
+    add: u64,
+    top: u64,
+}
+
+impl Matcher {
+    /// Chooses the matcher for the regex, which is not anchored.
+    pub fn new(regex: &str) -> Result<Self, regex::Error> {
+        Self::with_options(regex, RegexOptions::new())
+    }
+
+    /// Chooses the matcher for the regex, compiling it with the settings if it is not matched by a scanner.
+    /// 
+    /// The scanner only matches ASCII classes, which mean the same with and without Unicode.
+    pub fn with_options(regex: &str, options: RegexOptions) -> Result<Self, regex::Error> {
+        match Scanner::parse(regex) {
+            Some(scanner) => Ok(Matcher::Scan(scanner)),
+            None => regex_cache::get_with(&format!("^(?:{})", regex), options).map(Matcher::Regex),
+        }
+    }
+
+    /// The length of the match at the start of the input, if any.
+    pub fn find(&self, input: &str) -> Option<usize> {
+        match self {
+            Matcher::Scan(scanner) => scanner.find(input.as_bytes()),
+            Matcher::Regex(regex) => regex.find(input).map(|mat| mat.end()),
+        }
+    }
+
+    /// Whether the pattern matches at the start of the input.
+    pub fn is_match(&self, input: &str) -> bool {
+        self.find(input).is_some()
+    }
+
+    /// Whether the pattern is matched without the regex engine.
+    pub fn is_scanner(&self) -> bool {
+        matches!(self, Matcher::Scan(_))
+    }
+}
+
+impl Scanner {
+    /// Parses classes like `[a-z_]`, each optionally in a group `(?:...)`, where the last may be followed by `*` or `+`.
+    fn parse(regex: &str) -> Option<Self> {
+        let mut rest = regex;
+        let mut classes = Vec::new();
+        while !rest.is_empty() {
+            let (group, inner) = match rest.strip_prefix("(?:") {
+                Some(inner) => (true, inner),
+                None => (false, rest),
+            };
+            let (set, after) = ByteSet::parse(inner)?;
+            let (repeat, after) = match after.as_bytes().first() {
+                Some(b'*') => (Some(Repeat::Any), &after[1..]),
+                Some(b'+') => (Some(Repeat::AtLeastOne), &after[1..]),
+                _ => (None, after),
+            };
+            rest = if group { after.strip_prefix(')')? } else { after };
+            classes.push((set, repeat));
+        }
+
+        // Greedy matching is only exact when nothing follows a repeated class
+        let (last, last_repeat) = classes.pop()?;
+        if classes.iter().any(|(_, repeat)| repeat.is_some()) {
+            return None
+        }
+        let mut once: Vec<_> = classes.into_iter().map(|(set, _)| set).collect();
+        let repeated = match last_repeat {
+            Some(repeat) => Some((last, repeat)),
+            None => {
+                once.push(last);
+                None
+            },
+        };
+        Some(Scanner { once, repeated })
+    }
+
+    fn find(&self, input: &[u8]) -> Option<usize> {
+        // The classes only have ASCII bytes, which are never part of a multi-byte character, so the end is a char boundary
+        let mut length = 0;
+        for set in &self.once {
+            if !set.contains(*input.get(length)?) {
+                return None
+            }
+            length += 1;
+        }
+        if let Some((set, repeat)) = &self.repeated {
+            let count = set.prefix_len(&input[length..]);
+            if count == 0 && *repeat == Repeat::AtLeastOne {
+                return None
+            }
+            length += count;
+        }
+        Some(length)
+    }
+}
+
+impl ByteSet {
+    /// Parses a class like `[a-zA-Z_]` or `[ \n\r\t]` at the start of the regex, returning the rest.
+    fn parse(regex: &str) -> Option<(Self, &str)> {
+        let inner = regex.strip_prefix('[')?;
+        let end = inner.find(']')?;
+        let (class, rest) = (&inner[..end], &inner[end + 1..]);
+
+        // Set operations are left to the regex engine
+        if class.contains("--") {
+            return None
+        }
+        // The characters, and whether they were escaped, as an escaped `-` is not a range
+        let mut chars = Vec::new();
+        let mut iter = class.chars();
+        while let Some(c) = iter.next() {
+            chars.push(match c {
+                '\\' => match iter.next()? {
+                    'n' => ('\n', true),
+                    'r' => ('\r', true),
+                    't' => ('\t', true),
+                    c @ ('\\' | '-' | '^' | '.') => (c, true),
+                    _ => return None,
+                },
+                // Negation, nested classes and set operations are left to the regex engine
+                '^' | '[' | '&' | '~' => return None,
+                c if c.is_ascii() => (c, false),
+                _ => return None,
+            });
+        }
+        if chars.is_empty() {
+            return None
+        }
+
+        let mut set = ByteSet { table: [false; 128], ranges: None };
+        let mut i = 0;
+        while i < chars.len() {
+            // A range, unless the `-` is escaped or the last character of the class
+            if i + 2 < chars.len() && chars[i + 1] == ('-', false) {
+                let (from, to) = (chars[i].0, chars[i + 2].0);
+                if from > to {
+                    return None
+                }
+                for c in from..=to {
+                    set.table[c as usize] = true;
+                }
+                i += 3;
+            } else {
+                set.table[chars[i].0 as usize] = true;
+                i += 1;
+            }
+        }
+        set.ranges = set.word_ranges();
+        Some((set, rest))
+    }
+
+    /// The ranges of the set, if it has at most [`MAX_RANGES`]. Unused ranges are empty.
+    fn word_ranges(&self) -> Option<[Range; MAX_RANGES]> {
+        let splat = |byte: u8| u64::from_ne_bytes([byte; 8]);
+        // An empty range, as nothing is added to reach the highest bit, and all is taken away from the top
+        let mut ranges = [Range { add: 0, top: splat(0x80) }; MAX_RANGES];
+        let mut count = 0;
+        let mut byte = 0;
+        while byte < 128 {
+            if !self.table[byte] {
+                byte += 1;
+                continue
+            }
+            let lo = byte;
+            while byte < 128 && self.table[byte] {
+                byte += 1;
+            }
+            *ranges.get_mut(count)? = Range { add: splat((0x80 - lo) as u8), top: splat((0x80 + byte - 1) as u8) };
+            count += 1;
+        }
+        Some(ranges)
+    }
+
+    /// The number of bytes at the start of the input that are in the set.
+    fn prefix_len(&self, input: &[u8]) -> usize {
+        let mut length = 0;
+        if let Some(ranges) = &self.ranges {
+            while let Some(chunk) = input.get(length..length + 8) {
+                let word = u64::from_le_bytes(chunk.try_into().unwrap());
+                let ascii = word & !HIGH;
+                let inside = ranges.iter().fold(0, |inside, range| inside | (ascii + range.add) & (range.top - ascii)) & !word & HIGH;
+                if inside != HIGH {
+                    // The first byte is the lowest, as the word is little-endian
+                    return length + (!inside & HIGH).trailing_zeros() as usize / 8
+                }
+                length += 8;
+            }
+        }
+        length + input[length..].iter().take_while(|&&byte| self.contains(byte)).count()
+    }
+
+    fn contains(&self, byte: u8) -> bool {
+        self.table.get(byte as usize).copied().unwrap_or(false)
+    }
+}
@@ -0,0 +1,158 @@
+//! Checks for source code that displays differently from how it is lexed, like in the
+//! [Trojan Source](https://trojansource.codes) attacks.
+//!
+//! [`check`] runs all checks on the source and the identifiers among its tokens:
+//!
+//! ```
+//! use lexr::{lex_rule, security};
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Token { Ident, Other }
+//!
+//! lex_rule!{lex -> Token {
+//!     unicode_ident => |_| Token::Ident,
+//!     _ => |_| Token::Other,
+//! }}
+//!
+//! let source = "admin = аdmin"; // The second `а` is Cyrillic
+//! let tokens = lex(source).into_vec();
+//! let warnings = security::check(source, &tokens, |token| *token == Token::Ident);
+//! assert_eq!(warnings[0].to_string(), "Identifier `аdmin` at 1:9-13 mixes letters of different scripts");
+//! assert_eq!(warnings[1].to_string(), "Identifier `аdmin` at 1:9-13 looks like `admin` at 1:1-5");
+//! ```
+//!
+//! Confusable identifiers are found with a small hand-written table of the Cyrillic and Greek letters that look most like Latin letters.
+//! This is a partial heuristic, which misses most of the confusables of other scripts, digits and symbols,
+//! so an identifier not reported may still look like another.
+
+use std::{collections::HashMap, fmt::Display};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{LexBuf, SrcLoc};
+
+#[derive(Clone, Debug, PartialEq)]
+/// A part of the source that may display differently from how it is lexed.
+pub enum SecurityWarning {
+    /// A bidirectional control character, which can reorder how the surrounding text is displayed.
+    BidiControl { c: char, loc: SrcLoc },
+    /// An identifier mixing letters of scripts that look alike, like Latin and Cyrillic.
+    MixedScript { ident: String, loc: SrcLoc },
+    /// An identifier that looks like an earlier, different identifier.
+    Confusable { ident: String, loc: SrcLoc, other: String, other_loc: SrcLoc },
+}
+
+impl SecurityWarning {
+    /// The location of the character or identifier.
+    pub fn loc(&self) -> SrcLoc {
+        match self {
+            Self::BidiControl { loc, .. } | Self::MixedScript { loc, .. } | Self::Confusable { loc, .. } => *loc,
+        }
+    }
+}
+
+impl Display for SecurityWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BidiControl { c, loc } => write!(f, "Bidirectional control character U+{:04X} at {}", *c as u32, loc),
+            Self::MixedScript { ident, loc } => write!(f, "Identifier `{}` at {} mixes letters of different scripts", ident, loc),
+            Self::Confusable { ident, loc, other, other_loc } => write!(f, "Identifier `{}` at {} looks like `{}` at {}", ident, loc, other, other_loc),
+        }
+    }
+}
+
+/// Runs all checks, on the whole source and on the tokens for which `is_ident` is true.
+///
+/// The warnings are ordered by location.
+pub fn check<T>(source: &str, tokens: &[(T, SrcLoc)], mut is_ident: impl FnMut(&T) -> bool) -> Vec<SecurityWarning> {
+    let idents = tokens.iter().filter(|(token, _)| is_ident(token)).map(|(_, loc)| *loc);
+    let mut warnings = bidi_controls(source);
+    warnings.extend(confusable_identifiers(source, idents));
+    warnings.sort_by_key(|warning| warning.loc().get_abs_loc());
+    warnings
+}
+
+/// Finds the bidirectional control characters in the source, including in comments and strings.
+pub fn bidi_controls(source: &str) -> Vec<SecurityWarning> {
+    let buf = LexBuf::new(source);
+    let mut at = 0;
+    let mut warnings = Vec::new();
+    for (i, c) in source.char_indices().filter(|(_, c)| is_bidi_control(*c)) {
+        buf.advance(i - at);
+        let loc = buf.advance(c.len_utf8());
+        at = i + c.len_utf8();
+        warnings.push(SecurityWarning::BidiControl { c, loc });
+    }
+    warnings
+}
+
+/// Checks the identifiers at the locations for mixed scripts, and for looking like another of the identifiers.
+///
+/// Only the Latin, Cyrillic and Greek scripts are considered, and identifiers only look alike through the letters in the table of this module.
+pub fn confusable_identifiers(source: &str, idents: impl IntoIterator<Item = SrcLoc>) -> Vec<SecurityWarning> {
+    lazy_static! {
+        static ref SCRIPTS: [Regex; 3] = [r"\p{Latin}", r"\p{Cyrillic}", r"\p{Greek}"].map(|r| Regex::new(r).unwrap());
+    }
+
+    let mut warnings = Vec::new();
+    // The first identifier seen with each skeleton
+    let mut skeletons: HashMap<String, (&str, SrcLoc)> = HashMap::new();
+    for loc in idents {
+        let ident = loc.text(source);
+
+        if SCRIPTS.iter().filter(|script| script.is_match(ident)).count() > 1 {
+            warnings.push(SecurityWarning::MixedScript { ident: ident.to_string(), loc });
+        }
+
+        let (other, other_loc) = *skeletons.entry(skeleton(ident)).or_insert((ident, loc));
+        if other != ident {
+            warnings.push(SecurityWarning::Confusable { ident: ident.to_string(), loc, other: other.to_string(), other_loc });
+        }
+    }
+    warnings
+}
+
+/// Whether the character controls the direction of text, like U+202E RIGHT-TO-LEFT OVERRIDE.
+pub fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Replaces some Cyrillic and Greek letters that look like Latin letters with those.
+fn skeleton(ident: &str) -> String {
+    ident.chars().map(|c| match c {
+        'а' | 'α' => 'a',
+        'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'е' => 'e',
+        'һ' => 'h',
+        'і' | 'ι' => 'i',
+        'ј' => 'j',
+        'о' | 'ο' => 'o',
+        'р' | 'ρ' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'ν' => 'v',
+        'ԝ' => 'w',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' => 'y',
+        'А' | 'Α' => 'A',
+        'В' | 'Β' => 'B',
+        'С' | 'Ϲ' => 'C',
+        'Е' | 'Ε' => 'E',
+        'Н' | 'Η' => 'H',
+        'І' | 'Ι' => 'I',
+        'Ј' => 'J',
+        'К' | 'Κ' => 'K',
+        'М' | 'Μ' => 'M',
+        'Ν' => 'N',
+        'О' | 'Ο' => 'O',
+        'Р' | 'Ρ' => 'P',
+        'Ѕ' => 'S',
+        'Т' | 'Τ' => 'T',
+        'Х' | 'Χ' => 'X',
+        'У' | 'Υ' => 'Y',
+        'Ζ' => 'Z',
+        c => c,
+    }).collect()
+}
@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use crate::SrcLoc;
+
+#[derive(Clone, Debug)]
+/// The entries of a REPL session lexed by one [resumed](crate::Lexer::resume) lexer, for pointing diagnostics at the entry.
+///
+/// Locations from a resumed lexer count from the start of the first entry. When each entry is added to the session as it is lexed,
+/// the session can tell which entry a location is in, and where in that entry:
+///
+/// ```
+/// use lexr::{lex_rule, Session};
+///
+/// lex_rule!{lex -> char {
+///     ws => |_| continue,
+///     "[a-z]" => |s| s.chars().next().unwrap(),
+/// }}
+///
+/// let mut session = Session::new();
+/// let mut lexer = lex("");
+/// for entry in ["a\n", "b\nc d\n"] {
+///     session.add(entry);
+///     lexer.resume(entry);
+///     lexer.by_ref().for_each(drop);
+/// }
+///
+/// lexer.resume("e 1\n");
+/// session.add("e 1\n");
+/// assert_eq!(lexer.next_token(), Some('e'));
+/// let error = lexer.try_next().unwrap().unwrap_err();
+/// assert_eq!(session.locate(error.loc()).to_string(), "input 3, line 1, column 3");
+/// ```
+pub struct Session {
+    entries: Vec<Entry>,
+    /// The position and byte index after the last entry.
+    end: ((usize, usize), usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    /// The line and column of the first character.
+    start: (usize, usize),
+    /// The byte index of the first character.
+    idx: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A position in an entry of a [`Session`].
+pub struct EntryLoc {
+    /// The number of the entry, starting from 1.
+    pub entry: usize,
+    /// The line in the entry, starting from 1.
+    pub line: usize,
+    /// The column in the line, starting from 1.
+    pub col: usize,
+}
+
+impl Session {
+    /// Creates a session without entries.
+    pub fn new() -> Self {
+        Self { entries: Vec::new(), end: ((1, 1), 0) }
+    }
+
+    /// Adds the next entry, returning its number, starting from 1.
+    ///
+    /// Entries must be added in the order they are given to the lexer.
+    pub fn add(&mut self, input: &str) -> usize {
+        let ((mut line, mut col), idx) = self.end;
+        self.entries.push(Entry { start: (line, col), idx });
+        for c in input.chars() {
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        self.end = ((line, col), idx + input.len());
+        self.entries.len()
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no entries have been added.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entry of the start of the location, and the position in it.
+    ///
+    /// A location at the end of an entry, like that of an eof rule, is at the start of the next entry, if it has been added.
+    pub fn locate(&self, loc: SrcLoc) -> EntryLoc {
+        let idx = loc.get_abs_loc().0;
+        let entry = self.entries.partition_point(|entry| entry.idx <= idx).max(1);
+        let (start_line, start_col) = self.entries.get(entry - 1).map_or((1, 1), |entry| entry.start);
+        let (line, col) = loc.start();
+        let col = if line == start_line { col + 1 - start_col } else { col };
+        EntryLoc { entry, line: line + 1 - start_line, col }
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for EntryLoc {
+    /// Writes the position like `input 3, line 2, column 4`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "input {}, line {}, column {}", self.entry, self.line, self.col)
+    }
+}
@@ -0,0 +1,100 @@
+//! The sizes of token types, as tokens are moved and stored many times, and a large variant makes every token large.
+//!
+//! Deriving [`TokenShape`] lets [`analyze`] report the size of the token type, and the variants whose fields make it larger than a threshold:
+//!
+//! ```
+//! use lexr::shape::{self, TokenShape};
+//!
+//! #[derive(TokenShape)]
+//! enum Token {
+//!     Num(u32),
+//!     Ident(String),
+//!     Plus,
+//! }
+//!
+//! let report = shape::analyze::<Token>(16);
+//! assert_eq!(report.size, 24);
+//! assert_eq!(report.oversized().map(|v| v.name).collect::<Vec<_>>(), vec!["Ident"]);
+//! assert_eq!(report.warnings().unwrap(), "warning: variant Ident (String) of Token has 24 bytes of fields, \
+//!     over the threshold of 16 bytes. Consider a Box, an interned symbol or a span of the source instead");
+//! ```
+//!
+//! With `#[token_shape(max_size = 16)]` on the enum, the size is also checked when compiling, failing if it is larger:
+//!
+//! ```compile_fail
+//! use lexr::shape::TokenShape;
+//!
+//! #[derive(TokenShape)]
+//! #[token_shape(max_size = 16)]
+//! enum Token {
+//!     Ident(String),
+//! }
+//! ```
+
+use std::fmt::Write;
+
+pub use lexr_derive::TokenShape;
+
+/// Describes the variants of a token type. Implemented with `#[derive(TokenShape)]`.
+pub trait TokenShape {
+    /// The variants, in the order they are declared.
+    fn variants() -> Vec<VariantShape>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The size of a variant of a token type.
+pub struct VariantShape {
+    /// The name of the variant.
+    pub name: &'static str,
+    /// The size of the fields of the variant in bytes, without the discriminant.
+    pub size: usize,
+    /// The types of the fields, as written.
+    pub fields: Vec<&'static str>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The size of a token type and its variants, found by [`analyze`].
+pub struct ShapeReport {
+    /// The name of the type.
+    pub name: &'static str,
+    /// The size of the type in bytes.
+    pub size: usize,
+    /// The size above which variants are reported.
+    pub threshold: usize,
+    /// The variants, in the order they are declared.
+    pub variants: Vec<VariantShape>,
+}
+
+/// Reports the size of the token type, and its variants with fields larger than `threshold` bytes.
+pub fn analyze<T: TokenShape>(threshold: usize) -> ShapeReport {
+    let name = std::any::type_name::<T>();
+    ShapeReport {
+        name: name.rsplit("::").next().unwrap_or(name),
+        size: std::mem::size_of::<T>(),
+        threshold,
+        variants: T::variants(),
+    }
+}
+
+impl ShapeReport {
+    /// The variants with fields larger than the threshold.
+    pub fn oversized(&self) -> impl Iterator<Item = &VariantShape> {
+        self.variants.iter().filter(|variant| variant.size > self.threshold)
+    }
+
+    /// Describes the [oversized](Self::oversized) variants, one per line, or returns `None` if there are none.
+    pub fn warnings(&self) -> Option<String> {
+        let mut warnings = String::new();
+        for variant in self.oversized() {
+            if !warnings.is_empty() {
+                warnings.push('\n');
+            }
+            write!(
+                warnings, "warning: variant {} ({}) of {} has {} bytes of fields, over the threshold of {} bytes. \
+                    Consider a Box, an interned symbol or a span of the source instead",
+                variant.name, variant.fields.join(", "), self.name, variant.size, self.threshold,
+            ).unwrap();
+        }
+        (!warnings.is_empty()).then_some(warnings)
+    }
+}
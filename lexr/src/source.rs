@@ -0,0 +1,67 @@
+use std::{borrow::Cow, rc::Rc, sync::Arc};
+
+use crate::LexBuf;
+
+/// Something that can be lexed, by creating a [`LexBuf`] over it.
+/// 
+/// This is what the lexer functions generated by [`lex_rule!`](crate::lex_rule!) take. It is implemented for:
+/// - References to strings: `&str`, `&String`, `&Box<str>`, `&Rc<str>`, `&Arc<str>` and `&Cow<str>`.
+/// - References to bytes: `&[u8]`, `&[u8; N]` and `&Vec<u8>`. They are checked to be UTF-8 as they are lexed, like [`LexBuf::from_utf8_lazy`],
+///   and lexing returns [`LexError::InvalidUtf8`](crate::LexError::InvalidUtf8) where they are not. [`LexBuf::from_utf8`] checks all of them up front instead.
+/// - A [`LexBuf`], which is how sub rules are called on the buffer of another lexer.
+/// 
+/// Other types holding a single string can be lexed with `lex(text.as_ref())`.
+/// Text that is not stored as a single string, like a rope, can not be borrowed as the single string slice the patterns are matched against,
+/// so it is lexed a window at a time by a [`ChunkedLexer`](crate::ChunkedLexer) instead.
+pub trait Source<'a> {
+    /// Creates a buffer over the source.
+    fn into_buf(self) -> LexBuf<'a>;
+}
+
+impl<'a> Source<'a> for LexBuf<'a> {
+    fn into_buf(self) -> LexBuf<'a> {
+        self
+    }
+}
+
+macro_rules! impl_source_for_strings {
+    ($($string:ty),*) => {$(
+        impl<'a> Source<'a> for &'a $string {
+            fn into_buf(self) -> LexBuf<'a> {
+                LexBuf::from(AsRef::<str>::as_ref(self))
+            }
+        }
+    )*};
+}
+
+impl_source_for_strings!(str, String, Box<str>, Rc<str>, Arc<str>);
+
+impl<'a, 'b: 'a> Source<'a> for &'a &'b str {
+    fn into_buf(self) -> LexBuf<'a> {
+        LexBuf::from(*self)
+    }
+}
+
+impl<'a, 'b: 'a> Source<'a> for &'a Cow<'b, str> {
+    fn into_buf(self) -> LexBuf<'a> {
+        LexBuf::from(&**self)
+    }
+}
+
+impl<'a> Source<'a> for &'a [u8] {
+    fn into_buf(self) -> LexBuf<'a> {
+        LexBuf::from_utf8_lazy(self)
+    }
+}
+
+impl<'a, const N: usize> Source<'a> for &'a [u8; N] {
+    fn into_buf(self) -> LexBuf<'a> {
+        LexBuf::from_utf8_lazy(self)
+    }
+}
+
+impl<'a> Source<'a> for &'a Vec<u8> {
+    fn into_buf(self) -> LexBuf<'a> {
+        LexBuf::from_utf8_lazy(self)
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{lexer_spec::fnv1a, SrcLoc};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A location along with a hash of the text it covers, to tell if it is stale when applied to a file read again.
+///
+/// Caches of tokens store a stamp for each span. When the file is read again, [`is_valid`](Self::is_valid) tells
+/// if the span still covers the same text, and [`relocate`](Self::relocate) finds the text if it has moved a little:
+///
+/// ```
+/// use lexr::{lex_rule, SpanStamp};
+///
+/// lex_rule!{lex -> () {
+///     ws => |_| continue,
+///     ident => |_| (),
+/// }}
+///
+/// let old = "let x";
+/// let stamps: Vec<_> = lex(old).map(|(_, loc)| SpanStamp::new(loc, old)).collect();
+/// let bytes: Vec<_> = stamps.iter().map(|stamp| stamp.to_bytes()).collect();
+///
+/// let new = "let  y";
+/// let stamps: Vec<_> = bytes.iter().map(SpanStamp::from_bytes).collect();
+/// assert_eq!(lexr::span_stamp::stale(&stamps, new), vec![1]);
+/// assert_eq!(stamps[0].relocate(new, 8), Some(stamps[0].loc));
+/// assert_eq!(stamps[1].relocate(new, 8), None);
+/// ```
+pub struct SpanStamp {
+    /// The location.
+    pub loc: SrcLoc,
+    /// The hash of the text at the location.
+    pub hash: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The length and a hash of a whole file, to tell cheaply if none of its spans can be stale.
+pub struct FileStamp {
+    /// The length of the file in bytes.
+    pub len: usize,
+    /// The hash of the file.
+    pub hash: u64,
+}
+
+impl SpanStamp {
+    /// The size of a stamp written by [`to_bytes`](Self::to_bytes).
+    pub const BYTES: usize = SrcLoc::BYTES + 8;
+
+    /// Stamps the location with the text it covers in the source.
+    ///
+    /// Panics if the location is not within the source.
+    pub fn new(loc: SrcLoc, source: &str) -> Self {
+        Self { loc, hash: fnv1a(loc.text(source).as_bytes()) }
+    }
+
+    /// Whether the location still covers the same text in the source.
+    pub fn is_valid(&self, source: &str) -> bool {
+        let (start, end) = self.loc.get_abs_loc();
+        source.get(start..end).is_some_and(|text| fnv1a(text.as_bytes()) == self.hash)
+    }
+
+    /// Finds the text within `window` bytes of where it was, returning its new location, or `None` if it is not found.
+    ///
+    /// The nearest match is used, the earlier one on ties. A valid stamp is its own location.
+    pub fn relocate(&self, source: &str, window: usize) -> Option<SrcLoc> {
+        let (start, end) = self.loc.get_abs_loc();
+        let len = end - start;
+        let found = (0..=window).flat_map(|shift| [start.checked_sub(shift), start.checked_add(shift).filter(|_| shift > 0)])
+            .flatten()
+            .find(|&at| source.get(at..at + len).is_some_and(|text| fnv1a(text.as_bytes()) == self.hash))?;
+        Some(SrcLoc::from_offsets(source, (found, found + len)))
+    }
+
+    /// Writes the stamp as bytes, which are the same on all platforms: the location as by [`SrcLoc::to_bytes`], then the hash in little-endian.
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut bytes = [0; Self::BYTES];
+        bytes[..SrcLoc::BYTES].copy_from_slice(&self.loc.to_bytes());
+        bytes[SrcLoc::BYTES..].copy_from_slice(&self.hash.to_le_bytes());
+        bytes
+    }
+
+    /// Reads a stamp written by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8; Self::BYTES]) -> Self {
+        let loc = SrcLoc::from_bytes(bytes[..SrcLoc::BYTES].try_into().unwrap());
+        let hash = u64::from_le_bytes(bytes[SrcLoc::BYTES..].try_into().unwrap());
+        Self { loc, hash }
+    }
+}
+
+impl FileStamp {
+    /// Stamps the whole source.
+    pub fn new(source: &str) -> Self {
+        Self { len: source.len(), hash: fnv1a(source.as_bytes()) }
+    }
+
+    /// Whether the source is the same as the stamped one.
+    pub fn matches(&self, source: &str) -> bool {
+        *self == Self::new(source)
+    }
+}
+
+/// The indexes of the stamps that are stale in the source, in order.
+pub fn stale(stamps: &[SpanStamp], source: &str) -> Vec<usize> {
+    stamps.iter().enumerate().filter(|(_, stamp)| !stamp.is_valid(source)).map(|(i, _)| i).collect()
+}
@@ -1,7 +1,32 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::Path, str::FromStr};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// What lines and columns count from, for a consumer of locations like a language server.
+///
+/// It is kept with the rest of the configuration of the application, and given where locations are converted or displayed,
+/// with [`SrcLoc::start_in`], [`SrcLoc::end_in`] and [`LocDisplay::base`].
+pub enum Base {
+    /// Counting from 0, like the language server protocol.
+    Zero,
+    /// Counting from 1, like editors and compilers show to humans. This is how locations are stored.
+    #[default]
+    One,
+}
+
+impl Base {
+    /// The line and column, counting from 1, counted from this base instead.
+    pub fn position(self, (line, col): (usize, usize)) -> (usize, usize) {
+        match self {
+            Base::Zero => (line.saturating_sub(1), col.saturating_sub(1)),
+            Base::One => (line, col),
+        }
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 /// A location in the source code
+/// 
+/// Lines and columns count from 1. Accessors ending with `0` count from 0, like the language server protocol.
 pub struct SrcLoc {
     abs_range: (usize, usize),
     start: (usize, usize),
@@ -26,6 +51,51 @@ impl SrcLoc {
         self.end
     }
 
+    /// The start line, counting from 0.
+    pub fn line0(&self) -> usize {
+        self.start.0.saturating_sub(1)
+    }
+
+    /// The start line, counting from 1.
+    pub fn line1(&self) -> usize {
+        self.start.0
+    }
+
+    /// The start column, counting from 0.
+    pub fn col0(&self) -> usize {
+        self.start.1.saturating_sub(1)
+    }
+
+    /// The start column, counting from 1.
+    pub fn col1(&self) -> usize {
+        self.start.1
+    }
+
+    /// The start of the source location, counting from 0. (start_line, start_col)
+    pub fn start0(&self) -> (usize, usize) {
+        (self.start.0.saturating_sub(1), self.start.1.saturating_sub(1))
+    }
+
+    /// The end of the source location, counting from 0. (end_line, end_col)
+    pub fn end0(&self) -> (usize, usize) {
+        (self.end.0.saturating_sub(1), self.end.1.saturating_sub(1))
+    }
+
+    /// The start of the source location, counting from the base. (start_line, start_col)
+    pub fn start_in(&self, base: Base) -> (usize, usize) {
+        base.position(self.start)
+    }
+
+    /// The end of the source location, counting from the base. (end_line, end_col)
+    pub fn end_in(&self, base: Base) -> (usize, usize) {
+        base.position(self.end)
+    }
+
+    /// Create a new source location from a start and end position, counting from 0
+    pub fn new0(start: (usize, usize), end: (usize, usize), abs: (usize, usize)) -> Self {
+        Self::new((start.0 + 1, start.1 + 1), (end.0 + 1, end.1 + 1), abs)
+    }
+
     /// Create a new source location from a start and end position
     pub fn new(start: (usize, usize), end: (usize, usize), abs: (usize, usize)) -> Self {
         Self {
@@ -35,6 +105,21 @@ impl SrcLoc {
         }
     }
 
+    /// Create a source location from the byte offsets in the source, finding its lines and columns.
+    /// 
+    /// This is how locations from a lexer without positions get them, when they are needed, like for an error message.
+    /// Panics if the offsets are not within the source, at character boundaries.
+    pub fn from_offsets(source: &str, (start, end): (usize, usize)) -> Self {
+        let position = |idx: usize| {
+            let before = &source[..idx];
+            let line = before.matches('\n').count() + 1;
+            let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+            (line, col)
+        };
+        let last = source[start..end].char_indices().next_back().map_or(start, |(i, _)| start + i);
+        Self::new(position(start), position(last), (start, end))
+    }
+
     /// Combine two source locations into one, spanning from the start of the first to the end of the second
     /// 
     /// Panics if the source locations overlap
@@ -54,22 +139,186 @@ impl SrcLoc {
         (self.start.0, self.start.1, self.end.0, self.end.1)
     }
 
-    /// The absolute range of the source location, meaning the char indexes. [start, end)
+    /// The absolute range of the source location, meaning the byte offsets. [start, end)
     pub fn get_abs_loc(&self) -> (usize, usize) {
         self.abs_range
     }
+
+    /// The size of a location written by [`to_bytes`](Self::to_bytes).
+    pub const BYTES: usize = 48;
+
+    /// Writes the location as bytes, which are the same on all platforms, for caches of tokens.
+    /// 
+    /// The start line and column, the end line and column, and the byte offsets are written as little-endian 64 bit integers.
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut bytes = [0; Self::BYTES];
+        let fields = [self.start.0, self.start.1, self.end.0, self.end.1, self.abs_range.0, self.abs_range.1];
+        for (chunk, field) in bytes.chunks_exact_mut(8).zip(fields) {
+            chunk.copy_from_slice(&(field as u64).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reads a location written by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8; Self::BYTES]) -> Self {
+        let mut fields = bytes.chunks_exact(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()) as usize);
+        let mut next = || fields.next().unwrap();
+        Self { start: (next(), next()), end: (next(), next()), abs_range: (next(), next()) }
+    }
+
+    /// The text at the location, in the source it is from. This lets tokens carry only their location instead of owning their text.
+    /// 
+    /// Panics if the location is not within the source.
+    pub fn text<'s>(&self, source: &'s str) -> &'s str {
+        &source[self.abs_range.0..self.abs_range.1]
+    }
+
+    /// Parses the text at the location, in the source it is from, like the value of a number token.
+    /// 
+    /// Panics if the location is not within the source.
+    pub fn parse<F: FromStr>(&self, source: &str) -> Result<F, F::Err> {
+        self.text(source).parse()
+    }
+
+    /// Formats the location with options, like the path of its file, as in `loc.display().path("src/main.rs")`.
+    /// 
+    /// Without options, this is the same as the `Display` of the location, like `1:3-5`.
+    pub fn display(&self) -> LocDisplay<'static> {
+        LocDisplay { loc: *self, path: None, offsets: false, base: Base::One, start_only: false }
+    }
+
+    /// The lines covered by the location in the source, each underlined where it is covered, for showing errors in a terminal.
+    /// 
+    /// Tabs are expanded to the next multiple of `tab_width` columns, and full-width characters take two columns,
+    /// so the underline lines up with the text. A zero-width location is marked by a single caret.
+    /// Line numbers count from 1, like compilers show them. Panics if the location is not within the source.
+    /// 
+    /// ```
+    /// use lexr::SrcLoc;
+    /// 
+    /// let source = "let\tx = 1;";
+    /// let loc = SrcLoc::new((1, 5), (1, 5), (4, 5));
+    /// assert_eq!(loc.display_snippet(source, 4), "\
+    /// 1 | let x = 1;
+    ///   |     ^");
+    /// ```
+    pub fn display_snippet(&self, source: &str, tab_width: usize) -> String {
+        let (start, end) = self.abs_range;
+        assert!(start <= end && end <= source.len(), "The location {} is not within the source of {} bytes", self, source.len());
+        let first = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        // The last byte covered, or the start for zero-width locations
+        let last = source[start..end].char_indices().next_back().map_or(start, |(i, _)| start + i);
+        let lines_end = source[last..].find('\n').map_or(source.len(), |i| last + i);
+
+        let first_line = source[..start].matches('\n').count() + 1;
+        let lines: Vec<_> = source[first..lines_end].split('\n').collect();
+        let gutter = (first_line + lines.len() - 1).to_string().len();
+
+        let mut snippet = Vec::new();
+        let mut line_start = first;
+        for (i, line) in lines.iter().enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let mut text = String::new();
+            let mut underline = String::new();
+            let mut col = 0;
+            for (idx, c) in line.char_indices().map(|(idx, c)| (line_start + idx, c)) {
+                let width = match c {
+                    '\t' => tab_width.max(1) - col % tab_width.max(1),
+                    c => char_width(c),
+                };
+                match c {
+                    '\t' => text.extend(std::iter::repeat_n(' ', width)),
+                    c => text.push(c),
+                }
+                let covered = (start..end).contains(&idx) || (start == end && idx == start);
+                let mark = if covered { '^' } else { ' ' };
+                underline.extend(std::iter::repeat_n(mark, width));
+                col += width;
+            }
+            // A zero-width location at the end of the line is marked after it
+            if start == end && start == line_start + line.len() {
+                underline.push('^');
+            }
+            snippet.push(format!("{:>gutter$} | {}", first_line + i, text));
+            snippet.push(format!("{:>gutter$} | {}", "", underline).trim_end().to_string());
+            line_start += lines[i].len() + 1;
+        }
+        snippet.join("\n")
+    }
 }
 
-impl Display for SrcLoc {
+/// The number of columns a character takes in a terminal: two for full-width characters, and none for combining marks.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+/// A [`SrcLoc`] with options for how it is displayed, created with [`SrcLoc::display`].
+pub struct LocDisplay<'a> {
+    loc: SrcLoc,
+    path: Option<&'a Path>,
+    offsets: bool,
+    base: Base,
+    start_only: bool,
+}
+
+impl LocDisplay<'_> {
+    /// Writes the path of the file before the location, like `src/main.rs:1:3-5`.
+    pub fn path<P: AsRef<Path> + ?Sized>(self, path: &P) -> LocDisplay<'_> {
+        LocDisplay { path: Some(path.as_ref()), ..self }
+    }
+
+    /// Writes the byte offsets after the location, like `1:3-5 (2..5)`.
+    pub fn offsets(self) -> Self {
+        Self { offsets: true, ..self }
+    }
+
+    /// Writes lines and columns counting from the base, which is 1 by default.
+    pub fn base(self, base: Base) -> Self {
+        Self { base, ..self }
+    }
+
+    /// Writes lines and columns counting from 0, like the language server protocol, instead of from 1.
+    pub fn zero_based(self) -> Self {
+        self.base(Base::Zero)
+    }
+
+    /// Writes only the start of the location, like `src/main.rs:1:3`, which editors and terminals recognize as a link.
+    pub fn start_only(self) -> Self {
+        Self { start_only: true, ..self }
+    }
+}
+
+impl Display for LocDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (start_line, start_col, end_line, end_col) = self.get_loc();
+        if let Some(path) = self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        let (start_line, start_col) = self.loc.start_in(self.base);
+        let (end_line, end_col) = if self.start_only { (start_line, start_col) } else { self.loc.end_in(self.base) };
         if start_line == end_line && start_col == end_col {
-            write!(f, "{}:{}", start_line, start_col)
+            write!(f, "{}:{}", start_line, start_col)?;
         } else if start_line == end_line {
-            write!(f, "{}:{}-{}", start_line, start_col, end_col)
+            write!(f, "{}:{}-{}", start_line, start_col, end_col)?;
         } else {
-            write!(f, "{}:{}-{}:{}", start_line, start_col, end_line, end_col)
+            write!(f, "{}:{}-{}:{}", start_line, start_col, end_line, end_col)?;
         }
+        if self.offsets {
+            write!(f, " ({}..{})", self.loc.abs_range.0, self.loc.abs_range.1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SrcLoc {
+    /// Writes the location like `1:3-5`, or `1:3-2:1` over several lines, counting from 1.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.display().fmt(f)
     }
 }
 
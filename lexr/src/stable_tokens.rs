@@ -0,0 +1,96 @@
+use crate::SrcLoc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The identity of a token, which is kept when the source is lexed again after an edit elsewhere.
+///
+/// New ids are larger than all earlier ids, but the ids of the tokens of a source are not in source order after an [`update`](StableTokens::update).
+pub struct TokenId(pub u64);
+
+#[derive(Clone, Debug)]
+/// Tokens of a source with stable ids, for caches keyed by token that should survive edits.
+///
+/// After an edit, the source is lexed again and given to [`update`](Self::update).
+/// Tokens before and after the edited part keep their ids, if they are the same token with the same text.
+/// The tokens in between get new ids:
+///
+/// ```
+/// use lexr::{lex_rule, StableTokens};
+///
+/// lex_rule!{lex -> &'static str {
+///     ws => |_| continue,
+///     "[a-z]+" => |_| "word",
+/// }}
+///
+/// let mut tokens = StableTokens::new("one two three", lex("one two three").into_vec());
+/// let before = tokens.ids().to_vec();
+///
+/// tokens.update("one four three", lex("one four three").into_vec());
+/// let after = tokens.ids();
+/// assert_eq!((after[0], after[2]), (before[0], before[2]));
+/// assert!(after[1] > before[2]);
+/// ```
+pub struct StableTokens<T> {
+    source: String,
+    tokens: Vec<(T, SrcLoc)>,
+    ids: Vec<TokenId>,
+    next_id: u64,
+}
+
+impl<T: PartialEq> StableTokens<T> {
+    /// Gives new ids to the tokens of the source.
+    pub fn new(source: impl Into<String>, tokens: Vec<(T, SrcLoc)>) -> Self {
+        let ids = (0..tokens.len() as u64).map(TokenId).collect();
+        Self { source: source.into(), next_id: tokens.len() as u64, tokens, ids }
+    }
+
+    /// Replaces the source and its tokens, keeping the ids of tokens outside the changed part.
+    ///
+    /// A token keeps its id if it is in the unchanged start of the tokens, or in the unchanged end,
+    /// meaning the same token with the same text at the same distance from the start or end of the source.
+    pub fn update(&mut self, source: impl Into<String>, tokens: Vec<(T, SrcLoc)>) {
+        let source = source.into();
+        let same = |old: &(T, SrcLoc), new: &(T, SrcLoc), old_at: usize, new_at: usize| {
+            old.0 == new.0 && old_at == new_at && old.1.text(&self.source) == new.1.text(&source)
+        };
+
+        let prefix = self.tokens.iter().zip(&tokens)
+            .take_while(|(old, new)| same(old, new, old.1.get_abs_loc().0, new.1.get_abs_loc().0))
+            .count();
+        let max_suffix = self.tokens.len().min(tokens.len()) - prefix;
+        let suffix = self.tokens.iter().rev().zip(tokens.iter().rev())
+            .take(max_suffix)
+            .take_while(|(old, new)| same(old, new, self.source.len() - old.1.get_abs_loc().0, source.len() - new.1.get_abs_loc().0))
+            .count();
+
+        let changed = tokens.len() - prefix - suffix;
+        let mut ids = Vec::with_capacity(tokens.len());
+        ids.extend_from_slice(&self.ids[..prefix]);
+        ids.extend((self.next_id..self.next_id + changed as u64).map(TokenId));
+        ids.extend_from_slice(&self.ids[self.ids.len() - suffix..]);
+
+        self.next_id += changed as u64;
+        self.source = source;
+        self.tokens = tokens;
+        self.ids = ids;
+    }
+
+    /// The current source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The tokens of the current source.
+    pub fn tokens(&self) -> &[(T, SrcLoc)] {
+        &self.tokens
+    }
+
+    /// The ids of the tokens, in the same order.
+    pub fn ids(&self) -> &[TokenId] {
+        &self.ids
+    }
+
+    /// The tokens along with their ids.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, SrcLoc, TokenId)> {
+        self.tokens.iter().zip(&self.ids).map(|((token, loc), id)| (token, *loc, *id))
+    }
+}
@@ -0,0 +1,102 @@
+use std::{iter::FusedIterator, slice};
+
+use crate::SrcLoc;
+
+#[derive(Clone, Debug)]
+/// The tokens of a single pass over the input, viewed both with and without trivia like whitespace and comments.
+///
+/// Formatters need the [`raw`](Self::raw) tokens including trivia, while parsers want only the [`significant`](Self::significant) ones.
+/// The trivia rules of the lexer return tokens instead of using `continue`, and the streams tell them apart with a predicate:
+///
+/// ```
+/// use lexr::lex_rule;
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Token { Space, Comment, Word }
+/// use Token::*;
+///
+/// lex_rule!{lex -> Token {
+///     "[ \n]+" => |_| Space,
+///     "#[^\n]*" => |_| Comment,
+///     "[a-z]+" => |_| Word,
+/// }}
+///
+/// let streams = lex("a # note\nb").streams(|token| matches!(token, Space | Comment));
+/// assert_eq!(streams.raw().map(|(token, _)| token).collect::<Vec<_>>(), [&Word, &Space, &Comment, &Space, &Word]);
+/// assert_eq!(streams.significant().map(|(token, _)| token).collect::<Vec<_>>(), [&Word, &Word]);
+/// assert_eq!(streams.trivia_before(1).len(), 3);
+/// ```
+pub struct TokenStreams<T> {
+    tokens: Vec<(T, SrcLoc)>,
+    /// The indexes of the significant tokens in `tokens`.
+    significant: Vec<usize>,
+}
+
+impl<T> TokenStreams<T> {
+    /// Collects the tokens, noting which are significant, meaning not trivia.
+    pub fn new(tokens: impl IntoIterator<Item = (T, SrcLoc)>, mut is_trivia: impl FnMut(&T) -> bool) -> Self {
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        let significant = tokens.iter().enumerate().filter(|(_, (token, _))| !is_trivia(token)).map(|(i, _)| i).collect();
+        Self { tokens, significant }
+    }
+
+    /// All tokens, including trivia, in the order of the input.
+    pub fn raw(&self) -> slice::Iter<'_, (T, SrcLoc)> {
+        self.tokens.iter()
+    }
+
+    /// The tokens that are not trivia, in the order of the input.
+    pub fn significant(&self) -> Significant<'_, T> {
+        Significant { tokens: &self.tokens, indexes: self.significant.iter() }
+    }
+
+    /// The number of significant tokens.
+    pub fn significant_len(&self) -> usize {
+        self.significant.len()
+    }
+
+    /// The trivia right before the significant token with the index, or at the end of the input for the index after the last one.
+    ///
+    /// This is where formatters find the comments attached to a token.
+    pub fn trivia_before(&self, index: usize) -> &[(T, SrcLoc)] {
+        let end = self.significant.get(index).copied().unwrap_or(self.tokens.len());
+        let start = match index {
+            0 => 0,
+            index => self.significant.get(index - 1).map_or(end, |&previous| previous + 1),
+        };
+        &self.tokens[start..end]
+    }
+
+    /// All tokens, including trivia.
+    pub fn into_raw(self) -> Vec<(T, SrcLoc)> {
+        self.tokens
+    }
+}
+
+/// An iterator over the significant tokens of [`TokenStreams`].
+pub struct Significant<'a, T> {
+    tokens: &'a [(T, SrcLoc)],
+    indexes: slice::Iter<'a, usize>,
+}
+
+impl<'a, T> Iterator for Significant<'a, T> {
+    type Item = &'a (T, SrcLoc);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indexes.next().map(|&i| &self.tokens[i])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indexes.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for Significant<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.indexes.next_back().map(|&i| &self.tokens[i])
+    }
+}
+
+impl<T> ExactSizeIterator for Significant<'_, T> {}
+
+impl<T> FusedIterator for Significant<'_, T> {}
@@ -0,0 +1,203 @@
+use std::{collections::VecDeque, ops::Range};
+
+use crate::{LexBuf, LexError, SrcLoc};
+
+/// Text that is not necessarily stored as a single string, like the rope of an editor buffer.
+/// 
+/// It can be lexed with a [`ChunkedLexer`], which only copies a window of the text at a time.
+pub trait TextSource {
+    /// The length of the text in bytes.
+    fn len_bytes(&self) -> usize;
+
+    /// The first char boundary at or after the byte offset.
+    fn ceil_char_boundary(&self, idx: usize) -> usize;
+
+    /// Appends the text in the byte range to the string. The range is on char boundaries.
+    fn write_range(&self, range: Range<usize>, out: &mut String);
+}
+
+impl TextSource for str {
+    fn len_bytes(&self) -> usize {
+        self.len()
+    }
+
+    fn ceil_char_boundary(&self, mut idx: usize) -> usize {
+        while !self.is_char_boundary(idx) {
+            idx += 1;
+        }
+        idx
+    }
+
+    fn write_range(&self, range: Range<usize>, out: &mut String) {
+        out.push_str(&self[range])
+    }
+}
+
+impl TextSource for String {
+    fn len_bytes(&self) -> usize {
+        self.as_str().len_bytes()
+    }
+
+    fn ceil_char_boundary(&self, idx: usize) -> usize {
+        TextSource::ceil_char_boundary(self.as_str(), idx)
+    }
+
+    fn write_range(&self, range: Range<usize>, out: &mut String) {
+        self.as_str().write_range(range, out)
+    }
+}
+
+#[cfg(feature = "ropey")]
+impl TextSource for ropey::Rope {
+    fn len_bytes(&self) -> usize {
+        ropey::Rope::len_bytes(self)
+    }
+
+    fn ceil_char_boundary(&self, idx: usize) -> usize {
+        let char_idx = self.byte_to_char(idx);
+        match self.char_to_byte(char_idx) {
+            start if start < idx => self.char_to_byte(char_idx + 1),
+            start => start,
+        }
+    }
+
+    fn write_range(&self, range: Range<usize>, out: &mut String) {
+        for chunk in self.byte_slice(range).chunks() {
+            out.push_str(chunk)
+        }
+    }
+}
+
+/// Lexes a [`TextSource`] a window at a time, without copying all of it into a single string.
+/// 
+/// Tokens are only trusted if they end at least `margin` bytes before the end of the window,
+/// as a pattern might match more, or differently, with the rest of the text. The rest of the window is lexed again with the next window.
+/// A window is grown if no token fits in it, so tokens longer than the window are still lexed correctly.
+/// Errors are only reported once the window reaches the end of the text, so input that can not be lexed causes the windows to grow until then.
+/// 
+/// The lexer is started anew on each window, by calling the function with a buffer:
+/// ```
+/// use lexr::{lex_rule, ChunkedLexer};
+/// #[derive(Debug, PartialEq)]
+/// enum Token { Word }
+/// 
+/// lex_rule!{lex -> Token {
+///     ws => |_| continue,
+///     "[a-z]+" => |_| Token::Word,
+/// }}
+/// 
+/// let text = "some words ".repeat(100);
+/// let lexer = ChunkedLexer::new(&text, |buf| lex(buf).into_result_vec()).window(64, 16);
+/// assert_eq!(lexer.count(), 200);
+/// ```
+/// 
+/// The margin must cover how far the patterns look ahead, like with `followed_by`, and the text skipped between tokens.
+/// Tokens reaching the end of a window are always lexed again, so `eof` and `eol` only match at the end of the text and of lines,
+/// and each window starts at the line and column where it is in the text, so `bol` only matches at the start of lines.
+pub struct ChunkedLexer<'s, S: TextSource + ?Sized, T, F: FnMut(LexBuf<'_>) -> Vec<Result<(T, SrcLoc), LexError>>> {
+    source: &'s S,
+    lex: F,
+    window: usize,
+    margin: usize,
+    // The position the next window starts at, as a byte offset, line and column
+    pos: (usize, usize, usize),
+    text: String,
+    lexed: VecDeque<Result<(T, SrcLoc), LexError>>,
+    done: bool,
+}
+
+impl<'s, S: TextSource + ?Sized, T, F: FnMut(LexBuf<'_>) -> Vec<Result<(T, SrcLoc), LexError>>> ChunkedLexer<'s, S, T, F> {
+    /// Creates a lexer over the source, with windows of 64 KiB and a margin of 1 KiB.
+    pub fn new(source: &'s S, lex: F) -> Self {
+        Self {
+            source,
+            lex,
+            window: 64 * 1024,
+            margin: 1024,
+            pos: (0, 1, 1),
+            text: String::new(),
+            lexed: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Sets the size of the windows, and the margin at their end where tokens are lexed again with the next window.
+    pub fn window(mut self, window: usize, margin: usize) -> Self {
+        self.window = window.max(1);
+        self.margin = margin;
+        self
+    }
+
+    fn lex_window(&mut self) {
+        let (start, line, col) = self.pos;
+        let mut window = self.window;
+        loop {
+            let end = self.source.ceil_char_boundary((start + window).min(self.source.len_bytes()));
+            let last = end == self.source.len_bytes();
+            self.text.clear();
+            self.source.write_range(start..end, &mut self.text);
+
+            let buf = LexBuf::from(self.text.as_str());
+            *buf.idx.borrow_mut() = start;
+            *buf.line.borrow_mut() = line;
+            *buf.col.borrow_mut() = col;
+            let results = (self.lex)(buf);
+
+            if last {
+                self.lexed.extend(results);
+                self.done = true;
+                return
+            }
+
+            // The end of the window is not the end of the text, so matches reaching it, like with `eof` and `eol`, are lexed again
+            let limit = end.saturating_sub(self.margin).min(end - 1);
+            let mut trusted = Vec::new();
+            let mut resume = start;
+            for result in results {
+                match &result {
+                    Ok((_, loc)) if loc.get_abs_loc().1 <= limit => resume = loc.get_abs_loc().1,
+                    // Errors might be caused by a token that continues after the window
+                    _ => break,
+                }
+                trusted.push(result);
+            }
+
+            if resume == start {
+                window *= 2;
+                continue
+            }
+
+            self.lexed.extend(trusted);
+            let (mut line, mut col) = (line, col);
+            for c in self.text[..resume - start].chars() {
+                if c == '\n' {
+                    line += 1;
+                    col = 1;
+                } else {
+                    col += 1;
+                }
+            }
+            self.pos = (resume, line, col);
+            return
+        }
+    }
+
+    /// Gets the next token and location, or the error if the input could not be lexed.
+    pub fn try_next(&mut self) -> Option<Result<(T, SrcLoc), LexError>> {
+        while self.lexed.is_empty() && !self.done {
+            self.lex_window();
+        }
+        self.lexed.pop_front()
+    }
+}
+
+impl<S: TextSource + ?Sized, T, F: FnMut(LexBuf<'_>) -> Vec<Result<(T, SrcLoc), LexError>>> Iterator for ChunkedLexer<'_, S, T, F> {
+    type Item = (T, SrcLoc);
+
+    /// Panics if the input could not be lexed.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().map(|result| result.unwrap_or_else(|error| panic!("{}", error)))
+    }
+}
+
+impl<S: TextSource + ?Sized, T, F: FnMut(LexBuf<'_>) -> Vec<Result<(T, SrcLoc), LexError>>> std::iter::FusedIterator for ChunkedLexer<'_, S, T, F> {}
@@ -0,0 +1,210 @@
+//! Classes of tokens for editor features, like brace matching and auto-indent, without a parser.
+//!
+//! Deriving [`TokenClass`] marks the variants of the token enum, and gives `token.class()`.
+//! [`match_braces`] then pairs the open and close tokens, [`depths`] gives the nesting depth of each token,
+//! and [`folding_ranges`] gives the ranges an editor can fold:
+//!
+//! ```
+//! use lexr::lex_rule;
+//! use lexr::token_class::{self, Class, TokenClass};
+//!
+//! #[derive(TokenClass)]
+//! enum Token {
+//!     #[class(open = "paren")] LParen,
+//!     #[class(close = "paren")] RParen,
+//!     #[class(open = "brace")] LBrace,
+//!     #[class(close = "brace")] RBrace,
+//!     #[class(terminator)] Semi,
+//!     #[class(string)] Str(String),
+//!     Ident,
+//! }
+//!
+//! lex_rule!{lex -> Token {
+//!     ws => |_| continue,
+//!     r"\(" => |_| Token::LParen,
+//!     r"\)" => |_| Token::RParen,
+//!     r"\{" => |_| Token::LBrace,
+//!     r"\}" => |_| Token::RBrace,
+//!     ";" => |_| Token::Semi,
+//!     r#""[^"]*""# => |s| Token::Str(s.to_string()),
+//!     ident => |_| Token::Ident,
+//! }}
+//!
+//! let tokens = lex(r#"f(x) { g("}"); } )"#).into_vec();
+//! assert_eq!(tokens[7].0.class(), Class::String);
+//! assert_eq!(Token::LBrace.class(), Class::Open("brace"));
+//!
+//! let braces = token_class::match_braces(&tokens);
+//! assert_eq!(braces.pairs, vec![(1, 3), (6, 8), (4, 10)]);
+//! assert_eq!(braces.unmatched, vec![11]);
+//! assert_eq!(braces.partner(4), Some(10));
+//! assert_eq!(token_class::depths(&tokens), vec![0, 0, 1, 0, 0, 1, 1, 2, 1, 1, 0, 0]);
+//!
+//! let tokens = lex("f {\n  g(x);\n}").into_vec();
+//! let ranges = token_class::folding_ranges(&tokens);
+//! assert_eq!(ranges.iter().map(|loc| loc.to_string()).collect::<Vec<_>>(), vec!["1:3-3:1"]);
+//! ```
+
+use crate::SrcLoc;
+
+pub use lexr_derive::TokenClass;
+
+/// Classifies tokens for editor features. Implemented with `#[derive(TokenClass)]`.
+pub trait TokenClass {
+    /// The class of the token.
+    fn class(&self) -> Class;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The class of a token.
+pub enum Class {
+    /// Opens a pair, like `(`, with the kind of the pair.
+    Open(&'static str),
+    /// Closes a pair, like `)`, with the kind of the pair.
+    Close(&'static str),
+    /// Ends a statement, like `;`.
+    Terminator,
+    /// A comment.
+    Comment,
+    /// A string literal.
+    String,
+    /// Starts an import statement, like `use` or `import`.
+    Import,
+    /// Any other token.
+    Other,
+}
+
+impl Class {
+    /// The kind of the pair, for open and close tokens.
+    pub fn pair(&self) -> Option<&'static str> {
+        match self {
+            Class::Open(pair) | Class::Close(pair) => Some(pair),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// The open and close tokens paired by [`match_braces`], by their indexes in the tokens.
+pub struct Braces {
+    /// The indexes of the open and close token of each pair, in the order they are closed.
+    pub pairs: Vec<(usize, usize)>,
+    /// The indexes of the open and close tokens without a partner, in order.
+    pub unmatched: Vec<usize>,
+}
+
+impl Braces {
+    /// The index of the partner of the open or close token at the index.
+    pub fn partner(&self, index: usize) -> Option<usize> {
+        self.pairs.iter().find_map(|&(open, close)| match index {
+            _ if index == open => Some(close),
+            _ if index == close => Some(open),
+            _ => None,
+        })
+    }
+}
+
+/// Pairs each close token with the innermost open token before it, if it is of the same kind.
+///
+/// A close token of another kind is unmatched, and leaves the open token to be closed later.
+pub fn match_braces<T: TokenClass>(tokens: &[(T, SrcLoc)]) -> Braces {
+    let mut braces = Braces::default();
+    let mut open: Vec<(usize, &'static str)> = Vec::new();
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        match token.class() {
+            Class::Open(pair) => open.push((i, pair)),
+            Class::Close(pair) => match open.last() {
+                Some(&(start, kind)) if kind == pair => {
+                    open.pop();
+                    braces.pairs.push((start, i));
+                },
+                _ => braces.unmatched.push(i),
+            },
+            _ => (),
+        }
+    }
+    braces.unmatched.extend(open.into_iter().map(|(i, _)| i));
+    braces.unmatched.sort_unstable();
+    braces
+}
+
+/// The nesting depth of each token, for indenting lines by their first token.
+///
+/// Open tokens are at the depth outside them, and close tokens at the depth of their open token. Unmatched close tokens are ignored.
+pub fn depths<T: TokenClass>(tokens: &[(T, SrcLoc)]) -> Vec<usize> {
+    let braces = match_braces(tokens);
+    let mut closes = vec![false; tokens.len()];
+    for &(_, close) in &braces.pairs {
+        closes[close] = true;
+    }
+
+    let mut depth = 0;
+    tokens.iter().enumerate().map(|(i, (token, _))| match token.class() {
+        Class::Open(_) => {
+            depth += 1;
+            depth - 1
+        },
+        Class::Close(_) if closes[i] => {
+            depth -= 1;
+            depth
+        },
+        _ => depth,
+    }).collect()
+}
+
+/// The ranges spanning several lines that an editor can fold, in the order they start, like for an LSP `foldingRange` request.
+///
+/// These are the pairs of open and close tokens, the comments, along with the comments on the following lines that start them,
+/// and the import statements, along with the import statements on the following lines. An import statement ends before
+/// the first token starting a line outside the delimiters opened in it.
+pub fn folding_ranges<T: TokenClass>(tokens: &[(T, SrcLoc)]) -> Vec<SrcLoc> {
+    let classes: Vec<Class> = tokens.iter().map(|(token, _)| token.class()).collect();
+    let starts_line = |i: usize| i == 0 || tokens[i].1.start().0 > tokens[i - 1].1.end().0;
+    // Whether the token at `next` starts the line after the token at `prev`
+    let on_next_line = |prev: usize, next: usize| starts_line(next) && tokens[next].1.start().0 == tokens[prev].1.end().0 + 1;
+
+    let mut ranges: Vec<SrcLoc> = match_braces(tokens).pairs.into_iter()
+        .map(|(open, close)| tokens[open].1.combine(tokens[close].1))
+        .collect();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let mut end = i;
+        match classes[i] {
+            Class::Comment => {
+                while end + 1 < tokens.len() && classes[end + 1] == Class::Comment && on_next_line(end, end + 1) {
+                    end += 1;
+                }
+            },
+            Class::Import => loop {
+                let mut depth = 0usize;
+                let mut next = end + 1;
+                while next < tokens.len() && !(depth == 0 && starts_line(next)) {
+                    match classes[next] {
+                        Class::Open(_) => depth += 1,
+                        Class::Close(_) => depth = depth.saturating_sub(1),
+                        _ => (),
+                    }
+                    next += 1;
+                }
+                end = next - 1;
+                if next < tokens.len() && classes[next] == Class::Import && on_next_line(end, next) {
+                    end = next;
+                } else {
+                    break
+                }
+            },
+            _ => (),
+        }
+        if end > i {
+            ranges.push(tokens[i].1.combine(tokens[end].1));
+        } else if classes[i] == Class::Comment {
+            ranges.push(tokens[i].1);
+        }
+        i = end + 1;
+    }
+
+    ranges.retain(|loc| loc.start().0 < loc.end().0);
+    ranges.sort_by_key(|loc| loc.get_abs_loc());
+    ranges
+}
@@ -0,0 +1,69 @@
+use crate::SrcLoc;
+
+/// Checks that the locations of tokens fit the source, for [`Lexer::validated`](crate::Lexer::validated).
+pub(crate) struct SpanValidator<'s> {
+    source: &'s str,
+    /// The number of tokens checked.
+    tokens: usize,
+    /// The location of the previous token.
+    prev: Option<SrcLoc>,
+    /// A byte index with its line and column, which only moves forward, as tokens may not overlap.
+    cursor: (usize, (usize, usize)),
+}
+
+impl<'s> SpanValidator<'s> {
+    pub(crate) fn new(source: &'s str) -> Self {
+        Self { source, tokens: 0, prev: None, cursor: (0, (1, 1)) }
+    }
+
+    /// Panics unless the location is within the source, after the previous token, and its lines and columns match its byte offsets.
+    /// 
+    /// Tokens emitted by the same match may share its location.
+    pub(crate) fn check(&mut self, loc: SrcLoc) {
+        self.tokens += 1;
+        if self.prev == Some(loc) {
+            return
+        }
+        let (start, end) = loc.get_abs_loc();
+        let token = self.tokens;
+        let fail = |problem: String| -> ! { panic!("Invalid location {} of token {}: {}", loc, token, problem) };
+
+        if start > end || end > self.source.len() {
+            fail(format!("The bytes {}..{} are not within the source of {} bytes", start, end, self.source.len()))
+        }
+        if !self.source.is_char_boundary(start) || !self.source.is_char_boundary(end) {
+            fail(format!("The bytes {}..{} are not on character boundaries", start, end))
+        }
+        let prev_end = self.prev.map_or(0, |prev| prev.get_abs_loc().1);
+        if start < prev_end {
+            fail(format!("It starts at byte {}, before the previous token ends at byte {}", start, prev_end))
+        }
+        self.prev = Some(loc);
+
+        let position = self.position(start);
+        if loc.start() != position {
+            fail(format!("It starts at {}:{} in the source", position.0, position.1))
+        }
+        // The end is the position of the last character
+        let last = self.source[start..end].char_indices().next_back().map_or(start, |(i, _)| start + i);
+        let position = self.position(last);
+        if loc.end() != position {
+            fail(format!("It ends at {}:{} in the source", position.0, position.1))
+        }
+    }
+
+    /// The line and column of the byte index, which must not be before the cursor.
+    fn position(&mut self, idx: usize) -> (usize, usize) {
+        let (from, (mut line, mut col)) = self.cursor;
+        for c in self.source[from..idx].chars() {
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        self.cursor = (idx, (line, col));
+        (line, col)
+    }
+}
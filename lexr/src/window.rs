@@ -0,0 +1,115 @@
+use std::{collections::VecDeque, fmt::Display};
+
+use crate::SrcLoc;
+
+/// A buffer of tokens retaining only a bounded number of past tokens, for parsing arbitrarily long inputs in bounded memory.
+///
+/// A parser reads the tokens from the window, and can rewind to a position it has read, to backtrack,
+/// as long as the token there is still retained. Older tokens are dropped, and rewinding to them fails with a [`WindowError`]:
+///
+/// ```
+/// use lexr::{lex_rule, TokenWindow};
+///
+/// lex_rule!{lex -> char {
+///     ws => |_| continue,
+///     "." => |s| s.chars().next().unwrap(),
+/// }}
+///
+/// let mut window = TokenWindow::new(lex("a b c d e"), 2);
+/// assert_eq!(window.next().map(|(token, _)| *token), Some('a'));
+/// let start = window.position();
+/// window.next();
+/// window.next();
+/// window.rewind(start).unwrap();
+/// assert_eq!(window.next().map(|(token, _)| *token), Some('b'));
+///
+/// window.next();
+/// window.next();
+/// window.next();
+/// assert_eq!(window.history().map(|(token, _)| *token).collect::<String>(), "cde");
+/// assert_eq!(window.rewind(start).unwrap_err().to_string(),
+///     "Cannot rewind to token 1, as only tokens from 2 are retained");
+/// ```
+pub struct TokenWindow<T, I: Iterator<Item = (T, SrcLoc)>> {
+    tokens: I,
+    /// The retained tokens, from the oldest, including those read ahead of the position after rewinding.
+    buffer: VecDeque<(T, SrcLoc)>,
+    /// The position of the oldest retained token.
+    start: usize,
+    /// The position of the next token.
+    position: usize,
+    /// The number of tokens retained before the last one read.
+    history: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The error of rewinding a [`TokenWindow`] to a token that has been dropped.
+pub struct WindowError {
+    /// The position rewound to.
+    pub position: usize,
+    /// The position of the oldest retained token.
+    pub oldest: usize,
+}
+
+impl<T, I: Iterator<Item = (T, SrcLoc)>> TokenWindow<T, I> {
+    /// Creates a window over the tokens, retaining the last token read and the `history` tokens before it.
+    pub fn new(tokens: I, history: usize) -> Self {
+        Self { tokens, buffer: VecDeque::new(), start: 0, position: 0, history }
+    }
+
+    /// Reads the next token, advancing the position.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&(T, SrcLoc)> {
+        // Dropped before reading, so the token read is retained along with the history before it
+        while self.position - self.start > self.history {
+            self.buffer.pop_front();
+            self.start += 1;
+        }
+        if self.position == self.start + self.buffer.len() {
+            let pair = self.tokens.next()?;
+            self.buffer.push_back(pair);
+        }
+        self.position += 1;
+        self.buffer.get(self.position - 1 - self.start)
+    }
+
+    /// Gets the next token without advancing the position.
+    pub fn peek(&mut self) -> Option<&(T, SrcLoc)> {
+        if self.position == self.start + self.buffer.len() {
+            let pair = self.tokens.next()?;
+            self.buffer.push_back(pair);
+        }
+        self.buffer.get(self.position - self.start)
+    }
+
+    /// The position of the next token, counting the tokens read from the start.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves the position back to an earlier one, so the tokens from there are read again.
+    ///
+    /// Fails if the token at the position has been dropped, as more than the history has been read since.
+    /// Panics if the position is ahead of the current one.
+    pub fn rewind(&mut self, position: usize) -> Result<(), WindowError> {
+        assert!(position <= self.position, "Cannot rewind to token {}, which is ahead of the position {}", position, self.position);
+        if position < self.start {
+            return Err(WindowError { position, oldest: self.start })
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    /// The retained tokens before the position, from the oldest, like for the context of an error.
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &(T, SrcLoc)> {
+        self.buffer.range(..self.position - self.start)
+    }
+}
+
+impl Display for WindowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cannot rewind to token {}, as only tokens from {} are retained", self.position, self.oldest)
+    }
+}
+
+impl std::error::Error for WindowError {}
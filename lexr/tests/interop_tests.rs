@@ -0,0 +1,217 @@
+use lexr::{lex_rule, LexError, SrcLoc, interop::lalrpop::LalrpopLexer};
+#[derive(Debug, PartialEq)]
+enum Token {
+    A, B
+}
+use Token::*;
+
+#[test]
+fn lalrpop_lexer_produces_offset_triples() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "bb" => |_| B,
+    }}
+
+    let mut lexer = LalrpopLexer::new(lex("a\n bb c"));
+    assert_eq!(lexer.next(), Some(Ok((0, A, 1))));
+    assert_eq!(lexer.next(), Some(Ok((3, B, 5))));
+    assert_eq!(lexer.next(), Some(Err(LexError::UnexpectedChar('c', SrcLoc::new((2, 5), (2, 5), (6, 6))))));
+    assert_eq!(lexer.next(), None);
+
+    assert_eq!(lexer.loc_at(3), Some(SrcLoc::new((2, 2), (2, 3), (3, 5))));
+    assert_eq!(lexer.loc_at(4), None);
+}
+
+#[cfg(feature = "tree-sitter")]
+mod tree_sitter {
+    use lexr::{lex_rule, interop::tree_sitter::{ExternalScanner, TSLexer}};
+
+    #[repr(C)]
+    struct MockLexer {
+        lexer: TSLexer,
+        input: Vec<char>,
+        pos: usize,
+        end: usize,
+    }
+
+    unsafe extern "C" fn advance(lexer: *mut TSLexer, _skip: bool) {
+        let mock = &mut *lexer.cast::<MockLexer>();
+        mock.pos += 1;
+        mock.lexer.lookahead = mock.input.get(mock.pos).map_or(0, |c| *c as i32);
+    }
+
+    unsafe extern "C" fn mark_end(lexer: *mut TSLexer) {
+        let mock = &mut *lexer.cast::<MockLexer>();
+        mock.end = mock.pos;
+    }
+
+    unsafe extern "C" fn get_column(_: *mut TSLexer) -> u32 { 0 }
+
+    unsafe extern "C" fn is_at_included_range_start(_: *const TSLexer) -> bool { false }
+
+    unsafe extern "C" fn eof(lexer: *const TSLexer) -> bool {
+        let mock = &*lexer.cast::<MockLexer>();
+        mock.pos >= mock.input.len()
+    }
+
+    fn mock(input: &str) -> MockLexer {
+        let input: Vec<char> = input.chars().collect();
+        MockLexer {
+            lexer: TSLexer {
+                lookahead: input.first().map_or(0, |c| *c as i32),
+                result_symbol: 0,
+                advance, mark_end, get_column, is_at_included_range_start, eof,
+            },
+            input,
+            pos: 0,
+            end: 0,
+        }
+    }
+
+    lex_rule!{tokens -> u16 {
+        "[a-z]+" eol => |_| 0,
+        "[0-9]+" => |_| 1,
+    }}
+
+    #[derive(Clone, Default)]
+    struct Scanner {
+        tokens: u8,
+    }
+
+    impl ExternalScanner for Scanner {
+        const SYMBOLS: usize = 2;
+
+        fn scan(&mut self, input: &str, valid_symbols: &[bool]) -> Option<(u16, usize)> {
+            let (symbol, loc) = tokens(input).try_next()?.ok()?;
+            self.tokens += 1;
+            valid_symbols[symbol as usize].then_some((symbol, loc.get_abs_loc().1))
+        }
+
+        fn serialize(&self, buffer: &mut Vec<u8>) {
+            buffer.push(self.tokens)
+        }
+
+        fn deserialize(&mut self, bytes: &[u8]) {
+            self.tokens = bytes[0]
+        }
+    }
+
+    lexr::tree_sitter_scanner!(test, Scanner);
+
+    fn scan(payload: *mut std::ffi::c_void, lexer: &mut MockLexer, valid_symbols: &[bool; 2]) -> bool {
+        unsafe { tree_sitter_test_external_scanner_scan(payload, &mut lexer.lexer, valid_symbols.as_ptr()) }
+    }
+
+    #[test]
+    fn tree_sitter_scanner_marks_token_end() {
+        let payload = tree_sitter_test_external_scanner_create();
+
+        let mut lexer = mock("123abc");
+        assert!(scan(payload, &mut lexer, &[true, true]));
+        assert_eq!((lexer.lexer.result_symbol, lexer.end), (1, 3));
+
+        // Only an identifier at the end of a line is a token
+        let mut lexer = mock("abc def");
+        assert!(!scan(payload, &mut lexer, &[true, true]));
+        let mut lexer = mock("abc\ndef");
+        assert!(scan(payload, &mut lexer, &[true, true]));
+        assert_eq!((lexer.lexer.result_symbol, lexer.end), (0, 3));
+
+        let mut lexer = mock("123");
+        assert!(!scan(payload, &mut lexer, &[true, false]));
+
+        unsafe {
+            let mut buffer = [0; 1024];
+            assert_eq!(tree_sitter_test_external_scanner_serialize(payload, buffer.as_mut_ptr()), 1);
+            assert_eq!(buffer[0], 2);
+            tree_sitter_test_external_scanner_deserialize(payload, buffer.as_ptr(), 0);
+            assert_eq!(tree_sitter_test_external_scanner_serialize(payload, buffer.as_mut_ptr()), 1);
+            assert_eq!(buffer[0], 0);
+            tree_sitter_test_external_scanner_destroy(payload);
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct LargeState;
+
+    impl ExternalScanner for LargeState {
+        const SYMBOLS: usize = 0;
+
+        fn scan(&mut self, _: &str, _: &[bool]) -> Option<(u16, usize)> {
+            None
+        }
+
+        fn serialize(&self, buffer: &mut Vec<u8>) {
+            buffer.resize(2000, 1)
+        }
+    }
+
+    #[test]
+    fn tree_sitter_drops_oversized_scanner_state() {
+        let payload = lexr::interop::tree_sitter::create::<LargeState>();
+        let mut buffer = [0; 1024];
+        let length = unsafe { lexr::interop::tree_sitter::serialize::<LargeState>(payload, buffer.as_mut_ptr()) };
+        assert_eq!(length, 0);
+        assert!(buffer.iter().all(|&byte| byte == 0));
+        unsafe { lexr::interop::tree_sitter::destroy::<LargeState>(payload) };
+    }
+}
+
+#[cfg(feature = "proc-macro2")]
+mod proc_macro2 {
+    use lexr::{lex_rule, interop::proc_macro2::LiteralSource};
+    use proc_macro2::{Ident, Literal, TokenTree};
+
+    lex_rule!{words -> String {
+        ws => |_| continue,
+        "[a-z]+" => |s| s.to_string(),
+    }}
+
+    #[test]
+    fn literal_source_resolves_escapes() {
+        let source = LiteralSource::new(Literal::string("a\tb\n\"c\"")).unwrap();
+        assert_eq!(source.value(), "a\tb\n\"c\"");
+
+        let source = LiteralSource::new(Literal::u8_suffixed(1));
+        assert!(source.is_none());
+
+        let tokens = LiteralSource::new(Literal::string("ab cd")).unwrap();
+        let stream = tokens.to_token_stream(words(tokens.value()), |word, span| Ident::new(&word, span).into());
+        let idents: Vec<_> = stream.into_iter().map(|tree| match tree {
+            TokenTree::Ident(ident) => ident.to_string(),
+            _ => unreachable!(),
+        }).collect();
+        assert_eq!(idents, vec!["ab", "cd"]);
+    }
+}
+
+#[cfg(feature = "nom")]
+mod nom {
+    use lexr::{lex_rule, SrcLoc, interop::nom::{Tokens, token}};
+    use nom::{bytes::complete::take_while, multi::many1, IResult};
+    use super::Token::{self, *};
+
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    #[test]
+    fn nom_parses_lexed_tokens() {
+        let tokens = lex("a a b a").into_vec();
+        let result: IResult<_, _> = many1(token(A))(Tokens::new(&tokens));
+        let (rest, a_tokens) = result.unwrap();
+        assert_eq!(a_tokens.iter().map(|(_, loc)| loc.get_abs_loc()).collect::<Vec<_>>(), vec![(0, 1), (2, 3)]);
+        assert_eq!(rest.loc(), Some(SrcLoc::new((1, 5), (1, 5), (4, 5))));
+
+        let result: IResult<_, _> = take_while(|token: &Token| *token == B)(rest);
+        let (rest, b_tokens) = result.unwrap();
+        assert_eq!(b_tokens.as_slice().len(), 1);
+        assert_eq!(rest.as_slice().len(), 1);
+
+        let result: IResult<_, _> = token(B)(rest);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::{iter::FusedIterator, panic::{catch_unwind, AssertUnwindSafe}};
+
+use lexr::{lex_rule, LexError, Lexer};
+#[derive(Debug, PartialEq)]
+enum Token {
+    A, B, Eof
+}
+use Token::*;
+
+lex_rule!{lex -> Token {
+    "a" => |_| A,
+    "b" => |_| B,
+    "p" => |_| panic!("action panicked"),
+    "x" => |_| break,
+    "e" => |_| { emit!(A); emit!(B); break },
+    eof => |_| Eof,
+}}
+
+fn assert_fused<T>(_: &T) where T: FusedIterator {}
+
+#[test]
+fn lexers_are_fused() {
+    let lexer = lex("a");
+    assert_fused(&lexer);
+    assert_fused(&lexer.boxed());
+}
+
+#[test]
+fn next_after_eof_returns_none() {
+    let mut lexer = lex("a");
+    assert_eq!(lexer.next_token(), Some(A));
+    assert!(!lexer.is_finished());
+    assert_eq!(lexer.next_token(), Some(Eof));
+    assert_eq!(lexer.next_token(), None);
+    assert!(lexer.is_finished());
+    assert_eq!(lexer.next_token(), None);
+    assert_eq!(lexer.try_next(), None);
+}
+
+#[test]
+fn next_after_error_returns_none() {
+    let mut lexer = lex("a?b");
+    assert_eq!(lexer.next_token(), Some(A));
+    assert!(matches!(lexer.try_next(), Some(Err(LexError::UnexpectedChar('?', _)))));
+    assert!(lexer.is_finished());
+    assert_eq!(lexer.try_next(), None);
+    assert_eq!(lexer.next_token(), None);
+}
+
+#[test]
+fn next_after_error_panic_returns_none() {
+    let mut lexer = lex("?b");
+    assert!(catch_unwind(AssertUnwindSafe(|| lexer.next())).is_err());
+    assert!(lexer.is_finished());
+    assert_eq!(lexer.next_token(), None);
+}
+
+#[test]
+fn next_after_break_returns_none() {
+    let mut lexer = lex("axb");
+    assert_eq!(lexer.next_token(), Some(A));
+    assert_eq!(lexer.next_token(), None);
+    assert!(lexer.is_finished());
+    assert_eq!(lexer.next_token(), None);
+
+    // Tokens emitted before breaking are still returned
+    let mut lexer = lex("ea");
+    assert_eq!(lexer.next_token(), Some(A));
+    assert!(!lexer.is_finished());
+    assert_eq!(lexer.next_token(), Some(B));
+    assert_eq!(lexer.next_token(), None);
+    assert!(lexer.is_finished());
+}
+
+#[test]
+fn next_after_panicking_action_continues_after_the_match() {
+    let mut lexer = lex("apb");
+    assert_eq!(lexer.next_token(), Some(A));
+    assert!(catch_unwind(AssertUnwindSafe(|| lexer.next())).is_err());
+    assert!(!lexer.is_finished());
+    assert_eq!(lexer.next_token(), Some(B));
+    assert_eq!(lexer.next_token(), Some(Eof));
+}
+
+#[test]
+fn pushed_back_tokens_are_returned_after_finishing() {
+    let mut lexer: Lexer<_, _> = lex("");
+    assert_eq!(lexer.next_token(), Some(Eof));
+    assert_eq!(lexer.next_token(), None);
+    lexer.push_back(B, lexr::SrcLoc::DUMMY);
+    assert!(!lexer.is_finished());
+    assert_eq!(lexer.next_token(), Some(B));
+    assert_eq!(lexer.next_token(), None);
+    assert!(lexer.is_finished());
+}
+
+#[test]
+fn cancelled_lexer_is_finished() {
+    let token = lexr::CancelToken::new();
+    token.cancel();
+    let mut lexer = lex(lexr::LexBuf::new("a").with_cancel(token));
+    assert!(matches!(lexer.try_next(), Some(Err(LexError::Cancelled(_)))));
+    assert!(lexer.is_finished());
+    assert_eq!(lexer.next_token(), None);
+}
+
+#[test]
+fn token_window_retains_bounded_history() {
+    lex_rule!{lex -> char {
+        ws => |_| continue,
+        "." => |s| s.chars().next().unwrap(),
+    }}
+
+    let mut window = lex("a b c").window(0);
+    assert_eq!(window.peek().map(|(token, _)| *token), Some('a'));
+    assert_eq!(window.next().map(|(token, _)| *token), Some('a'));
+    window.rewind(0).unwrap();
+    assert_eq!(window.next().map(|(token, _)| *token), Some('a'));
+    assert_eq!(window.next().map(|(token, loc)| (*token, loc.get_abs_loc())), Some(('b', (2, 3))));
+    assert_eq!(window.rewind(0), Err(lexr::WindowError { position: 0, oldest: 1 }));
+    assert_eq!(window.history().count(), 1);
+
+    // Tokens read ahead before rewinding are kept until read again
+    let mut window = lex("a b c d").window(1);
+    window.next();
+    window.next();
+    window.next();
+    window.rewind(1).unwrap();
+    assert_eq!(window.next().map(|(token, _)| *token), Some('b'));
+    assert_eq!(window.next().map(|(token, _)| *token), Some('c'));
+    assert_eq!(window.next().map(|(token, _)| *token), Some('d'));
+    assert!(window.next().is_none());
+    assert_eq!(window.position(), 4);
+}
@@ -221,4 +221,1752 @@ fn readme_example() {
     
     let tokens = lex("a ba ca S 42").into_token_vec();
     assert_eq!(tokens, vec![A, B, C, D, Num, Eof])
-}
\ No newline at end of file
+}
+
+#[test]
+fn lex_into_reuses_buffer() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let mut buffer = Vec::new();
+    lex("abab").lex_into(&mut buffer);
+    assert_eq!(buffer.len(), 4);
+    let capacity = buffer.capacity();
+
+    lex("ba").lex_into(&mut buffer);
+    assert_eq!(buffer.iter().map(|(t, _)| t).collect::<Vec<_>>(), vec![&B, &A]);
+    assert_eq!(buffer.capacity(), capacity);
+
+    let buffer = lex("a").collect_reusing(buffer);
+    assert_eq!(buffer.len(), 1);
+    assert_eq!(buffer.capacity(), capacity);
+}
+
+#[test]
+fn on_eof_is_emitted_exactly_once() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        on_eof => Eof,
+    }}
+
+    let mut lexer = lex("aa");
+    assert_eq!(lexer.next_token(), Some(A));
+    assert_eq!(lexer.next_token(), Some(A));
+    assert_eq!(lexer.next_token(), Some(Eof));
+    assert_eq!(lexer.next_token(), None);
+    assert_eq!(lexer.next_token(), None);
+}
+
+#[test]
+fn on_eof_has_location_at_end() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        on_eof => Eof,
+    }}
+
+    let (token, loc) = lex("a\na ").last().unwrap();
+    assert_eq!(token, Eof);
+    assert_eq!(loc.start(), (2, 3));
+    assert_eq!(loc.get_abs_loc(), (4, 4));
+}
+
+#[test]
+fn on_eof_is_emitted_after_sub_rule_reaches_end() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "#" => |_, buf| { comment(buf).deplete(); continue },
+        on_eof => Eof,
+    }}
+
+    lex_rule!{comment -> () {
+        eof => |_| break,
+        _ => |_| continue,
+    }}
+
+    let tokens = lex("aa# comment").into_token_vec();
+    assert_eq!(tokens, vec![A, A, Eof]);
+}
+
+#[test]
+fn break_does_not_emit_on_eof() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "b" => |_| break,
+        on_eof => Eof,
+    }}
+
+    let tokens = lex("aba").into_token_vec();
+    assert_eq!(tokens, vec![A]);
+}
+
+#[test]
+fn sub_rule_on_eof_reports_unterminated_string() {
+    use lexr::SrcLoc;
+
+    #[derive(Debug, PartialEq)]
+    enum T {
+        Str(SrcLoc),
+        Unterminated(SrcLoc),
+        Eof,
+    }
+
+    lex_rule!{lex -> T {
+        ws => |_| continue,
+        "\"" => |_, buf, loc| string(buf, loc).next_token().unwrap(),
+        on_eof => T::Eof,
+    }}
+
+    lex_rule!{string(start: SrcLoc) -> T {
+        "\"" => |_, _, loc| T::Str(start.combine(loc)),
+        _ => |_| continue,
+        on_eof => |loc| T::Unterminated(start.combine(loc)),
+    }}
+
+    let tokens = lex("\"abc\" \"de").into_token_vec();
+    assert_eq!(tokens, vec![
+        T::Str(SrcLoc::new((1, 1), (1, 5), (0, 5))),
+        T::Unterminated(SrcLoc::new((1, 7), (1, 10), (6, 9))),
+        T::Eof,
+    ]);
+}
+
+#[test]
+fn bol_only_matches_at_start_of_line() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        bol "#" r"[a-z]+" => |_| A,
+        "#" => |_| B,
+        r"[a-z]+" => |_| C,
+    }}
+
+    let tokens = lex("#define x #y\n#if z").into_token_vec();
+    assert_eq!(tokens, vec![A, C, B, C, A, C]);
+}
+
+#[test]
+fn eol_only_matches_at_end_of_line() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        r"[a-z]+" eol => |_| A,
+        r"[a-z]+" => |_| B,
+    }}
+
+    let tokens = lex("ab cd\r\nef gh\nij").into_token_vec();
+    assert_eq!(tokens, vec![B, A, B, A, A]);
+}
+
+#[test]
+fn followed_by_does_not_consume_lookahead() {
+    #[derive(Debug, PartialEq)]
+    enum T {
+        Int, Float, Range
+    }
+
+    lex_rule!{lex -> T {
+        ws => |_| continue,
+        r"[0-9]+" followed_by(r"\.\.") => |_| T::Int,
+        r"[0-9]+\." not_followed_by(r"\.") => |_| T::Float,
+        r"[0-9]+" => |_| T::Int,
+        r"\.\." => |_| T::Range,
+    }}
+
+    let tokens = lex("1..2 3.").into_vec();
+    assert_eq!(tokens.iter().map(|(t, _)| t).collect::<Vec<_>>(), vec![&T::Int, &T::Range, &T::Int, &T::Float]);
+    assert_eq!(tokens[0].1.get_abs_loc(), (0, 1));
+}
+
+#[test]
+fn trailing_context_only_consumes_the_first_part() {
+    lex_rule!{lex -> Token {
+        "a+" / "ab" => |_| A,
+        "a" => |_| B,
+        "b" => |_| C,
+        ws => |_| continue,
+        "x" / ws => |_| Eof,
+    }}
+
+    let tokens = lex("aaab x ").into_vec();
+    assert_eq!(tokens.iter().map(|(t, _)| t).collect::<Vec<_>>(), vec![&A, &B, &C, &Eof]);
+    assert_eq!(tokens[0].1.get_abs_loc(), (0, 2));
+}
+
+#[test]
+fn reject_falls_through_to_next_rule() {
+    #[derive(Debug, PartialEq)]
+    enum T {
+        Small(u8), Big(String), Space
+    }
+
+    lex_rule!{lex -> T {
+        r"[0-9]+" => |s| match s.parse() {
+            Ok(n) => T::Small(n),
+            Err(_) => reject!(),
+        },
+        r"[0-9]+" => |s| T::Big(s.to_string()),
+        ws => |_| T::Space,
+    }}
+
+    let tokens = lex("12 1234").into_vec();
+    assert_eq!(tokens.iter().map(|(t, _)| t).collect::<Vec<_>>(), vec![&T::Small(12), &T::Space, &T::Big("1234".to_string())]);
+    assert_eq!(tokens[2].1.get_abs_loc(), (3, 7));
+}
+
+#[test]
+fn emit_queues_extra_tokens() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "ab" => |_, _, loc| {
+            let (line, _, _, _) = loc.get_loc();
+            let (start_idx, _) = loc.get_abs_loc();
+            emit!(A, lexr::SrcLoc::new((line, 1), (line, 1), (start_idx, start_idx + 1)));
+            B
+        },
+        "c+" => |s| {
+            for _ in 0..s.len() {
+                emit!(C);
+            }
+            continue
+        },
+        on_eof => Eof,
+    }}
+
+    let tokens = lex("ab ccc").into_vec();
+    assert_eq!(tokens.iter().map(|(t, _)| t).collect::<Vec<_>>(), vec![&A, &B, &C, &C, &C, &Eof]);
+    assert_eq!(tokens[0].1.get_abs_loc(), (0, 1));
+    assert_eq!(tokens[1].1.get_abs_loc(), (0, 2));
+    assert_eq!(tokens[2].1.get_abs_loc(), (3, 6));
+}
+
+#[test]
+fn pushed_back_tokens_are_returned_first() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let mut lexer = lex("ab");
+    let (a, a_loc) = lexer.next().unwrap();
+    lexer.push_back(a, a_loc);
+    lexer.push_back(C, lexr::SrcLoc::DUMMY);
+    assert_eq!(lexer.into_vec(), vec![
+        (C, lexr::SrcLoc::DUMMY),
+        (A, a_loc),
+        (B, lexr::SrcLoc::new((1, 2), (1, 2), (1, 2))),
+    ]);
+}
+
+#[test]
+fn try_next_returns_errors_instead_of_panicking() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+    }}
+
+    let mut lexer = lex("ab");
+    assert_eq!(lexer.try_next(), Some(Ok((A, lexr::SrcLoc::new((1, 1), (1, 1), (0, 1))))));
+    let error = lexer.try_next().unwrap().unwrap_err();
+    assert_eq!(error, lexr::LexError::UnexpectedChar('b', lexr::SrcLoc::new((1, 2), (1, 2), (1, 1))));
+    assert_eq!(error.to_string(), "Unexpected character 'b' at 1:2");
+    assert_eq!(lexer.try_next(), None);
+}
+
+#[test]
+fn spec_describes_rules() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        bol "#" "[a-z]+" => |_| B,
+        "a+" / "b" => |_| A,
+        "[0-9]+" not_followed_by(r"\.") => |_| C,
+        on_eof => Eof,
+    }}
+
+    let spec = lex("").spec();
+    assert_eq!(spec.name, "lex");
+    assert_eq!(spec.on_eof, Some("Eof"));
+    assert_eq!(spec.rules.len(), 4);
+    assert_eq!(spec.rules[1].regex, "(?:#)(?:[a-z]+)");
+    assert!(spec.rules[1].bol);
+    assert_eq!(spec.rules[2].trailing.as_deref(), Some("b"));
+    assert_eq!(spec.rules[3].full_regex(), r"(?:[0-9]+)(?!\.)");
+    assert!(spec.rules[3].mentions("C"));
+    assert!(!spec.rules[3].mentions("A"));
+
+    let json = spec.to_textmate_json("source.test", &[("C", "constant.numeric")]);
+    assert!(json.contains(r#"{ "match": "^(?:(?:#)(?:[a-z]+))" }"#));
+    assert!(json.contains(r#"{ "match": "(?:[0-9]+)(?!\\.)", "name": "constant.numeric" }"#));
+}
+
+#[test]
+fn lexer_spec_draws_the_rules_as_dot() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "[a-z]+" => |_| A,
+        "if" => |_| B,
+        r#"\"[^"]*\""# => |_| C,
+        eof => |_| Eof,
+    }}
+
+    let dot = lex("").spec().to_dot();
+    assert!(dot.starts_with("digraph \"lex\" {\n"));
+    assert!(dot.contains(r##"  rule4 [label="4: r#\"\\\"[^\"]*\\\"\"#\nC"];"##));
+    assert!(dot.contains("  start -> rule1;\n  rule1 -> rule2 [label=\"no match\"];"));
+    assert!(dot.contains("  rule5 -> error [label=\"no match\"];"));
+    assert!(dot.contains("  rule2 -> rule3 [style=dashed, label=\"shadows\"];"));
+
+    lex_rule!{longest -> Token {
+        config { longest_match: true, eof_token: Eof },
+        "a" => |_| A,
+    }}
+    let dot = longest("").spec().to_dot();
+    assert!(dot.contains("  start -> on_eof [label=\"end of input\"];"));
+    assert!(dot.contains("  start -> rule1 [label=\"longest match\"];\n  start -> error [label=\"no match\"];"));
+}
+
+#[test]
+fn doc_comments_document_the_rules() {
+    lex_rule!{
+        /// The tokens of the language.
+        ///
+        /// Whitespace is skipped.
+        lex -> Token {
+            ws => |_| continue,
+            /// An identifier,
+            /// like `x`
+            ident => |_| A,
+            #[doc = " A number"]
+            "[0-9]+" => |_| B,
+        }
+    }
+
+    let spec = lex("").spec();
+    assert_eq!(spec.doc.as_deref(), Some("The tokens of the language.\n\nWhitespace is skipped."));
+    assert_eq!(spec.rules[0].doc, None);
+    assert_eq!(spec.rules[1].doc.as_deref(), Some("An identifier,\nlike `x`"));
+    assert_eq!(spec.rules[2].doc.as_deref(), Some("A number"));
+    assert_eq!(spec.to_markdown(), "# `lex`\n\nThe tokens of the language.\n\nWhitespace is skipped.\n\n\
+        The rules, in the order they are tried:\n\n- `ws`\n- `ident`: An identifier, like `x`\n- `\"[0-9]+\"`: A number\n");
+    assert_eq!(lex("a 1").into_token_vec(), vec![A, B]);
+}
+
+#[test]
+fn lexer_spec_is_available_without_a_lexer() {
+    lex_rule!{lex<'a>(keyword: &'a str) -> Token {
+        config { longest_match: true },
+        ws => |_| continue,
+        "a+" / "b" => |s| if s == keyword { A } else { B },
+    }}
+
+    let spec = lexr::lexer_spec!(lex);
+    assert!(std::ptr::eq(spec, lex("", "a").spec()));
+    assert!(spec.longest_match);
+    assert_eq!(spec.rules.iter().map(|rule| rule.name).collect::<Vec<_>>(), vec!["ws", r#""a+" / "b""#]);
+}
+
+#[test]
+fn fingerprint_changes_with_the_rules() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+    }}
+
+    lex_rule!{same -> Token {
+        "a" => |_| A,
+    }}
+
+    lex_rule!{changed -> Token {
+        "a" => |_| B,
+    }}
+
+    assert_eq!(lexr::lexer_fingerprint!(lex), lexr::lexer_spec!(lex).fingerprint());
+    // The fingerprint hashes the name of the lexer along with the rules
+    assert_ne!(lexr::lexer_fingerprint!(lex), lexr::lexer_fingerprint!(same));
+    assert_ne!(lexr::lexer_fingerprint!(lex, same), lexr::lexer_fingerprint!(same, lex));
+
+    let renamed = |name, spec: &lexr::LexerSpec| lexr::LexerSpec { name, ..spec.clone() };
+    let lex_spec = lexr::lexer_spec!(lex);
+    assert_eq!(renamed("same", lex_spec).fingerprint(), lexr::lexer_spec!(same).fingerprint());
+    assert_ne!(renamed("changed", lex_spec).fingerprint(), lexr::lexer_spec!(changed).fingerprint());
+
+    // The source of actions is hashed without the spacing chosen by the compiler, but not inside literals
+    let mut respaced = lex_spec.clone();
+    respaced.rules[0].action = " A\n";
+    assert_eq!(respaced.fingerprint(), lex_spec.fingerprint());
+    let action = |action, spec: &lexr::LexerSpec| {
+        let mut spec = spec.clone();
+        spec.rules[0].action = action;
+        spec.fingerprint()
+    };
+    assert_eq!(action("Kw(\"else if\")", lex_spec), action("Kw (\"else if\")\n", lex_spec));
+    assert_ne!(action("Kw(\"else if\")", lex_spec), action("Kw(\"elseif\")", lex_spec));
+    assert_ne!(action("Kw(r#\"\" a\"#)", lex_spec), action("Kw(r#\"\"a\"#)", lex_spec));
+    assert_ne!(action("Kw(' ')", lex_spec), action("Kw('\\t')", lex_spec));
+    assert_ne!(action("move x", lex_spec), action("movex", lex_spec));
+    respaced.rules[0].regex = "b".into();
+    assert_ne!(respaced.fingerprint(), lex_spec.fingerprint());
+}
+
+#[test]
+fn config_regex_options_apply_to_the_rules() {
+    lex_rule!{ascii -> Token {
+        config { unicode: false, regex_size_limit: 1 << 20 },
+        r"\w+" => |_| A,
+        _ => |_| B,
+    }}
+
+    lex_rule!{unicode -> Token {
+        r"\w+" => |_| A,
+        _ => |_| B,
+    }}
+
+    assert_eq!(ascii("aé").into_token_vec(), vec![A, B]);
+    assert_eq!(unicode("aé").into_token_vec(), vec![A]);
+    let spec = lexr::lexer_spec!(ascii);
+    assert_eq!(spec.regex_options, lexr::regex_cache::RegexOptions::new().unicode(false).size_limit(1 << 20));
+    assert!(spec.to_string().starts_with("ascii (unicode: false, regex_size_limit: 1048576)\n"));
+    assert!(spec.warm_up().is_ok());
+    assert!(lexr::lexer_spec!(unicode).regex_options.is_default());
+
+    lex_rule!{tiny -> Token {
+        config { regex_size_limit: 10 },
+        r"[a-z]{100}|\w+" => |_| A,
+    }}
+    assert!(lexr::lexer_spec!(tiny).warm_up().is_err());
+}
+
+#[test]
+fn config_longest_match_picks_the_longest_rule() {
+    lex_rule!{lex -> Token {
+        config { longest_match: true, eof_token: Eof },
+        lit "a" => A,
+        "a+" => |_| B,
+        "a+b" => |s| if s.len() > 3 { reject!() } else { C },
+    }}
+
+    assert_eq!(lex("a").into_token_vec(), vec![A, Eof]);
+    assert_eq!(lex("aa").into_token_vec(), vec![B, Eof]);
+    assert_eq!(lex("aab").into_token_vec(), vec![C, Eof]);
+    // The rejected match is followed by the longest match of the later rules, of which there are none
+    assert!(matches!(lex("aaab").try_next(), Some(Err(lexr::LexError::UnexpectedChar('a', _)))));
+    assert!(lex("").spec().longest_match);
+    assert!(lex("").describe().starts_with("lex (longest match)\n"));
+
+    lex_rule!{first -> Token {
+        config { longest_match: false },
+        lit "a" => A,
+        "a+" => |_| B,
+        "a+" => |_| C,
+    }}
+
+    assert_eq!(first("aa").into_token_vec(), vec![A, A]);
+    assert_eq!(first("").spec().unreachable_rules().len(), 1);
+}
+
+#[test]
+fn config_positions_false_tracks_only_offsets() {
+    lex_rule!{lex -> Token {
+        config { positions: false },
+        ws => |_| continue,
+        bol "a" => |_| A,
+        "a" => |_| B,
+        "é" => |_| C,
+    }}
+
+    let source = "a a\né\na";
+    let tokens = lex(source).into_vec();
+    assert_eq!(tokens.iter().map(|(token, _)| token).collect::<Vec<_>>(), [&A, &B, &C, &A]);
+    assert_eq!(tokens[2].1, lexr::SrcLoc::new((0, 0), (0, 0), (4, 6)));
+    assert_eq!(lexr::SrcLoc::from_offsets(source, tokens[2].1.get_abs_loc()).to_string(), "2:1");
+    assert_eq!(lexr::SrcLoc::from_offsets(source, (0, 3)), lexr::SrcLoc::new((1, 1), (1, 3), (0, 3)));
+    assert!(lex("").describe().starts_with("lex (no positions)\n"));
+    let mut lexer = lex("a b");
+    lexer.next();
+    assert!(matches!(lexer.try_next(), Some(Err(lexr::LexError::UnexpectedChar('b', loc))) if loc.get_loc() == (0, 0, 0, 0)));
+    assert!(!lexr::LexBuf::new("a").without_positions().tracks_positions());
+}
+
+#[test]
+fn cancelled_lexer_stops_with_error() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let token = lexr::CancelToken::new();
+    let mut lexer = lex(lexr::LexBuf::new("abab").with_cancel(token.clone()));
+    assert_eq!(lexer.try_next(), Some(Ok((A, lexr::SrcLoc::new((1, 1), (1, 1), (0, 1))))));
+    token.cancel();
+    assert_eq!(lexer.try_next(), Some(Err(lexr::LexError::Cancelled(lexr::SrcLoc::new((1, 2), (1, 2), (1, 1))))));
+    assert_eq!(lexer.try_next(), None);
+
+    let lexer = lex(lexr::LexBuf::new("abab").with_cancel(token));
+    assert_eq!(lexer.into_token_vec(), vec![]);
+}
+
+#[test]
+fn budget_limits_matches_and_rejects() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "b" => |_| reject!(),
+        "b" => |_| B,
+    }}
+
+    let mut lexer = lex(lexr::LexBuf::new("aab").with_budget(lexr::Budget::new().max_matches(2)));
+    assert_eq!(lexer.try_next().map(|result| result.map(|(token, _)| token)), Some(Ok(A)));
+    assert_eq!(lexer.try_next().map(|result| result.map(|(token, _)| token)), Some(Ok(A)));
+    assert_eq!(lexer.try_next(), Some(Err(lexr::LexError::BudgetExceeded(lexr::SrcLoc::new((1, 3), (1, 3), (2, 2))))));
+    assert_eq!(lexer.try_next(), None);
+
+    // Each b is rejected once
+    let budget = lexr::Budget::new().max_rejects(1);
+    let mut lexer = lex(lexr::LexBuf::new("bab").with_budget(budget));
+    assert_eq!(lexer.try_next().map(|result| result.map(|(token, _)| token)), Some(Ok(B)));
+    assert_eq!(lexer.try_next().map(|result| result.map(|(token, _)| token)), Some(Ok(A)));
+    assert!(matches!(lexer.try_next(), Some(Err(lexr::LexError::BudgetExceeded(_)))));
+
+    let budget = lexr::Budget::new().timeout(std::time::Duration::ZERO);
+    let mut lexer = lex(lexr::LexBuf::new("a").with_budget(budget));
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    assert_eq!(lexer.try_next(), Some(Err(lexr::LexError::BudgetExceeded(lexr::SrcLoc::new((1, 1), (1, 1), (0, 0))))));
+}
+
+#[test]
+fn resumed_lexer_continues_locations() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+        on_eof => Eof,
+    }}
+
+    let mut lexer = lex("a\n");
+    assert_eq!(lexer.by_ref().map(|(token, _)| token).collect::<Vec<_>>(), vec![A, Eof]);
+    assert!(lexer.is_finished());
+
+    lexer.resume("b a\n");
+    let tokens: Vec<_> = lexer.by_ref().map(|(token, loc)| (token, loc.to_string())).collect();
+    assert_eq!(tokens, vec![(B, "2:1".to_string()), (A, "2:3".to_string()), (Eof, "3:1".to_string())]);
+
+    // The rest of an entry with an error is skipped
+    lexer.resume("ac b\n");
+    assert_eq!(lexer.try_next().map(|result| result.map(|(token, _)| token)), Some(Ok(A)));
+    let Some(Err(error)) = lexer.try_next() else { panic!("Expected an error") };
+    assert_eq!(error.to_string(), "Unexpected character 'c' at 3:2");
+
+    lexer.resume("b\n");
+    let tokens: Vec<_> = lexer.map(|(token, loc)| (token, loc.to_string(), loc.get_abs_loc())).collect();
+    assert_eq!(tokens, vec![(B, "4:1".to_string(), (11, 12)), (Eof, "5:1".to_string(), (13, 13))]);
+}
+
+#[test]
+fn session_locates_entries() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let mut session = lexr::Session::new();
+    let mut lexer = lex("");
+    let mut locs = Vec::new();
+    // The second entry continues the line of the first
+    for entry in ["a b", " a\nb\n", "\n\n  a"] {
+        assert_eq!(session.add(entry), session.len());
+        lexer.resume(entry);
+        locs.extend(lexer.by_ref().map(|(_, loc)| session.locate(loc).to_string()));
+    }
+    assert_eq!(locs, [
+        "input 1, line 1, column 1",
+        "input 1, line 1, column 3",
+        "input 2, line 1, column 2",
+        "input 2, line 2, column 1",
+        "input 3, line 3, column 3",
+    ]);
+}
+
+#[test]
+fn lex_session_restarts_on_each_input() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+        eof => |_| Eof,
+    }}
+
+    let mut session = lexr::LexSession::new(|buf| lex(buf).boxed());
+    let locs = |tokens: &[(Token, lexr::SrcLoc)]| tokens.iter().map(|(_, loc)| loc.to_string()).collect::<Vec<_>>();
+    assert_eq!(locs(session.lex("a\nb").unwrap()), ["1:1", "2:1", "2:2"]);
+    // Locations count from the start of each input, also after an error
+    assert!(matches!(session.lex("b c a"), Err(lexr::LexError::UnexpectedChar('c', _))));
+    assert_eq!(locs(session.lex(" b").unwrap()), ["1:2", "1:3"]);
+    let input = String::from("ab");
+    assert_eq!(session.lex(&input).unwrap().iter().map(|(token, _)| token).collect::<Vec<_>>(), [&A, &B, &Eof]);
+    drop(input);
+
+    let interner = session.interner();
+    let x = interner.intern("x");
+    assert_eq!((interner.intern("y"), interner.intern("x")), (lexr::Symbol(1), x));
+    assert_eq!(interner.resolve(x), "x");
+}
+
+#[test]
+fn progress_is_reported_periodically() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "bbb" => |_| B,
+    }}
+
+    let mut reports = Vec::new();
+    let tokens = lex(lexr::LexBuf::new("abbbaaa").with_progress(3, |done, total| reports.push((done, total)))).into_token_vec();
+    assert_eq!(tokens, vec![A, B, A, A, A]);
+    assert_eq!(reports, vec![(0, 7), (4, 7), (7, 7)]);
+}
+
+#[test]
+fn lazy_utf8_buffers_are_checked_as_they_are_lexed() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "[a-zé]+" => |_| A,
+    }}
+
+    // The first megabyte ends within an `é`
+    let mut bytes = "é ".repeat(400_000).into_bytes();
+    let mut reports = Vec::new();
+    let buf = lexr::LexBuf::from_utf8_lazy(&bytes).without_positions().with_progress(usize::MAX, |done, total| reports.push((done, total)));
+    assert_eq!(lex(buf).count(), 400_000);
+    assert_eq!(reports, vec![(0, 1_200_000), (1_200_000, 1_200_000)]);
+
+    bytes.extend(b"ab \xff cd");
+    let mut lexer = lex(lexr::LexBuf::from_utf8_lazy(&bytes).without_positions());
+    let tokens = std::iter::from_fn(|| lexer.try_next()?.ok()).count();
+    assert_eq!(tokens, 400_001);
+    let mut lexer = lex(lexr::LexBuf::from_utf8_lazy(b"ab \xff"));
+    assert_eq!(lexer.try_next().unwrap().unwrap().0, A);
+    assert_eq!(lexer.try_next().unwrap().unwrap_err().to_string(), "Invalid UTF-8 at 1:4");
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mapped_sources_are_checked_as_they_are_lexed() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "[a-z]+" => |_| A,
+    }}
+
+    let path = std::env::temp_dir().join(format!("lexr_mapped_{}.txt", std::process::id()));
+    std::fs::write(&path, b"ab cd\n\xfe").unwrap();
+    let source = lexr::MappedSource::open(&path).unwrap();
+    assert_eq!(source.as_bytes().len(), 7);
+    let mut lexer = lex(&source);
+    assert_eq!(lexer.try_next().unwrap().unwrap().0, A);
+    assert_eq!(lexer.try_next().unwrap().unwrap().0, A);
+    assert_eq!(lexer.try_next(), Some(Err(lexr::LexError::InvalidUtf8(lexr::SrcLoc::new((2, 1), (2, 1), (6, 6))))));
+
+    drop(lexer);
+    drop(source);
+    std::fs::remove_file(&path).unwrap();
+    assert!(lexr::MappedSource::open(&path).is_err());
+}
+
+#[test]
+fn chunked_lexer_matches_contiguous_lexing() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a+" => |_| A,
+        "b" => |_| B,
+        "\"[^\"]*\"" => |_| C,
+    }}
+
+    let text = format!("aa b\naaa \"{}\"\nb aaaa  b\n", "x".repeat(50)).repeat(5);
+    let expected = lex(&text).into_vec();
+    let chunked: Vec<_> = lexr::ChunkedLexer::new(&text, |buf| lex(buf).into_result_vec()).window(16, 4).collect();
+    assert_eq!(chunked, expected);
+
+    let mut errors = lexr::ChunkedLexer::new("aaaa b c aaaa", |buf| lex(buf).into_result_vec()).window(4, 1);
+    assert_eq!(errors.try_next().map(|r| r.unwrap().0), Some(A));
+    assert_eq!(errors.try_next().map(|r| r.unwrap().0), Some(B));
+    assert!(matches!(errors.try_next(), Some(Err(lexr::LexError::UnexpectedChar('c', _)))));
+    assert_eq!(errors.try_next(), None);
+}
+
+#[test]
+fn chunked_lexer_keeps_anchors_and_eof_at_the_ends_of_the_text() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        bol "#" => |_| A,
+        "[a-z#]" eol => |_| B,
+        "[a-z#]" => |_| C,
+        eof => |_| Eof,
+    }}
+
+    let text = "ab#cd\n#ab#c\nde";
+    let expected = lex(text).into_token_vec();
+    assert_eq!(expected.iter().filter(|token| **token == Eof).count(), 1);
+    for margin in [0, 1] {
+        let chunked: Vec<_> = lexr::ChunkedLexer::new(text, |buf| lex(buf).into_result_vec()).window(2, margin).map(|(token, _)| token).collect();
+        assert_eq!(chunked, expected);
+    }
+}
+
+#[test]
+fn lexers_take_any_string_source() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let shared: std::rc::Rc<str> = "ab".into();
+    assert_eq!(lex(&shared).into_token_vec(), vec![A, B]);
+    let cow = std::borrow::Cow::Borrowed("ba");
+    assert_eq!(lex(&cow).into_token_vec(), vec![B, A]);
+    let bytes = lexr::LexBuf::from_utf8(b"aa").unwrap();
+    assert_eq!(lex(bytes).into_token_vec(), vec![A, A]);
+    assert!(lexr::LexBuf::from_utf8(&[0xff]).is_err());
+
+    assert_eq!(lex(b"ab").into_token_vec(), vec![A, B]);
+    let bytes = b"ba".to_vec();
+    assert_eq!(lex(&bytes).into_token_vec(), vec![B, A]);
+    assert_eq!(lex(bytes.as_slice()).into_token_vec(), vec![B, A]);
+    let mut lexer = lex(b"a\xffb".as_slice());
+    assert_eq!(lexer.try_next().unwrap().unwrap().0, A);
+    assert!(matches!(lexer.try_next(), Some(Err(lexr::LexError::InvalidUtf8(_)))));
+}
+
+#[test]
+fn owned_lexer_outlives_the_read_source() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a+" => |_| A,
+    }}
+
+    fn read() -> lexr::OwnedLexer<Token> {
+        let source = "aa a x".to_string();
+        lexr::OwnedLexer::new(source, |buf| lex(buf).boxed())
+    }
+
+    let mut lexer = read();
+    let (token, loc) = lexer.try_next().unwrap().unwrap();
+    assert_eq!((token, lexer.slice(loc)), (A, "aa"));
+    assert_eq!(lexer.next().map(|(token, _)| token), Some(A));
+    assert!(matches!(lexer.try_next(), Some(Err(lexr::LexError::UnexpectedChar('x', _)))));
+}
+
+#[test]
+fn forked_lexer_does_not_affect_original() {
+    #[derive(Clone, Debug, PartialEq)]
+    enum Tok { A, B(usize) }
+
+    lex_rule!{lex(n: usize) -> Tok {
+        "a" => |_| { emit!(Tok::B(n)); Tok::A },
+        "b" => |_| Tok::B(n),
+    }}
+
+    let mut lexer = lex("aba", 1);
+    assert_eq!(lexer.next_token(), Some(Tok::B(1)));
+    let fork = lexer.fork();
+    assert_eq!(fork.into_token_vec(), vec![Tok::A, Tok::B(1), Tok::B(1), Tok::A]);
+    assert_eq!(lexer.into_token_vec(), vec![Tok::A, Tok::B(1), Tok::B(1), Tok::A]);
+
+    // Lexers with tokens that can not be cloned can still be defined
+    lex_rule!{not_clone -> Token {
+        "a" => |_| A,
+    }}
+    assert_eq!(not_clone("a").into_token_vec(), vec![A]);
+}
+
+#[test]
+fn describe_lists_rules_in_order() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        bol "#" eol => |_| B,
+        "a+" / "b" => |_| A,
+        "[0-9]+" not_followed_by(r"\.") => |_| C,
+        on_eof => Eof,
+    }}
+
+    assert_eq!(lex("").describe(), [
+        r##"lex"##,
+        r##"  #  pattern                          action"##,
+        r##"  1  "[ \\n\\r\\t]"                   continue"##,
+        r##"  2  bol "#" eol                      B"##,
+        r##"  3  "a+" / "b"                       A"##,
+        r##"  4  "[0-9]+" not_followed_by("\\.")  C"##,
+        r##"     on_eof                           Eof"##,
+    ].join("\n"));
+}
+
+#[test]
+fn generated_code_does_not_clash_with_user_names() {
+    #[derive(Debug, PartialEq)]
+    #[allow(dead_code)]
+    enum Named { Some, None, Ok, Err }
+    use Named::*;
+
+    // The macro is used through its path, and the args are named like internals of the lexer
+    lexr::lex_rule!{lex(buf: u8, queue: u8, regex: u8, name: u8) -> Named {
+        "a" / "b" => |_| { assert_eq!((buf, queue, regex, name), (1, 2, 3, 4)); Some },
+        "b" followed_by("c") => |_| None,
+        "[bc]" => |_| Ok,
+    }}
+
+    let tokens = lex("abbc", 1, 2, 3, 4).into_token_vec();
+    assert_eq!(tokens, vec![Some, Ok, None, Ok]);
+}
+
+#[test]
+fn named_binders_give_buf_and_loc() {
+    lex_rule!{lex -> (Token, usize) {
+        ws => |_| continue,
+        "a" => |_, @loc loc| (A, loc.get_abs_loc().0),
+        "#" => |_, @buf buf, @loc loc| { comment(buf).deplete(); (C, loc.get_abs_loc().0) },
+    }}
+
+    lex_rule!{comment -> () {
+        "\n" => |_| break,
+        _ => |_| continue,
+    }}
+
+    let tokens = lex("a #b\n a").into_token_vec();
+    assert_eq!(tokens, vec![(A, 0), (C, 2), (A, 6)]);
+}
+
+#[test]
+fn tokens_know_the_rule_that_produced_them() {
+    lex_rule!{lex -> (Token, &'static str) {
+        ws => |_| continue,
+        "[ab]" => |_, @rule rule| (A, rule.name),
+        "c" => |_| { emit!((C, "emitted")); (B, "returned") },
+        on_eof => (Eof, "eof"),
+    }}
+
+    let mut lexer = lex("a c");
+    lexer.push_back((C, "pushed"), lexer.end_loc());
+    let rules: Vec<_> = lexer.with_rules().map(|(token, _, rule)| (token, rule.map(|rule| (rule.index, rule.name)))).collect();
+    assert_eq!(rules, vec![
+        ((C, "pushed"), None),
+        ((A, r#""[ab]""#), Some((1, r#""[ab]""#))),
+        ((C, "emitted"), Some((2, r#""c""#))),
+        ((B, "returned"), Some((2, r#""c""#))),
+        ((Eof, "eof"), Some((3, "on_eof"))),
+    ]);
+    assert_eq!(lex("").spec().rule(lexr::RuleId::new(1, "")).unwrap().regex, "[ab]");
+}
+
+#[test]
+fn unreachable_rules_are_reported() {
+    lex_rule!{lex -> Token {
+        "[a-z]+" => |_| A,
+        "fn" => |_| B,
+        "[0-9]+" => |s| if s.len() > 3 { reject!() } else { C },
+        "12" => |_| C,
+        _ => |_| continue,
+        "[A-Z]" => |_| A,
+        eof => |_| Eof,
+    }}
+
+    let spec = lex("").spec();
+    assert_eq!(spec.unreachable_rules(), vec![
+        lexr::UnreachableRule { index: 1, shadowed_by: 0 },
+        lexr::UnreachableRule { index: 5, shadowed_by: 4 },
+    ]);
+    assert_eq!(spec.warnings().unwrap().lines().next().unwrap(),
+        r#"warning: rule 2 ("fn") in lex can never match, as rule 1 ("[a-z]+") always matches first"#);
+}
+
+#[test]
+fn finding_unreachable_rules_compiles_nothing_into_the_regex_cache() {
+    lex_rule!{lex -> Token {
+        "unreach(able)?" => |_| A,
+        "unreach" => |_| B,
+    }}
+
+    let spec = lexr::lexer_spec!(lex);
+    assert_eq!(spec.unreachable_rules(), vec![lexr::UnreachableRule { index: 1, shadowed_by: 0 }]);
+    assert!(!lexr::regex_cache::contains("^(?:unreach(able)?)"));
+}
+
+#[test]
+fn rules_at_the_end_of_input_are_reachable_after_a_wildcard() {
+    lex_rule!{lex -> Token {
+        _ => |_| A,
+        eof => |_| B,
+        "" eol => |_| C,
+        "x*" => |_| C,
+        "x" => |_| C,
+        on_eof => Eof,
+    }}
+
+    assert_eq!(lex("ab").into_token_vec(), vec![A, A, B, Eof]);
+    assert_eq!(lex("").spec().unreachable_rules(), vec![lexr::UnreachableRule { index: 4, shadowed_by: 0 }]);
+}
+
+#[test]
+fn alternatives_stay_within_their_fragment() {
+    lex_rule!{lex -> Token {
+        "a|b" => |_| A,
+        "c|x" "d" => |_| B,
+        _ => |_| C,
+    }}
+
+    // Without grouping, `^a|b` would match the `b` after any character, and `c|xd` a lone `c`
+    let tokens = lex("cbcdx").into_token_vec();
+    assert_eq!(tokens, vec![C, A, B, C]);
+}
+
+#[test]
+fn repetitions_apply_to_the_whole_fragment_before_them() {
+    const WORD: &str = "[a-z]+ ";
+    lex_rule!{lex -> Token {
+        WORD "*" "[0-9]" => |_| A,
+        "x|y" "{2}" => |_| B,
+        _ => |_| C,
+    }}
+
+    assert_eq!(lex("ab cd 1xyab1").into_token_vec(), vec![A, B, C, C, A]);
+    assert_eq!(lex("").spec().rules[0].regex, "(?:[a-z]+ )*(?:[0-9])");
+}
+
+#[test]
+fn verbatim_fragments_are_not_grouped() {
+    use lexr::verbatim;
+    lex_rule!{lex -> Token {
+        verbatim("(?i)") "ab" => |_| A,
+        "ab" => |_| B,
+    }}
+
+    let tokens = lex("AbaB").into_token_vec();
+    assert_eq!(tokens, vec![A, A]);
+}
+
+#[test]
+fn literal_rules_need_no_escaping() {
+    const ARROW: &str = "->";
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        lit "++" => A,
+        lit ARROW => |_| B,
+        lit "." / "[0-9]" => |_| C,
+        lit "." => Eof,
+        "[0-9]" => |_| continue,
+    }}
+
+    let tokens = lex("++ -> .5 .").into_token_vec();
+    assert_eq!(tokens, vec![A, B, C, Eof]);
+    assert_eq!(lex("").spec().rules[1].regex, r"\+\+");
+}
+
+#[test]
+fn custom_matchers_replace_the_regex() {
+    fn digits(input: &str) -> Option<usize> {
+        let len = input.bytes().take_while(u8::is_ascii_digit).count();
+        (len > 0).then_some(len)
+    }
+
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        matcher digits not_followed_by("[a-z]") => |_| A,
+        matcher |s: &str| s.starts_with("->").then_some(2) => |_| B,
+        matcher lexr::regex::Regex::new("^[a-z]+").unwrap() => |_| C,
+        _ => |_| Eof,
+    }}
+
+    assert_eq!(lex("12 -> ab 3c").into_token_vec(), vec![A, B, C, Eof, C]);
+    let spec = lexr::lexer_spec!(lex);
+    assert_eq!(spec.rules[1].matcher, Some("digits"));
+    assert_eq!(spec.rules[1].to_string(), r#"matcher digits not_followed_by("[a-z]")"#);
+    assert!(spec.unreachable_rules().is_empty());
+    assert!(spec.warm_up().is_ok());
+}
+
+#[test]
+fn identifier_shorthands_match_identifiers() {
+    lex_rule!{ascii -> String {
+        ws => |_| continue,
+        ident => |s| s.to_string(),
+        _ => |s| format!("<{s}>"),
+    }}
+
+    lex_rule!{unicode -> String {
+        ws => |_| continue,
+        unicode_ident => |s| s.to_string(),
+        _ => |s| format!("<{s}>"),
+    }}
+
+    assert_eq!(ascii("_a1 é1").into_token_vec(), vec!["_a1", "<é>", "<1>"]);
+    assert_eq!(unicode("_a1 é1 1").into_token_vec(), vec!["_a1", "é1", "<1>"]);
+}
+
+#[cfg(feature = "unicode-normalization")]
+#[test]
+fn normalizing_binders_make_identifiers_equal() {
+    lex_rule!{lex -> String {
+        ws => |_| continue,
+        "ﬁ" => |_, @nfkc text| text.into_owned(),
+        unicode_ident => |_, @loc _, @nfc ident| ident.into_owned(),
+    }}
+
+    let tokens = lex("caf\u{e9} cafe\u{301} ﬁ").into_token_vec();
+    assert_eq!(tokens, vec!["caf\u{e9}", "caf\u{e9}", "fi"]);
+}
+
+#[test]
+fn edits_apply_at_token_locations() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let source = "a b a";
+    let locs: Vec<_> = lex(source).map(|(_, loc)| loc).collect();
+    let mut edits = lexr::EditBuilder::new();
+    edits.replace(locs[2], "c").insert_before(locs[0], "(").insert_after(locs[2], ")").delete(locs[1]);
+    assert_eq!(edits.apply(source).unwrap(), "(a  c)");
+
+    edits.replace(locs[0].combine(locs[1]), "x");
+    let conflict = edits.apply(source).unwrap_err();
+    assert_eq!(conflict.first.range, 2..3);
+    assert_eq!(conflict.second.range, 0..3);
+    assert_eq!(conflict.to_string(), "Edits of bytes 2..3 and 0..3 overlap");
+}
+
+#[test]
+fn token_ids_survive_edits_elsewhere() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let mut tokens = lexr::StableTokens::new("a b a", lex("a b a").into_vec());
+    let old = tokens.ids().to_vec();
+
+    // Inserting shifts the tokens after the edit, but they keep their ids
+    tokens.update("a b b b a", lex("a b b b a").into_vec());
+    let ids = tokens.ids().to_vec();
+    assert_eq!(ids.len(), 5);
+    assert_eq!((ids[0], ids[1], ids[4]), (old[0], old[1], old[2]));
+    assert!(ids[2] > old[2] && ids[3] > ids[2]);
+
+    // A token with different text is new, even at the same position
+    tokens.update("a a b b a", lex("a a b b a").into_vec());
+    assert_eq!(tokens.ids()[0], ids[0]);
+    assert!(!ids.contains(&tokens.ids()[1]));
+    assert_eq!(&tokens.ids()[2..], &ids[2..]);
+}
+
+#[test]
+fn batches_keep_results_in_order() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let dir = std::env::temp_dir().join("lexr_batch_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut paths = Vec::new();
+    for i in 0..20 {
+        let path = dir.join(format!("{}.txt", i));
+        std::fs::write(&path, "a b ".repeat(i)).unwrap();
+        paths.push(path);
+    }
+    paths.push(dir.join("missing.txt"));
+
+    let report = lexr::batch::process_with_threads(
+        4,
+        &paths,
+        |source| lex(source).into_result_vec().into_iter().collect(),
+        |_, tokens| tokens.iter().filter(|(token, _)| *token == B).count(),
+    );
+
+    for (i, file) in report.files[..20].iter().enumerate() {
+        assert_eq!(file.path, paths[i]);
+        assert_eq!(*file.result.as_ref().unwrap(), i);
+    }
+    assert!(matches!(report.files[20].result, Err(lexr::batch::BatchError::Io(_))));
+    assert_eq!((report.stats.files, report.stats.failed, report.stats.tokens), (21, 1, 380));
+    assert_eq!(report.stats.bytes, 4 * 190);
+}
+
+#[test]
+fn diagnostics_are_reported_into_sinks() {
+    use lexr::{Diagnostic, DiagnosticSink, Severity};
+
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let tokens = lex("ab?a").into_vec_reporting(&mut diagnostics);
+    assert_eq!(tokens.len(), 2);
+    diagnostics.warning("X0001", "Two tokens", tokens[1].1);
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].code, "L0001");
+    assert_eq!(diagnostics[0].to_string(), "error[L0001]: Unexpected character '?' at 1:3");
+    assert_eq!(diagnostics[1].to_string(), "warning[X0001]: Two tokens at 1:2");
+
+    let mut severities = Vec::new();
+    lex("a!").into_vec_reporting(&mut |d: Diagnostic| severities.push(d.severity));
+    assert_eq!(severities, vec![Severity::Error]);
+}
+
+#[test]
+fn built_in_diagnostics_are_in_the_catalog() {
+    use lexr::{diagnostic::lookup, Diagnostic, CATALOG};
+
+    let codes: Vec<_> = CATALOG.iter().map(|info| info.code).collect();
+    let mut sorted = codes.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(codes, sorted);
+
+    let loc = lexr::SrcLoc::new((1, 1), (1, 1), (0, 1));
+    for diagnostic in [Diagnostic::from(lexr::LexError::UnexpectedChar('?', loc)), Diagnostic::from(lexr::LexError::Cancelled(loc))] {
+        assert_eq!(lookup(diagnostic.code).unwrap().severity, diagnostic.severity);
+    }
+    assert_eq!(lookup("L0001").unwrap().title, "Unexpected character");
+    assert!(lookup("L9999").is_none());
+}
+
+#[test]
+fn lints_check_each_line_once() {
+    use lexr::{codes, Diagnostic, LexBuf, Lints, Severity};
+
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let source = "a\n\tb \n  a\n \tb\r\na";
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let lints = Lints::new().trailing_whitespace(Severity::Note).mixed_indentation(Severity::Error);
+    let buf = LexBuf::new(source).with_lints(lints, |d| diagnostics.push(d));
+    let checkpoint = buf.checkpoint();
+    let mut lexer = lex(buf.share());
+    lexer.next();
+    lexer.next();
+    // Lexing the same text again does not report it again
+    buf.rewind(&checkpoint);
+    lexer.deplete();
+    drop(buf);
+
+    let found: Vec<_> = diagnostics.iter().map(|d| (d.code, d.severity, d.loc.to_string())).collect();
+    assert_eq!(found, vec![
+        (codes::TRAILING_WHITESPACE, Severity::Note, "2:3".to_string()),
+        (codes::MIXED_INDENTATION, Severity::Error, "3:1-2".to_string()),
+        (codes::MIXED_INDENTATION, Severity::Error, "4:1-2".to_string()),
+    ]);
+    assert_eq!(diagnostics[0].suggestions[0].apply(source).unwrap(), "a\n\tb\n  a\n \tb\r\na");
+    assert_eq!(diagnostics[2].message, "Indentation mixes tabs and spaces");
+}
+
+#[test]
+fn stuck_lexers_report_a_repro() {
+    lex_rule!{lex -> Token {
+        "a" => |_| A,
+        "b*" => |_| continue,
+    }}
+
+    let mut lexer = lex("ac");
+    assert_eq!(lexer.try_next().map(|r| r.unwrap().0), Some(A));
+    let Some(Err(lexr::LexError::Stuck(repro))) = lexer.try_next() else { panic!("Expected the lexer to be stuck") };
+    assert_eq!(repro.lexer, "lex");
+    assert_eq!(repro.fingerprint, lexer.spec().fingerprint());
+    assert_eq!((repro.rule.index, repro.rule.name), (1, "\"b*\""));
+    assert_eq!(repro.loc.to_string(), "1:2");
+    assert_eq!(repro.input, "c");
+    assert_eq!(lexer.try_next(), None);
+
+    // Returning a token for the empty input is stuck too, once the rule matches it again
+    lex_rule!{lex_tokens -> Token {
+        "a*" => |_| A,
+    }}
+    let mut lexer = lex_tokens("b");
+    assert_eq!(lexer.try_next().map(|r| r.unwrap().0), Some(A));
+    let Some(Err(error)) = lexer.try_next() else { panic!("Expected the lexer to be stuck") };
+    assert_eq!(error.to_string(), format!(r#"Lexer is stuck, as "a*" keeps matching the empty input: lexer lex_tokens, rules {:016x}, rule 1 at 1:1, input "b""#,
+        lexr::lexer_spec!(lex_tokens).fingerprint()));
+
+    // Rules matching the empty input are fine when they reject it
+    lex_rule!{lex_spaces -> Token {
+        " *" => |s| if s.is_empty() { reject!() } else { continue },
+        "a" => |_| A,
+    }}
+    assert_eq!(lex_spaces("  a a").into_token_vec(), vec![A, A]);
+}
+
+#[test]
+fn identical_patterns_share_compiled_regexes() {
+    lex_rule!{lex_one -> Token {
+        "cached_[0-9]+" => |_| A,
+    }}
+    lex_rule!{lex_other -> Token {
+        "cached_[0-9]+" => |_| B,
+    }}
+
+    assert!(!lexr::regex_cache::contains("^(?:cached_[0-9]+)"));
+    assert_eq!(lex_one("cached_1").into_token_vec(), vec![A]);
+    assert!(lexr::regex_cache::contains("^(?:cached_[0-9]+)"));
+    // The other lexer uses the regex compiled for the first
+    let cached = lexr::regex_cache::get("^(?:cached_[0-9]+)").unwrap();
+    assert_eq!(lex_other("cached_2").into_token_vec(), vec![B]);
+    assert!(cached.is_match("cached_3"));
+}
+
+#[test]
+fn warming_up_compiles_all_rules() {
+    lex_rule!{lex -> Token {
+        "warm_[a-z]+" not_followed_by("warm_lookahead") => |_| A,
+        "warm_[0-9]+" / "warm_trailing" => |_| B,
+    }}
+
+    lex("").warm_up().unwrap();
+    assert!(lexr::regex_cache::contains("^(?:warm_[a-z]+)"));
+    assert!(lexr::regex_cache::contains("^(?:warm_lookahead)"));
+    assert!(lexr::regex_cache::contains("^(warm_[0-9]+)(?:warm_trailing)"));
+
+    lex_rule!{lex_invalid -> Token {
+        "a" => |_| A,
+        "(" => |_| B,
+    }}
+    assert!(lex_invalid("").warm_up().is_err());
+}
+
+#[test]
+fn common_shapes_are_scanned_without_regexes() {
+    use lexr::scan::Matcher;
+
+    for (regex, scanned) in [
+        (r"[ \n\r\t]", true),
+        (lexr::classes::IDENT, true),
+        ("[0-9]+", true),
+        ("(?:[a-z])(?:[a-z0-9_]*)", true),
+        (r"[a\-z]+", true),
+        ("[a-z]+[0-9]", false),
+        ("[^a-z]", false),
+        ("[a-z]|[0-9]", false),
+        ("(?i)[a-z]", false),
+        (r"\p{XID_Start}", false),
+    ] {
+        assert_eq!(Matcher::new(regex).unwrap().is_scanner(), scanned, "{}", regex);
+    }
+
+    // Scanners match like the regexes they replace
+    for (regex, input) in [
+        (lexr::classes::IDENT, "_abc1 d"), (lexr::classes::IDENT, "1abc"), ("[0-9]+", "123a"), ("[0-9]+", "a"),
+        ("[a-c]*", "abcd"), ("[a-c]*", "éa"), (r"[a\-z]+", "a-zb"), ("[a-]+", "a-b"), ("(?:[a-z])(?:[0-9])", "a1b"),
+    ] {
+        let expected = lexr::regex::Regex::new(&format!("^(?:{})", regex)).unwrap().find(input).map(|m| m.end());
+        assert_eq!(Matcher::new(regex).unwrap().find(input), expected, "{} on {}", regex, input);
+    }
+
+    // Long runs are tested eight bytes at a time, up to any byte ending them, and sets of many ranges a byte at a time
+    for regex in [r"[ \n\r\t]*", "[A-Za-z0-9_]+", "[a-a]*", "[\t-\x7f]*", "[acegikmoq]*"] {
+        let expected_regex = lexr::regex::Regex::new(&format!("^(?:{})", regex)).unwrap();
+        let matcher = Matcher::new(regex).unwrap();
+        assert!(matcher.is_scanner(), "{}", regex);
+        for run in 0..20 {
+            for end in ["", "é", "\u{80}", "-", "\0", "\x7f", "Z", "~"] {
+                let input = format!("{}{}aa", "aq_ \tZ9\n".chars().cycle().skip(run % 3).take(run).collect::<String>(), end);
+                let expected = expected_regex.find(&input).map(|m| m.end());
+                assert_eq!(matcher.find(&input), expected, "{} on {:?}", regex, input);
+            }
+        }
+    }
+
+    lex_rule!{lex -> String {
+        ws => |_| continue,
+        ident => |s| s.to_string(),
+        "[0-9]+" => |s| s.to_string(),
+        _ => |s| s.to_string(),
+    }}
+    assert_eq!(lex("x1 = 42é").into_token_vec(), vec!["x1", "=", "42", "é"]);
+}
+
+#[test]
+fn token_shapes_report_large_variants() {
+    use lexr::shape::{self, TokenShape};
+
+    #[allow(dead_code)]
+    #[derive(TokenShape)]
+    #[token_shape(max_size = 32)]
+    enum Shaped {
+        Unit,
+        Small(u8, u16),
+        Pair { a: u64, b: u64 },
+        Text(String),
+    }
+
+    let report = shape::analyze::<Shaped>(16);
+    assert_eq!(report.name, "Shaped");
+    assert_eq!(report.size, std::mem::size_of::<Shaped>());
+    let sizes: Vec<_> = report.variants.iter().map(|v| (v.name, v.size)).collect();
+    assert_eq!(sizes, vec![("Unit", 0), ("Small", 4), ("Pair", 16), ("Text", 24)]);
+    assert_eq!(report.variants[2].fields, vec!["u64", "u64"]);
+    assert_eq!(report.oversized().map(|v| v.name).collect::<Vec<_>>(), vec!["Text"]);
+    assert!(shape::analyze::<Shaped>(24).warnings().is_none());
+}
+
+#[test]
+fn span_only_tokens_read_text_from_the_source() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a+" => |_| A,
+        "b+" => |_| B,
+    }}
+
+    let source = "aa bbb\na";
+    let tokens: Vec<_> = lex(source).with_text(source).collect();
+    assert_eq!(tokens.iter().map(|(token, _, text)| (token, *text)).collect::<Vec<_>>(), vec![(&A, "aa"), (&B, "bbb"), (&A, "a")]);
+    assert_eq!(tokens[2].1.text(source), "a");
+    assert!(tokens[0].1.parse::<u32>(source).is_err());
+}
+
+#[test]
+fn validated_lexer_accepts_consistent_locations() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "é+" => |_| A,
+        "ab" => |_, _, loc| {
+            let (start, end) = loc.get_abs_loc();
+            emit!(A, lexr::SrcLoc::new(loc.start(), loc.start(), (start, start + 1)));
+            emit!(B, lexr::SrcLoc::new(loc.end(), loc.end(), (start + 1, end)));
+            continue
+        },
+        "c" => |_| { emit!(C); C },
+        on_eof => Eof,
+    }}
+
+    let source = "éé\n ab c";
+    let tokens: Vec<_> = lex(source).validated(source).map(|(token, _)| token).collect();
+    assert_eq!(tokens, vec![A, A, B, C, C, Eof]);
+}
+
+#[test]
+#[should_panic(expected = "Invalid location 1:1 of token 2: It starts at byte 0, before the previous token ends at byte 2")]
+fn validated_lexer_rejects_overlapping_locations() {
+    lex_rule!{lex -> Token {
+        "ab" => |_, _, loc| {
+            emit!(A, loc);
+            emit!(B, lexr::SrcLoc::new(loc.start(), loc.start(), (0, 1)));
+            C
+        },
+    }}
+
+    lex("ab").validated("ab").for_each(drop);
+}
+
+#[test]
+fn src_loc_display_options() {
+    let loc = lexr::SrcLoc::new((2, 3), (2, 5), (7, 10));
+    assert_eq!(loc.display().to_string(), "2:3-5");
+    assert_eq!(loc.display().path("src/main.rs").to_string(), "src/main.rs:2:3-5");
+    assert_eq!(loc.display().path("src/main.rs").start_only().to_string(), "src/main.rs:2:3");
+    assert_eq!(loc.display().offsets().to_string(), "2:3-5 (7..10)");
+    assert_eq!(loc.display().zero_based().to_string(), "1:2-4");
+
+    let path = std::path::PathBuf::from("input.txt");
+    let loc = lexr::SrcLoc::new((1, 1), (3, 2), (0, 12));
+    assert_eq!(loc.display().zero_based().path(&path).to_string(), "input.txt:0:0-2:1");
+}
+
+#[test]
+fn src_loc_accessors_count_from_zero_or_one() {
+    let loc = lexr::SrcLoc::new((2, 3), (4, 1), (7, 20));
+    assert_eq!((loc.line0(), loc.col0()), (1, 2));
+    assert_eq!((loc.line1(), loc.col1()), (2, 3));
+    assert_eq!((loc.start0(), loc.end0()), ((1, 2), (3, 0)));
+    assert_eq!(lexr::SrcLoc::new0((1, 2), (3, 0), (7, 20)), loc);
+    assert_eq!(lexr::SrcLoc::DUMMY.start0(), (0, 0));
+
+    // An application keeps the base of its consumer, and gives it where locations are converted or displayed
+    let base = lexr::Base::default();
+    assert_eq!((loc.start_in(base), loc.end_in(base)), ((2, 3), (4, 1)));
+    let base = lexr::Base::Zero;
+    assert_eq!((loc.start_in(base), loc.end_in(base)), (loc.start0(), loc.end0()));
+    assert_eq!(loc.display().base(base).to_string(), loc.display().zero_based().to_string());
+}
+
+mod conformance {
+    use lexr::{lex_rule, lex_tests};
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Num(u32), Plus,
+    }
+    use Token::*;
+
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "[0-9]+" => |s| Num(s.parse().unwrap()),
+        lit "+" => Plus,
+    }}
+
+    lex_tests!{lexer = lex;
+        "" => [];
+        "1+2" => [Num(1), Plus, Num(2)];
+        "10 + 2" => spans [(Num(10), "1:1-2"), (Plus, "1:4"), (Num(2), "1:6")];
+        "1\n+" => spans [(Num(1), "1:1"), (Plus, "2:1")];
+        "1 - 2" => error "1:3";
+        "1 - 2" => error '-' "1:3";
+        "1 +\n-" => [Num(1), Plus] then error "2:1";
+        "@" => [] then error "1:1";
+        "12" => not [Num(1), Num(2)];
+        "1 2" => not [Num(12)];
+    }
+
+    lex_rule!{lex_zero -> Token {
+        ws => |_| continue,
+        "[0-9]+" => |s| Num(s.parse().unwrap()),
+    }}
+
+    lex_tests!{lexer = lex_zero; base = Zero;
+        "10\n 2" => spans [(Num(10), "0:0-1"), (Num(2), "1:1")];
+        "1 +" => [Num(1)] then error "0:2";
+    }
+
+    #[test]
+    #[should_panic(expected = "expected [Num(1), Plus]\n     found [Num(1) at 1:1, Num(2) at 1:3]")]
+    fn wrong_tokens_are_reported() {
+        lexr::conformance::check_tokens(lexr::Base::One, "1 2", lex("1 2").into_result_vec(), vec![(Num(1), None), (Plus, None)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Lexing \"1+\" succeeded, but was expected to fail at 1:2")]
+    fn missing_errors_are_reported() {
+        lexr::conformance::check_error(lexr::Base::One, "1+", lex("1+").into_result_vec(), None, None, "1:2");
+    }
+
+    #[test]
+    #[should_panic(expected = "Lexing \"1 -\" failed at 1:3, but after [Num(1)] instead of [Num(1), Plus]")]
+    fn tokens_before_errors_are_checked() {
+        lexr::conformance::check_error(lexr::Base::One, "1 -", lex("1 -").into_result_vec(), Some(vec![Num(1), Plus]), None, "1:3");
+    }
+
+    #[test]
+    #[should_panic(expected = "Lexing \"12\" produced [Num(12)], which it was expected not to")]
+    fn unexpected_tokens_are_reported() {
+        lexr::conformance::check_not_tokens("12", lex("12").into_result_vec(), vec![Num(12)]);
+    }
+}
+
+#[test]
+fn corpus_stats_count_kinds_and_lengths() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "a+" => |_| A,
+        "b+" => |_| B,
+    }}
+
+    let mut stats = lexr::CorpusStats::new();
+    for source in ["a bb a", "bbbbb  a"] {
+        stats.add(source, &lex(source).into_vec(), |token| format!("{:?}", token));
+    }
+    assert_eq!((stats.sources, stats.bytes, stats.token_bytes, stats.tokens()), (2, 14, 10, 5));
+    let kinds: Vec<_> = stats.kinds().iter().map(|k| (k.kind.as_str(), k.count, k.bytes, k.max_len)).collect();
+    assert_eq!(kinds, vec![("A", 3, 3, 1), ("B", 2, 7, 5)]);
+    assert_eq!(stats.length_histogram(), &[0, 3, 1, 0, 1]);
+    assert_eq!(lexr::corpus::length_bucket(4), 3);
+    assert_eq!(lexr::corpus::length_bucket(5), 4);
+
+    assert_eq!(stats.to_string(), "\
+5 tokens in 2 sources of 14 bytes, 28.6% trivia
+  kind     count   share  avg len  max len
+  A            3   60.0%      1.0        1
+  B            2   40.0%      3.5        5
+
+     length     count
+          1         3  ########################
+          2         1  ########
+        5-8         1  ########");
+}
+
+#[test]
+fn streams_separate_trivia_from_significant_tokens() {
+    lex_rule!{lex -> Token {
+        ws => |_| C,
+        "a" => |_| A,
+        "b" => |_| B,
+    }}
+
+    let streams = lex(" a  b ").streams(|token| *token == C);
+    assert_eq!(streams.raw().len(), 6);
+    assert_eq!(streams.significant_len(), 2);
+    assert_eq!(streams.significant().rev().map(|(token, _)| token).collect::<Vec<_>>(), vec![&B, &A]);
+    assert_eq!(streams.trivia_before(0).len(), 1);
+    assert_eq!(streams.trivia_before(1).len(), 2);
+    assert_eq!(streams.trivia_before(2).len(), 1);
+    assert_eq!(streams.trivia_before(2)[0].1.get_abs_loc(), (5, 6));
+    assert_eq!(streams.into_raw().len(), 6);
+}
+
+#[test]
+fn layout_inserts_virtual_tokens() {
+    #[derive(Clone, Debug, PartialEq)]
+    enum Tok { Let, In, X, LBrace, RBrace, Open, Semi, Close }
+    use Tok::*;
+
+    lex_rule!{lex -> Tok {
+        ws => |_| continue,
+        "let" => |_| Let,
+        "in" => |_| In,
+        "x" => |_| X,
+        r"\{" => |_| LBrace,
+        r"\}" => |_| RBrace,
+    }}
+
+    let layout = lexr::Layout::new(Open, Semi, Close, |token| *token == Let)
+        .explicit_braces(|token| *token == LBrace, |token| *token == RBrace);
+    let source = "let x\n    x\nin let { x } let\nx";
+    let tokens = layout.apply(lex(source));
+    assert_eq!(tokens.iter().map(|(token, _)| token.clone()).collect::<Vec<_>>(), vec![
+        Let, Open, X, Semi, X, Close, In, Let, LBrace, X, RBrace, Let, Open, X, Close,
+    ]);
+    // The virtual tokens are zero-width, at the token they precede
+    assert_eq!(tokens[1].1.get_abs_loc(), (4, 4));
+    assert_eq!(tokens[3].1.to_string(), "2:5");
+    assert_eq!(tokens[5].1.get_abs_loc(), (12, 12));
+
+    let tokens = layout.apply(lex("let x"));
+    assert_eq!(tokens.iter().map(|(token, _)| token.clone()).collect::<Vec<_>>(), vec![Let, Open, X, Close]);
+    assert_eq!(tokens[3].1.get_abs_loc(), (5, 5));
+    assert_eq!(tokens[3].1.start(), (1, 6));
+
+    // A block not indented past the enclosing block is empty
+    let tokens = layout.top_level().apply(lex("let\nx"));
+    assert_eq!(tokens.into_iter().map(|(token, _)| token).collect::<Vec<_>>(), vec![Open, Let, Open, Close, Semi, X, Close]);
+}
+
+#[test]
+fn conditionals_splice_nested_regions() {
+    use lexr::{Conditionals, ConditionalError, Directive};
+
+    #[derive(Debug, PartialEq)]
+    enum Tok { If(bool), Elif(bool), Else, End, X(char) }
+    use Tok::*;
+
+    lex_rule!{lex -> Tok {
+        ws => |_| continue,
+        "#if [01]" => |s| If(s.ends_with('1')),
+        "#elif [01]" => |s| Elif(s.ends_with('1')),
+        "#else" => |_| Else,
+        "#end" => |_| End,
+        "[a-z]" => |s| X(s.chars().next().unwrap()),
+    }}
+
+    let conditionals = Conditionals::new(
+        |token| match token {
+            If(_) => Some(Directive::If),
+            Elif(_) => Some(Directive::ElseIf),
+            Else => Some(Directive::Else),
+            End => Some(Directive::EndIf),
+            X(_) => None,
+        },
+        |token| matches!(token, If(true) | Elif(true)),
+    );
+
+    let source = "a #if 0 b #if 1 c #end #elif 1 d #if 0 e #else f #end #else g #end h";
+    let spliced = conditionals.apply(lex(source)).unwrap();
+    assert_eq!(spliced.tokens.iter().map(|(token, _)| token).collect::<Vec<_>>(), vec![&X('a'), &X('d'), &X('f'), &X('h')]);
+    assert_eq!(spliced.tokens[1].1.get_abs_loc(), (31, 32));
+    assert_eq!(spliced.disabled.iter().map(|region| region.text(source)).collect::<Vec<_>>(), vec!["b #if 1 c #end", "e", "g"]);
+
+    assert_eq!(conditionals.apply(lex("a #else b")), Err(ConditionalError::Unmatched(lexr::SrcLoc::new((1, 3), (1, 7), (2, 7)))));
+    assert!(matches!(conditionals.apply(lex("#if 1 a #else b #else c #end")), Err(ConditionalError::Unmatched(_))));
+    assert_eq!(conditionals.apply(lex("#if 1 #if 0 a #end")).unwrap_err().to_string(), "Unterminated conditional directive at 1:1-5");
+}
+
+#[test]
+fn directive_scanner_parses_directives_in_trivia() {
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        r"/\*([^*]|\*[^/])*\*/" => |_| C,
+        "#[^\n]*" => |_| C,
+        "a" => |_| A,
+    }}
+
+    let source = "a /* lexr:allow x,y\n * not:a directive\n * lexr:skip */ a\n#pragma once\n# a #pragma no\n";
+    let scanner = lexr::DirectiveScanner::new("lexr:").prefix("#pragma ");
+    let trivia = lex(source).filter(|(token, _)| *token == C).map(|(_, loc)| loc);
+    let directives = scanner.scan(source, trivia);
+
+    assert_eq!(directives.iter().map(|directive| directive.name).collect::<Vec<_>>(), vec!["allow", "skip", "once"]);
+    let allow = &directives[0];
+    assert_eq!(allow.prefix, "lexr:");
+    assert_eq!(allow.args.iter().map(|arg| (arg.key, arg.value)).collect::<Vec<_>>(), vec![(None, "x"), (None, "y")]);
+    assert_eq!(allow.args[1].loc.to_string(), "1:19");
+    assert_eq!(allow.loc.text(source), "lexr:allow x,y");
+    assert_eq!(directives[1].loc.to_string(), "3:4-12");
+    assert_eq!(directives[1].name_loc.get_abs_loc(), (47, 51));
+    assert_eq!(directives[2].prefix, "#pragma ");
+    assert_eq!(directives[2].loc.text(source), "#pragma once");
+}
+
+#[test]
+fn embedded_lexer_shares_position() {
+    lex_rule!{outer -> Token {
+        ws => |_| continue,
+        "a" => |_| A,
+        "b" => |_| B,
+        eof => |_| Eof,
+    }}
+
+    lex_rule!{inner -> Token {
+        ws => |_| continue,
+        "a" => |_| C,
+        "b" => |_| break,
+    }}
+
+    let tokens = outer("a a b a a").embed(|token| *token == A, inner).collect::<Vec<_>>();
+    assert_eq!(tokens.iter().map(|(token, _)| token).collect::<Vec<_>>(), vec![&A, &C, &A, &C, &Eof]);
+    assert_eq!(tokens[2].1.get_abs_loc(), (6, 7));
+}
+
+#[test]
+fn token_classes_match_braces() {
+    use lexr::token_class::{self, Class, TokenClass};
+
+    #[derive(TokenClass)]
+    #[allow(dead_code)]
+    enum Tok {
+        #[class(open = "paren")] Open,
+        #[class(close = "paren")] Close,
+        #[class(open = "bracket")] OpenBracket,
+        #[class(close = "bracket")] CloseBracket,
+        #[class(comment)] Comment { text: String },
+        #[class(terminator)] Semi(char),
+        X,
+    }
+
+    assert_eq!(Tok::Comment { text: "//".into() }.class(), Class::Comment);
+    assert_eq!(Tok::Semi(';').class(), Class::Terminator);
+    assert_eq!(Tok::X.class(), Class::Other);
+    assert_eq!(Tok::CloseBracket.class().pair(), Some("bracket"));
+
+    let tokens: Vec<_> = [Tok::Open, Tok::OpenBracket, Tok::Close, Tok::X, Tok::CloseBracket, Tok::Close]
+        .into_iter().map(|token| (token, lexr::SrcLoc::new((1, 1), (1, 1), (0, 0)))).collect();
+    let braces = token_class::match_braces(&tokens);
+    assert_eq!(braces.pairs, vec![(1, 4), (0, 5)]);
+    assert_eq!(braces.unmatched, vec![2]);
+    assert_eq!(braces.partner(2), None);
+    assert_eq!(token_class::depths(&tokens), vec![0, 1, 2, 2, 1, 0]);
+}
+
+#[test]
+fn folding_ranges_cover_delimiters_comments_and_imports() {
+    use lexr::token_class::{self, TokenClass};
+
+    #[derive(TokenClass)]
+    enum Tok {
+        #[class(open = "brace")] Open,
+        #[class(close = "brace")] Close,
+        #[class(comment)] Comment,
+        #[class(import)] Use,
+        #[class(terminator)] Semi,
+        X,
+    }
+
+    lex_rule!{lex -> Tok {
+        ws => |_| continue,
+        r"\{" => |_| Tok::Open,
+        r"\}" => |_| Tok::Close,
+        "//[^\n]*" => |_| Tok::Comment,
+        r"/\*([^*]|\*[^/])*\*/" => |_| Tok::Comment,
+        "use" => |_| Tok::Use,
+        ";" => |_| Tok::Semi,
+        "[a-z]+" => |_| Tok::X,
+    }}
+
+    let source = "use a;\nuse b {\n  c };\nuse d;\n\nuse e;\n// one\n// two\nx /* a\n b */ { x }\n{\n}";
+    let tokens = lex(source).into_vec();
+    let ranges = token_class::folding_ranges(&tokens);
+    assert_eq!(ranges.iter().map(|loc| loc.to_string()).collect::<Vec<_>>(), vec!["1:1-4:6", "2:7-3:5", "7:1-8:6", "9:3-10:5", "11:1-12:1"]);
+    assert_eq!(ranges[0].text(source), "use a;\nuse b {\n  c };\nuse d;");
+}
+
+#[test]
+fn span_stamps_detect_and_relocate_drift() {
+    use lexr::{FileStamp, SpanStamp, SrcLoc};
+
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        "[a-zé]+" => |_| A,
+    }}
+
+    let old = "ab\ncd éf";
+    let loc = lex(old).last().unwrap().1;
+    assert_eq!(SrcLoc::from_bytes(&loc.to_bytes()), loc);
+    assert_eq!(loc.to_bytes()[32..40], 6u64.to_le_bytes());
+
+    let stamp = SpanStamp::new(loc, old);
+    assert_eq!(SpanStamp::from_bytes(&stamp.to_bytes()), stamp);
+    assert!(FileStamp::new(old).matches(old));
+
+    let new = "ab\n\ncd éf";
+    assert!(!FileStamp::new(old).matches(new));
+    assert!(!stamp.is_valid(new));
+    assert_eq!(stamp.relocate(new, 0), None);
+    let moved = stamp.relocate(new, 1).unwrap();
+    assert_eq!((moved.start(), moved.end(), moved.get_abs_loc()), ((3, 4), (3, 5), (7, 10)));
+    assert!(SpanStamp::new(moved, new).is_valid(new));
+    assert_eq!(lexr::span_stamp::stale(&[stamp, SpanStamp::new(moved, new)], "ab"), vec![0, 1]);
+}
+
+#[test]
+fn snippets_underline_the_covered_text() {
+    use lexr::SrcLoc;
+
+    let source = "a\n\tb 日本 c\nd\n";
+    // The tab expands to column 4, and the wide characters take two columns each
+    let wide = SrcLoc::new((2, 4), (2, 5), (5, 11));
+    assert_eq!(wide.display_snippet(source, 4), "2 |     b 日本 c\n  |       ^^^^");
+    // Several lines are each underlined where covered
+    let lines = SrcLoc::new((2, 6), (3, 1), (12, 15));
+    assert_eq!(lines.display_snippet(source, 2), "2 |   b 日本 c\n  |          ^\n3 | d\n  | ^");
+    // A zero-width location at the end is marked after the text
+    let end = SrcLoc::new((3, 2), (3, 2), (15, 15));
+    assert_eq!(end.display_snippet(source, 4), "3 | d\n  |  ^");
+}
+
+#[test]
+#[cfg(feature = "debugger")]
+fn debugger_traces_and_toggles_rules() {
+    use lexr::debugger::Debugger;
+
+    lex_rule!{lex -> Token {
+        ws => |_| continue,
+        bol "#" => |_| C,
+        "[a-z]+" not_followed_by("[0-9]") => |_| A,
+        "[a-z]" => |_| B,
+    }}
+
+    let mut debugger = Debugger::new(lex("").spec());
+    assert_eq!(debugger.describe("ab c1 #"), [
+        r#"  1:1-2  rule 3 "[a-z]+" not_followed_by("[0-9]")  "ab"  A"#,
+        r#"  1:3    rule 1 ws  " "  continue"#,
+        r#"  1:4    rule 4 "[a-z]"  "c"  B"#,
+        r#"  1:5    no rule matches "1""#,
+    ].join("\n"));
+    assert_eq!(debugger.trace("#").iter().map(|step| step.rule).collect::<Vec<_>>(), [Some(1)]);
+
+    let mut output = Vec::new();
+    debugger.run("ab\n:toggle 3\nab\n:toggle 9\n:quit\nab\n".as_bytes(), &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), [
+        r#"> "#,
+        r#"  1:1-2  rule 3 "[a-z]+" not_followed_by("[0-9]")  "ab"  A"#,
+        "\n> ",
+        r#"rule 3 "[a-z]+" not_followed_by("[0-9]") is off"#,
+        "\n> ",
+        r#"  1:1  rule 4 "[a-z]"  "a"  B"#,
+        "\n",
+        r#"  1:2  rule 4 "[a-z]"  "b"  B"#,
+        "\n> ",
+        "Unknown command `toggle 9`, expected `:rules`, `:toggle N` or `:quit`",
+        "\n> \n",
+    ].concat());
+}
@@ -0,0 +1,53 @@
+use lexr::{lex_rule, security::{self, SecurityWarning}};
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Ident, Comment, Other
+}
+
+lex_rule!{lex -> Token {
+    ws => |_| continue,
+    "//[^\n]*" => |_| Token::Comment,
+    unicode_ident => |_| Token::Ident,
+    _ => |_| Token::Other,
+}}
+
+#[test]
+fn bidi_controls_are_found_in_comments() {
+    let source = "a = 1 // \u{202E} } \u{2066}\nb";
+    let warnings = security::bidi_controls(source);
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].to_string(), "Bidirectional control character U+202E at 1:10");
+    assert_eq!(warnings[0].loc().get_abs_loc(), (9, 12));
+    assert!(matches!(warnings[1], SecurityWarning::BidiControl { c: '\u{2066}', .. }));
+}
+
+#[test]
+fn confusable_identifiers_are_found() {
+    // The `о` of the second identifier is Cyrillic. The third is all Cyrillic, and does not look like `bar`
+    let source = "foo fоo bar вар";
+    let tokens = lex(source).into_vec();
+    let warnings = security::check(source, &tokens, |token| *token == Token::Ident);
+
+    let messages: Vec<_> = warnings.iter().map(|w| w.to_string()).collect();
+    assert_eq!(messages, vec![
+        "Identifier `fоo` at 1:5-7 mixes letters of different scripts",
+        "Identifier `fоo` at 1:5-7 looks like `foo` at 1:1-3",
+    ]);
+}
+
+#[test]
+fn plain_identifiers_are_not_flagged() {
+    let source = "héllo мир // wörld";
+    let tokens = lex(source).into_vec();
+    assert_eq!(security::check(source, &tokens, |token| *token == Token::Ident), vec![]);
+}
+
+#[test]
+fn warnings_suggest_fixes() {
+    use lexr::Diagnostic;
+
+    let source = "a\u{202E}b";
+    let diagnostic: Diagnostic = security::bidi_controls(source).remove(0).into();
+    assert_eq!(diagnostic.suggestions[0].apply(source).unwrap(), "ab");
+}